@@ -1,54 +1,167 @@
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+/// byte offsets of a node in its source, as given by the lexer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnOp {
     Neg, // negate
     Not, // logical not
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BinOp {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
 
     Eq,
+    Neq,
     Lt,
     Gt,
+    Le,
+    Ge,
 
     And,
     Or,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// builtin functions on lists, the list is their first argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ListOp {
+    Map,    // map(xs, f)
+    Filter, // filter(xs, p)
+    Reduce, // reduce(xs, f, init), as fold
+    Length, // length(xs)
+    Head,   // head(xs), first element of a non empty list
+    Append, // append(xs, x), xs with x added at the end
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Assn {
     pub dest: String,
     pub src: Expr,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// statement of an action with conditions or nested actions,
+/// see `Expr::ActionBlock`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Stmt {
+    Assn(Assn),
+    Insert(Insert),
+    Edit(RowEdit),
+    If {                // if cond then { .. } else { .. }
+        cond: Expr,
+        then_stmts: Vec<Stmt>,
+        else_stmts: Vec<Stmt>,
+    },
+    Do(Expr),           // do other_action;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Insert {                    // insert {id: 1, ..}
     pub row: Expr,                     // each row is a vector
     pub table_name: String,
+    pub cond: Option<Expr>,            // of the ifs an insert is in, see `flatten_stmts`
+}
+
+/// edit of the rows of a table where a condition holds, which is evaluated
+/// with the columns of each row in scope
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RowEdit {
+    Update {                           // update items set price = price * 2 where id == 3
+        table_name: String,
+        sets: Vec<Assn>,               // dest is a column
+        where_clause: Expr,
+        cond: Option<Expr>,            // as of an insert
+    },
+    Delete {                           // delete from items where price > 100
+        table_name: String,
+        where_clause: Expr,
+        cond: Option<Expr>,
+    },
+}
+
+impl RowEdit {
+    pub fn table_name(&self) -> &String {
+        match self {
+            RowEdit::Update { table_name, .. } | RowEdit::Delete { table_name, .. } => table_name,
+        }
+    }
+
+    /// condition of the ifs the edit is in, evaluated without the columns
+    pub fn cond_mut(&mut self) -> &mut Option<Expr> {
+        match self {
+            RowEdit::Update { cond, .. } | RowEdit::Delete { cond, .. } => cond,
+        }
+    }
+}
+
+/// arm of a match, e.g. `Done(r) => r`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pattern {
+    Variant { name: String, binds: Vec<String> }, // Done(r), Pending
+    Var(String),                                  // any value, bound to the name, e.g. `_`
+}
+
+impl Pattern {
+    /// names bound in the body of the arm
+    pub fn binds(&self) -> Vec<String> {
+        match self {
+            Pattern::Variant { binds, .. } => binds.clone(),
+            Pattern::Var(name) => vec![name.clone()],
+        }
+    }
+
+    pub fn binds_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Pattern::Variant { binds, .. } => binds.iter_mut().collect(),
+            Pattern::Var(name) => vec![name],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Entry {
     pub name: String, // column name         id
     pub val: Expr, // value to be inserted    1
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Record {
     pub val: Vec<Expr>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Expr {
     /// Basic Lambda Core expressions
     Number {
-        val: i32,
+        val: i64,
+    },
+    Float {
+        val: OrderedFloat<f64>, // ordered, so exprs can still be compared and hashed
     },
     Bool {
         val: bool,
@@ -66,6 +179,9 @@ pub enum Expr {
         key: String,
         value: Box<Expr>,
     },
+    List { // [1, 2, 3]
+        val: Vec<Expr>,
+    },
     Unop {
         op: UnOp,
         expr: Box<Expr>,
@@ -84,20 +200,44 @@ pub enum Expr {
 
     Func {
         params: Vec<String>,
+        #[serde(default)]
+        param_typs: Vec<Option<TypeAnnot>>, // annotation of each param, if any
         body: Box<Expr>,
     },
     FuncApply {
         func: Box<Expr>,
         args: Vec<Expr>,
     },
+    Let { // let name = val in body, name is bound in body only
+        name: String,
+        annot: Option<TypeAnnot>,
+        val: Box<Expr>,
+        body: Box<Expr>,
+    },
 
     /// Action
     Action {
         assns: Vec<Assn>,
         inserts: Vec<Insert>,
+        edits: Vec<RowEdit>,
+    },
+    // action as written, evaluated to the Action doing all of
+    // its writes in one transaction
+    ActionBlock {
+        stmts: Vec<Stmt>,
     },
 
-    TableColumn { // table1.id for example will be treated as an expression and evaluated separately
+    RecordField { // person.name, on a record that is not a named value, e.g. f(x).name
+        expr: Box<Expr>,
+        name: String,
+    },
+    TupleIndex { // pair.0
+        expr: Box<Expr>,
+        idx: usize,
+    },
+
+    TableColumn { // table1.id for example will be treated as an expression and evaluated separately,
+                  // or a field of a record named table1
         table_name: String,
         column_name: String,
     },
@@ -118,61 +258,170 @@ pub enum Expr {
     },
     Fold {
         args: Vec<Expr>
-    }
+    },
+    ListOp {
+        op: ListOp,
+        args: Vec<Expr>,
+    },
+
+    /// value of a declared type, e.g. Pending or Done(3)
+    Variant {
+        name: String,
+        args: Vec<Expr>,
+    },
+    Match {
+        expr: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+
+    /// expr as parsed, with its position in the source for type errors
+    /// to point at, see `Prog::erase_spans`
+    Spanned {
+        span: Span,
+        expr: Box<Expr>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Decl {
     Import {
         srv_name: String,
+        span: Span,
     },
     VarDecl {
         name: String,
+        annot: Option<TypeAnnot>,
         val: Expr,
+        span: Span,
     },
     DefDecl {
         name: String,
+        annot: Option<TypeAnnot>,
         val: Expr,
         is_pub: bool,
+        span: Span,
     },
     TableDecl {
         name: String,
         fields: Vec<Field>,
+        span: Span,
+    },
+    TypeDecl { // type status = Pending | Done(int);
+        name: String,
+        variants: Vec<Variant>,
+        span: Span,
     },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl Decl {
+    pub fn span(&self) -> Span {
+        match self {
+            Decl::Import { span, .. }
+            | Decl::VarDecl { span, .. }
+            | Decl::DefDecl { span, .. }
+            | Decl::TableDecl { span, .. }
+            | Decl::TypeDecl { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Field {
     pub name: String,
     pub type_: DataType,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// type annotation written in the source, e.g. `(int, a) -> a`,
+/// names other than the builtin types are type variables
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TypeAnnot {
+    Int,
+    Float,
+    Bool,
+    String,
+    Action,
+    Var(String),
+    Fun(Vec<TypeAnnot>, Box<TypeAnnot>),
+    Record(Vec<(String, TypeAnnot)>), // {name: string, age: int}
+    Tuple(Vec<TypeAnnot>),            // {int, string}
+    List(Box<TypeAnnot>),             // list<int>
+}
+
+/// variant of a declared type, with the types of its fields
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<TypeAnnot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DataType {
     String,
     Number,
+    Float,
     Bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Service {
     pub name: String,
     pub decls: Vec<Decl>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl Service {
+    /// names of services imported by this service, in declaration order
+    pub fn imports(&self) -> Vec<String> {
+        self.decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::Import { srv_name, .. } => Some(srv_name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// names of vars, defs and tables not declared `pub` by this service
+    pub fn private_names(&self) -> HashSet<String> {
+        self.decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::VarDecl { name, .. }
+                | Decl::DefDecl { name, is_pub: false, .. }
+                | Decl::TableDecl { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// names of `pub def`s declared by this service
+    pub fn pub_names(&self) -> Vec<String> {
+        self.decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::DefDecl { name, is_pub: true, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Test {
     pub name: String,
     pub commands: Vec<ReplCmd>, // commands here refer to dos and asserts
+    pub span: Span,             // of `@test(name)`
+    pub cmd_spans: Vec<Span>,   // of each command
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Prog {
     pub services: Vec<Service>,
     pub tests: Vec<Test>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "expr")]
 pub enum ReplCmd {
     Do(Expr),
     Assert(Expr),
@@ -188,6 +437,141 @@ impl Default for Expr {
     }
 }
 
+impl Expr {
+    pub fn spanned(span: Span, expr: Expr) -> Expr {
+        Expr::Spanned { span, expr: Box::new(expr) }
+    }
+
+    /// the expr without the span the parser gave it, if any
+    pub fn unspanned(&self) -> &Expr {
+        match self {
+            Expr::Spanned { expr, .. } => expr.unspanned(),
+            expr => expr,
+        }
+    }
+
+    pub fn into_unspanned(self) -> Expr {
+        match self {
+            Expr::Spanned { expr, .. } => expr.into_unspanned(),
+            expr => expr,
+        }
+    }
+
+    /// drop the spans of the expr and all its sub-exprs
+    pub fn erase_spans(&mut self) {
+        match self {
+            Expr::Spanned { expr, .. } => {
+                *self = std::mem::take(expr.as_mut());
+                self.erase_spans();
+            }
+            Expr::Number { .. }
+            | Expr::Float { .. }
+            | Expr::Bool { .. }
+            | Expr::String { .. }
+            | Expr::Variable { .. }
+            | Expr::TableColumn { .. } => {}
+            Expr::Vector { val: exprs }
+            | Expr::List { val: exprs }
+            | Expr::Table { records: exprs, .. }
+            | Expr::Fold { args: exprs }
+            | Expr::ListOp { args: exprs, .. }
+            | Expr::Variant { args: exprs, .. } => exprs.iter_mut().for_each(Expr::erase_spans),
+            Expr::KeyVal { value: expr, .. }
+            | Expr::Unop { expr, .. }
+            | Expr::Func { body: expr, .. }
+            | Expr::RecordField { expr, .. }
+            | Expr::TupleIndex { expr, .. }
+            | Expr::Select { where_clause: expr, .. } => expr.erase_spans(),
+            Expr::Binop { expr1, expr2, .. } | Expr::Let { val: expr1, body: expr2, .. } => {
+                expr1.erase_spans();
+                expr2.erase_spans();
+            }
+            Expr::If { cond, expr1, expr2 } => {
+                cond.erase_spans();
+                expr1.erase_spans();
+                expr2.erase_spans();
+            }
+            Expr::FuncApply { func, args } => {
+                func.erase_spans();
+                args.iter_mut().for_each(Expr::erase_spans);
+            }
+            Expr::Action { assns, inserts, edits } => {
+                assns.iter_mut().for_each(|assn| assn.src.erase_spans());
+                inserts.iter_mut().for_each(Insert::erase_spans);
+                edits.iter_mut().for_each(RowEdit::erase_spans);
+            }
+            Expr::ActionBlock { stmts } => stmts.iter_mut().for_each(Stmt::erase_spans),
+            Expr::Match { expr, arms } => {
+                expr.erase_spans();
+                arms.iter_mut().for_each(|arm| arm.body.erase_spans());
+            }
+        }
+    }
+}
+
+impl Stmt {
+    pub fn erase_spans(&mut self) {
+        match self {
+            Stmt::Assn(assn) => assn.src.erase_spans(),
+            Stmt::Insert(insert) => insert.erase_spans(),
+            Stmt::Edit(edit) => edit.erase_spans(),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                cond.erase_spans();
+                then_stmts.iter_mut().chain(else_stmts).for_each(Stmt::erase_spans);
+            }
+            Stmt::Do(action) => action.erase_spans(),
+        }
+    }
+}
+
+impl Insert {
+    pub fn erase_spans(&mut self) {
+        self.row.erase_spans();
+        self.cond.iter_mut().for_each(Expr::erase_spans);
+    }
+}
+
+impl RowEdit {
+    pub fn erase_spans(&mut self) {
+        match self {
+            RowEdit::Update { sets, where_clause, cond, .. } => {
+                sets.iter_mut().for_each(|set| set.src.erase_spans());
+                where_clause.erase_spans();
+                cond.iter_mut().for_each(Expr::erase_spans);
+            }
+            RowEdit::Delete { where_clause, cond, .. } => {
+                where_clause.erase_spans();
+                cond.iter_mut().for_each(Expr::erase_spans);
+            }
+        }
+    }
+}
+
+impl ReplCmd {
+    pub fn erase_spans(&mut self) {
+        match self {
+            ReplCmd::Do(expr) | ReplCmd::Assert(expr) => expr.erase_spans(),
+        }
+    }
+}
+
+impl Prog {
+    /// drop the spans of all exprs once typechecked, so the runtime only
+    /// sees exprs as they are evaluated
+    pub fn erase_spans(&mut self) {
+        for srv in self.services.iter_mut() {
+            for decl in srv.decls.iter_mut() {
+                if let Decl::VarDecl { val, .. } | Decl::DefDecl { val, .. } = decl {
+                    val.erase_spans();
+                }
+            }
+        }
+        for test in self.tests.iter_mut() {
+            test.commands.iter_mut().for_each(ReplCmd::erase_spans);
+        }
+    }
+}
+
 impl Display for UnOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -204,30 +588,99 @@ impl Display for BinOp {
             BinOp::Sub => write!(f, "-"),
             BinOp::Mul => write!(f, "*"),
             BinOp::Div => write!(f, "/"),
+            BinOp::Mod => write!(f, "%"),
             BinOp::Eq => write!(f, "=="),
+            BinOp::Neq => write!(f, "!="),
             BinOp::Lt => write!(f, "<"),
             BinOp::Gt => write!(f, ">"),
+            BinOp::Le => write!(f, "<="),
+            BinOp::Ge => write!(f, ">="),
             BinOp::And => write!(f, "&&"),
             BinOp::Or => write!(f, "||"),
         }
     }
 }
 
+impl ListOp {
+    /// builtin named `name`, e.g. `map`
+    pub fn from_name(name: &str) -> Option<ListOp> {
+        match name {
+            "map" => Some(ListOp::Map),
+            "filter" => Some(ListOp::Filter),
+            "reduce" => Some(ListOp::Reduce),
+            "length" => Some(ListOp::Length),
+            "head" => Some(ListOp::Head),
+            "append" => Some(ListOp::Append),
+            _ => None,
+        }
+    }
+
+    /// params of the op as a function, e.g. `def f = map;`
+    pub fn params(&self) -> Vec<String> {
+        let params: &[&str] = match self {
+            ListOp::Map => &["xs", "f"],
+            ListOp::Filter => &["xs", "p"],
+            ListOp::Reduce => &["xs", "f", "init"],
+            ListOp::Length | ListOp::Head => &["xs"],
+            ListOp::Append => &["xs", "x"],
+        };
+        params.iter().map(|p| p.to_string()).collect()
+    }
+}
+
+impl Display for ListOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListOp::Map => write!(f, "map"),
+            ListOp::Filter => write!(f, "filter"),
+            ListOp::Reduce => write!(f, "reduce"),
+            ListOp::Length => write!(f, "length"),
+            ListOp::Head => write!(f, "head"),
+            ListOp::Append => write!(f, "append"),
+        }
+    }
+}
+
 impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expr::Number { val } => write!(f, "{}", val),
+            Expr::Float { val } => write!(f, "{:?}", val.0), // keeps the `.0` of whole floats
             Expr::Bool { val } => write!(f, "{}", val),
             Expr::String { val } => write!(f, "{}", val),
-            Expr::Vector { val } => write!(f, "vector"),
-            Expr::KeyVal { key, value } => write!(f, "keyval: {}, {}", key, value),
+            Expr::Vector { val } => {
+                let val = val.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{{{}}}", val.join(", "))
+            }
+            Expr::KeyVal { key, value } => write!(f, "{}: {}", key, value),
+            Expr::List { val } => {
+                let val = val.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "[{}]", val.join(", "))
+            }
             Expr::Variable { ident } => write!(f, "{}", ident),
             Expr::Unop { op, expr } => write!(f, "{}{}", op, expr),
             Expr::Binop { op, expr1, expr2 } => write!(f, "{} {} {}", expr1, op, expr2),
             Expr::If { cond, expr1, expr2 } => {
                 write!(f, "if {} then {} else {}", cond, expr1, expr2)
             }
-            Expr::Func { params, body } => write!(f, "fn({})[{}]", params.join(","), body),
+            Expr::Func { params, param_typs, body } => {
+                let params = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| match param_typs.get(i) {
+                        Some(Some(annot)) => format!("{}: {}", param, annot),
+                        _ => param.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "fn({})[{}]", params.join(","), body)
+            }
+            Expr::Let { name, annot, val, body } => {
+                write!(f, "let {}", name)?;
+                if let Some(annot) = annot {
+                    write!(f, ": {}", annot)?;
+                }
+                write!(f, " = {} in {}", val, body)
+            }
             Expr::FuncApply { func, args } =>
                 write!(
                     f,
@@ -235,14 +688,20 @@ impl Display for Expr {
                     func,
                     args.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
                 ),
-            Expr::Action { assns, inserts } =>
+            Expr::Action { assns, .. } =>
                 write!(
                     f,
                     "Action({:?})",
                     assns.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
                 ),
+            Expr::ActionBlock { stmts } => {
+                let stmts = stmts.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "action {{ {} }}", stmts.join(" "))
+            }
             Expr::TableColumn { table_name, column_name } =>
                 write!(f, "{}.{}", table_name, column_name),
+            Expr::RecordField { expr, name } => write!(f, "{}.{}", expr, name),
+            Expr::TupleIndex { expr, idx } => write!(f, "{}.{}", expr, idx),
             Expr::Select { table_name, column_names, where_clause } => write!(f, "{}", where_clause),
             Expr::Table {records , ..} => {
                 write!(f, "[",)?;
@@ -268,7 +727,24 @@ impl Display for Expr {
             }
             write!(f, "]")
             },
-            Expr::Fold { .. } => write!(f, "fold")
+            Expr::Fold { .. } => write!(f, "fold"),
+            Expr::ListOp { op, args } => {
+                let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{}({})", op, args.join(", "))
+            }
+            Expr::Variant { name, args } if args.is_empty() => write!(f, "{}", name),
+            Expr::Variant { name, args } => {
+                let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expr::Match { expr, arms } => {
+                let arms = arms
+                    .iter()
+                    .map(|arm| format!("{} => {}", arm.pattern, arm.body))
+                    .collect::<Vec<_>>();
+                write!(f, "match {} {{ {} }}", expr, arms.join(", "))
+            }
+            Expr::Spanned { expr, .. } => write!(f, "{}", expr),
         }
     }
 }
@@ -279,19 +755,119 @@ impl Display for Assn {
     }
 }
 
+impl Display for RowEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowEdit::Update { table_name, sets, where_clause, .. } => {
+                let sets = sets.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "update {} set {} where {}", table_name, sets.join(", "), where_clause)
+            }
+            RowEdit::Delete { table_name, where_clause, .. } => {
+                write!(f, "delete from {} where {}", table_name, where_clause)
+            }
+        }
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let block = |stmts: &Vec<Stmt>| stmts.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ");
+        match self {
+            Stmt::Assn(assn) => write!(f, "{};", assn),
+            Stmt::Insert(insert) => write!(f, "insert {} into {}", insert.row, insert.table_name),
+            Stmt::Edit(edit) => write!(f, "{};", edit),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                write!(f, "if {} then {{ {} }}", cond, block(then_stmts))?;
+                if !else_stmts.is_empty() {
+                    write!(f, " else {{ {} }}", block(else_stmts))?;
+                }
+                Ok(())
+            }
+            Stmt::Do(action) => write!(f, "do {};", action),
+        }
+    }
+}
+
 impl Display for Decl {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Decl::Import { srv_name } => todo!(),
-            Decl::VarDecl { name, val } => { write!(f, "var {} = {}", name, val) }
-            Decl::DefDecl { name, val, is_pub } => {
+            Decl::Import { srv_name, .. } => write!(f, "import {}", srv_name),
+            Decl::VarDecl { name, annot, val, .. } => {
+                write!(f, "var {}", name)?;
+                if let Some(annot) = annot {
+                    write!(f, ": {}", annot)?;
+                }
+                write!(f, " = {}", val)
+            }
+            Decl::DefDecl { name, annot, val, is_pub, .. } => {
                 if *is_pub {
-                    write!(f, "pub def {} = {}", name, val)
-                } else {
-                    write!(f, "def {} = {}", name, val)
+                    write!(f, "pub ")?;
+                }
+                write!(f, "def {}", name)?;
+                if let Some(annot) = annot {
+                    write!(f, ": {}", annot)?;
+                }
+                write!(f, " = {}", val)
+            }
+            Decl::TableDecl { name, fields, .. } => { write!(f, "table {} created", name) }
+            Decl::TypeDecl { name, variants, .. } => {
+                let variants = variants.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "type {} = {}", name, variants.join(" | "))
+            }
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.fields.is_empty() {
+            let fields = self.fields.iter().map(ToString::to_string).collect::<Vec<_>>();
+            write!(f, "({})", fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Variant { name, binds } if binds.is_empty() => write!(f, "{}", name),
+            Pattern::Variant { name, binds } => write!(f, "{}({})", name, binds.join(", ")),
+            Pattern::Var(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Display for TypeAnnot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeAnnot::Int => write!(f, "int"),
+            TypeAnnot::Float => write!(f, "float"),
+            TypeAnnot::Bool => write!(f, "bool"),
+            TypeAnnot::String => write!(f, "string"),
+            TypeAnnot::Action => write!(f, "action"),
+            TypeAnnot::Var(name) => write!(f, "{}", name),
+            TypeAnnot::Fun(params, ret) => match params.as_slice() {
+                [param @ TypeAnnot::Fun(..)] => write!(f, "({}) -> {}", param, ret),
+                [param] => write!(f, "{} -> {}", param, ret),
+                _ => {
+                    let params = params.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    write!(f, "({}) -> {}", params.join(", "), ret)
                 }
+            },
+            TypeAnnot::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, annot)| format!("{}: {}", name, annot))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            TypeAnnot::Tuple(annots) => {
+                let annots = annots.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{{{}}}", annots.join(", "))
             }
-            Decl::TableDecl { name, fields } => { write!(f, "table {} created", name) }
+            TypeAnnot::List(annot) => write!(f, "list<{}>", annot),
         }
     }
 }
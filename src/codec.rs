@@ -0,0 +1,182 @@
+//! stable JSON encoding of programs, transactions and runtime messages
+//!
+//! every encoded value is wrapped in an envelope carrying the encoding
+//! version, decoding a value of another version fails instead of silently
+//! misreading it. bump `ENCODING_VERSION` whenever the JSON shape changes,
+//! e.g. renaming a variant or field of `Expr`, `Decl`, `Msg` or `CmdMsg`.
+//!
+//! shape of values:
+//! - enums with fields are tagged by `kind`, e.g.
+//!   `{"kind": "Number", "val": 1}` for `Expr::Number { val: 1 }`
+//! - `ReplCmd` keeps its expression under `expr`, e.g.
+//!   `{"kind": "Do", "expr": {"kind": "Variable", "ident": "inc"}}`
+//! - enums without fields are strings, e.g. `"Add"` for `BinOp::Add`
+//! - `TxnId` is `{"time": <ns since unix epoch>, "iteration": <retry>,
+//!   "process": <random id of the issuing process>}`
+//! - `Span` is `{"start": <byte offset>, "end": <byte offset>}`, carried by
+//!   decls and tests. exprs carry theirs in `Expr::Spanned` only until
+//!   typechecked, programs are encoded without them
+//! - actor and client addresses are `{"node": <listening addr>, "id": <n>}`
+use std::fmt::Display;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const ENCODING_VERSION: u32 = 5;
+
+#[derive(Debug)]
+pub enum CodecError {
+    Json(serde_json::Error),
+    Version { expected: u32, found: u32 },
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Json(e) => write!(f, "invalid json encoding: {}", e),
+            CodecError::Version { expected, found } => write!(
+                f,
+                "json encoding version {} is not supported, expected {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<serde_json::Error> for CodecError {
+    fn from(e: serde_json::Error) -> Self {
+        CodecError::Json(e)
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    body: &'a T,
+}
+
+/// version is checked before decoding the body
+#[derive(Deserialize)]
+struct RawEnvelope {
+    version: u32,
+    body: serde_json::Value,
+}
+
+pub fn to_json<T: Serialize>(val: &T) -> Result<String, CodecError> {
+    Ok(serde_json::to_string(&Envelope {
+        version: ENCODING_VERSION,
+        body: val,
+    })?)
+}
+
+pub fn to_json_pretty<T: Serialize>(val: &T) -> Result<String, CodecError> {
+    Ok(serde_json::to_string_pretty(&Envelope {
+        version: ENCODING_VERSION,
+        body: val,
+    })?)
+}
+
+pub fn to_json_bytes<T: Serialize>(val: &T) -> Result<Vec<u8>, CodecError> {
+    Ok(serde_json::to_vec(&Envelope {
+        version: ENCODING_VERSION,
+        body: val,
+    })?)
+}
+
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, CodecError> {
+    decode(serde_json::from_str(json)?)
+}
+
+pub fn from_json_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+    decode(serde_json::from_slice(bytes)?)
+}
+
+fn decode<T: DeserializeOwned>(envelope: RawEnvelope) -> Result<T, CodecError> {
+    if envelope.version != ENCODING_VERSION {
+        return Err(CodecError::Version {
+            expected: ENCODING_VERSION,
+            found: envelope.version,
+        });
+    }
+    Ok(serde_json::from_value(envelope.body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Assn, BinOp, Expr, Insert, RowEdit};
+    use crate::runtime::message::Msg;
+    use crate::runtime::transaction::{Txn, TxnId};
+
+    fn txn() -> Txn {
+        let var = |ident: &str| Expr::Variable { ident: ident.to_string() };
+        Txn::new(
+            TxnId::new().retry_id(),
+            vec![Assn {
+                dest: "count".to_string(),
+                src: Expr::Binop {
+                    op: BinOp::Add,
+                    expr1: Box::new(var("count")),
+                    expr2: Box::new(Expr::Number { val: 1 }),
+                },
+            }],
+            vec![Insert {
+                row: Expr::Vector { val: vec![Expr::String { val: "a".to_string() }] },
+                table_name: "items".to_string(),
+                cond: Some(var("open")),
+            }],
+            vec![RowEdit::Delete {
+                table_name: "items".to_string(),
+                where_clause: Expr::Bool { val: true },
+                cond: None,
+            }],
+        )
+    }
+
+    #[test]
+    fn txn_round_trip() {
+        let txn = txn();
+        let decoded: Txn = from_json(&to_json(&txn).unwrap()).unwrap();
+        assert_eq!(decoded.id, txn.id);
+        assert_eq!(decoded.id.process, txn.id.process);
+        assert_eq!(decoded.assns, txn.assns);
+        assert_eq!(decoded.inserts, txn.inserts);
+        assert_eq!(decoded.edits, txn.edits);
+    }
+
+    #[test]
+    fn msg_round_trip() {
+        let txn = txn();
+        let msg = Msg::UsrReadVarResult {
+            txn: txn.id.clone(),
+            name: "count".to_string(),
+            result: Expr::Number { val: 3 },
+            pred: Some(txn.clone()),
+        };
+        let bytes = to_json_bytes(&msg).unwrap();
+        let Msg::UsrReadVarResult { txn: id, name, result, pred } = from_json_bytes(&bytes).unwrap()
+        else {
+            panic!("decoded another variant of Msg");
+        };
+        assert_eq!(id, txn.id);
+        assert_eq!(name, "count");
+        assert_eq!(result, Expr::Number { val: 3 });
+        assert_eq!(pred.map(|pred| pred.assns), Some(txn.assns));
+    }
+
+    #[test]
+    fn reject_other_version() {
+        let version = |version: u32| format!("\"version\":{}", version);
+        let json = to_json(&TxnId::new())
+            .unwrap()
+            .replacen(&version(ENCODING_VERSION), &version(ENCODING_VERSION - 1), 1);
+        match from_json::<TxnId>(&json) {
+            Err(CodecError::Version { expected, found }) => {
+                assert_eq!(expected, ENCODING_VERSION);
+                assert_eq!(found, ENCODING_VERSION - 1);
+            }
+            other => panic!("decoded another version: {:?}", other),
+        }
+    }
+}
@@ -1,9 +1,83 @@
-use std::fmt::{Display};
-use std::io;
-use std::result;
+//! diagnostics reported to the developer, pointing into the source
+//!
+//! a diagnostic renders as
+//! ```text
+//! error: cannot unify right hand side bool and int
+//!  --> tests/test0.meerkat:5:5
+//!   |
+//! 5 |     def g = x + true;
+//!   |     ^^^^^^^^^^^^^^^^^
+//!   = note: ...
+//! ```
+use std::fmt::Display;
 
-pub type Result<T> = result::Result<T, Error>;
+use crate::ast::Span;
 
-pub enum Error {
-    Message(String),
-}
\ No newline at end of file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,   // 1-based
+    pub column: usize, // 1-based, in chars
+    pub message: String,
+    pub notes: Vec<String>,
+
+    snippet: String,  // source line of the span
+    underline: usize, // chars of the span on that line
+}
+
+impl Diagnostic {
+    /// diagnostic at `span` of `source`, read from `file`
+    pub fn new(file: &str, source: &str, span: Span, message: String) -> Self {
+        let start = floor_char_boundary(source, span.start.min(source.len()));
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let end = floor_char_boundary(source, span.end.clamp(start, line_end));
+
+        Diagnostic {
+            file: file.to_string(),
+            line: source[..start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            message,
+            notes: vec![],
+            snippet: source[line_start..line_end].trim_end().to_string(),
+            underline: source[start..end].chars().count().max(1),
+        }
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{}--> {}:{}:{}", gutter, self.file, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.snippet)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column - 1),
+            "^".repeat(self.underline)
+        )?;
+        for note in self.notes.iter() {
+            write!(f, "\n{} = note: {}", gutter, note)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+fn floor_char_boundary(source: &str, mut idx: usize) -> usize {
+    while !source.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
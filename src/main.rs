@@ -1,19 +1,28 @@
 use clap::Parser;
 use log::LevelFilter;
 use std::error::Error;
+use std::time::Duration;
 use std::{env, fs};
 
 use parser::meerkat;
+use runtime::report::{self, ReportFormat};
 
 use tokio;
 
 pub mod ast;
+pub mod codec;
+pub mod error;
 pub mod parser;
 pub mod runtime;
 pub mod static_analysis;
 
 /// example usage
 /// cargo run -- -f test0.meerkat -v
+/// cargo run -- -f test0.meerkat -i
+///
+/// services on separate nodes
+/// cargo run -- -f test_import.meerkat -l tcp://127.0.0.1:7000 -s counter
+/// cargo run -- -f test_import.meerkat -s dashboard -p tcp://127.0.0.1:7000
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
@@ -24,6 +33,37 @@ struct Args {
     /// all such printing go to info!("...")
     #[arg(short = 'v', long = "verbose", default_value_t = false)]
     verbose: bool,
+
+    /// start services and read do/assert commands from the prompt,
+    /// instead of running the @test blocks
+    #[arg(short = 'i', long = "interactive", default_value_t = false)]
+    interactive: bool,
+
+    /// run as a node serving its actors on this address,
+    /// e.g. tcp://127.0.0.1:7000 or unix:///tmp/meerkat.sock
+    #[arg(short = 'l', long = "listen")]
+    listen: Option<String>,
+
+    /// services hosted by this node, all services if not given
+    #[arg(short = 's', long = "service")]
+    services: Vec<String>,
+
+    /// nodes to look up imported services on
+    #[arg(short = 'p', long = "peer")]
+    peers: Vec<String>,
+
+    /// milliseconds an assert waits for its result before it fails
+    #[arg(short = 't', long = "timeout", default_value_t = runtime::TIMEOUT_INTERVAL)]
+    timeout: u64,
+
+    /// format test results are written in, besides the printed summary
+    #[arg(long = "format", value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// file test results are written to, stdout if not given, in which
+    /// case the summary and progress are printed to stderr
+    #[arg(short = 'o', long = "output")]
+    output: Option<String>,
 }
 
 #[tokio::main]
@@ -41,12 +81,63 @@ pub async fn main() -> Result<(), Box<dyn Error>> {
 
     let file_name = args.input_file; // the second argument be test.meerkat
 
-    let prog = parser::parser::parse(file_name.clone()) // using iterator instead of string for updated parse
-        .map_err(|e| format!("Parse error: {e}"))?;
+    let source = fs::read_to_string(&file_name)
+        .map_err(|e| format!("Couldn't read file {}: {}", file_name, e))?;
+
+    // diagnostics point into the source, print them as they are
+    let mut prog = parser::parser::parse_source(&file_name, &source).unwrap_or_else(|diag| {
+        eprintln!("{}", diag);
+        std::process::exit(1);
+    });
+    // checked first, typecheck would only see names used before declared
+    if let Err(cycles) = static_analysis::var_analysis::cycles::check_cycles(&prog) {
+        for cycle in cycles.iter() {
+            eprintln!("{}\n", cycle.to_diagnostic(&file_name, &source));
+        }
+        eprintln!("{} dependency cycle(s), not running {}", cycles.len(), file_name);
+        std::process::exit(1);
+    }
+    // ill-typed programs never reach the runtime
+    if let Err(errors) = static_analysis::typecheck::typecheck_prog(&prog) {
+        for e in errors.iter() {
+            eprintln!("{}\n", e.to_diagnostic(&file_name, &source));
+        }
+        eprintln!("{} type error(s), not running {}", errors.len(), file_name);
+        std::process::exit(1);
+    }
+    prog.erase_spans();
+    let node = runtime::transport::NodeConfig {
+        listen: args.listen,
+        services: args.services,
+        peers: args.peers,
+    };
+    let timeout = Duration::from_millis(args.timeout);
+    if args.interactive {
+        runtime::repl::run_repl(&prog, &node, timeout).await?;
+    } else {
+        // stdout carries only the report, e.g. for `--format json | jq`
+        if args.format != ReportFormat::Text && args.output.is_none() {
+            runtime::progress_to_stderr();
+        }
+        let reports = runtime::run(&prog, &node, timeout).await?;
+        let passed = report::print_summary(&reports);
+
+        let output = match args.format {
+            ReportFormat::Text => None,
+            ReportFormat::Junit => Some(report::to_junit(&reports)),
+            ReportFormat::Json => Some(report::to_json(&reports)),
+        };
+        match (output, args.output) {
+            (Some(output), Some(path)) => fs::write(path, output)?,
+            (Some(output), None) => print!("{}", output),
+            (None, _) => {}
+        }
 
-    let _ = static_analysis::typecheck::typecheck_prog(&prog);
-    let _ = runtime::run(&prog).await;
+        // exit code lets CI gate on the tests
+        if !passed {
+            std::process::exit(1);
+        }
+    }
 
-    // runtime.repl
     Ok(())
 }
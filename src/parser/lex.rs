@@ -13,21 +13,21 @@ use logos::{Lexer, Logos, Skip};
 use std::{fmt, num::ParseIntError};
 use strum_macros::AsRefStr;
 
-fn from_num<'b>(lex: &mut Lexer<'b, Token<'b>>) -> Result<i32, String> {
+fn from_num<'b>(lex: &mut Lexer<'b, Token<'b>>) -> Result<i64, String> {
     let slice = lex.slice();
 
-    let res = slice.parse();
+    // All numbers are positive because - is lexed seperately
+    slice
+        .parse()
+        .map_err(|_| format!("Number {} is out of bounds", slice))
+}
 
-    if res.is_err() {
-        return Err(format!("Parsing failed wtih Error {:?}", res.unwrap_err()));
-    }
-    let out: i64 = res.unwrap();
-    if out > ((i32::MIN as i64).abs()) {
-        // All numbers are positive because - is lexed seperately
-        return Err(format!("Number {} is out of bounds", out));
-    }
+fn from_float<'b>(lex: &mut Lexer<'b, Token<'b>>) -> Result<f64, String> {
+    let slice = lex.slice();
 
-    Ok(out as i32) // returning i32 since numbers are defined as i32
+    slice
+        .parse()
+        .map_err(|err| format!("Parsing failed wtih Error {:?}", err))
 }
 
 fn skip_multi_line_comments<'b>(lex: &mut Lexer<'b, Token<'b>>) -> Skip {
@@ -80,11 +80,14 @@ impl<'a> fmt::Display for Token<'a> {
 pub enum Token<'a> {
   #[regex(r#""[^"]*""#, |lex| lex.slice().trim_matches('"'))] // regex for string within ""
   StrLit(&'a str),
+  // also names of variants, told apart once parsed, see `ctors`
   #[regex(r"(?&identifier)")]
   Ident(&'a str),
 
     #[regex(r"0|[1-9][0-9]*", from_num)]
-    Number(i32),
+    Number(i64),
+    #[regex(r"[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?", from_float)]
+    Float(f64),
 
     #[token("true")]
     TRUE,
@@ -100,20 +103,32 @@ pub enum Token<'a> {
     Asterisk,
     #[token("/")]
     Div,
+    #[token("%")]
+    Percent,
     #[token("=")]
     Assgn,
     #[token("=>")]
     Fn_Assgn,
+    #[token("->")]
+    Arrow,
     #[token("==")]
     EQ_EQ,
+    #[token("!=")]
+    NOT_EQ,
     #[token("<")]
     LT,
     #[token(">")]
     GT,
+    #[token("<=")]
+    LT_EQ,
+    #[token(">=")]
+    GT_EQ,
     #[token("&&")]
     AND_AND,
     #[token("||")]
     OR_OR,
+    #[token("|")]
+    Bar,
     #[token("!")]
     NOT_NOT,
   
@@ -180,6 +195,14 @@ pub enum Token<'a> {
   IF_KW,
   #[token("else")]
   ELSE_KW,
+  #[token("type")]
+  TYPE_KW,
+  #[token("match")]
+  MATCH_KW,
+  #[token("let")]
+  LET_KW,
+  #[token("in")]
+  IN_KW,
   #[token("number")]
   NUMBER_KW,
   #[token("string")]
@@ -11,28 +11,98 @@ use lalrpop_util::lalrpop_mod;
 lalrpop_mod!(pub meerkat, "/parser/meerkat.rs");
 
 pub mod lex;
+pub mod resolve;
 
 pub mod parser {
-    use logos::{Logos, Span};
-    use std::fs;
+    use lalrpop_util::ParseError;
+    use logos::{Logos, Span as LexSpan};
 
+    use super::resolve::{self, Scope};
     use super::lex::Token;
     use super::meerkat;
-    use crate::ast::Prog;
+    use crate::ast::{Prog, ReplCmd, Span};
+    use crate::error::Diagnostic;
 
-    fn parse_string(input: String) -> Result<Prog, String> {
+    /// parse program `source` read from `file`
+    pub fn parse_source(file: &str, source: &str) -> Result<Prog, Diagnostic> {
         // You'll need lexer_with_extras later trust me :)
-        let lex_stream = Token::lexer_with_extras(&input, ())
+        let lex_stream = Token::lexer_with_extras(source, ())
             .spanned()
-            .map(|(t, y): (Token, Span)| (y.start, t, y.end));
+            .map(|(t, y): (Token, LexSpan)| (y.start, t, y.end));
 
-        meerkat::ProgParser::new()
+        let mut prog = meerkat::ProgParser::new()
             .parse(lex_stream)
-            .map_err(|e| format!("Couldn't parse file. Failed with message {:?}", e))
+            .map_err(|e| parse_error(file, source, e))?;
+        resolve::resolve_prog(&mut prog)
+            .map_err(|(span, message)| Diagnostic::new(file, source, span, message))?;
+        Ok(prog)
     }
-    pub fn parse(file_name: String) -> Result<Prog, String> {
-        let str_file = fs::read_to_string(file_name).expect("Couldn't read file");
 
-        parse_string(str_file)
+    /// parse a single `do <expr>;` or `assert(<expr>);`, on a service of
+    /// the running program whose names are in `scope`
+    pub fn parse_repl_cmd(input: &str, scope: &Scope) -> Result<ReplCmd, String> {
+        let lex_stream = Token::lexer_with_extras(input, ())
+            .spanned()
+            .map(|(t, y): (Token, LexSpan)| (y.start, t, y.end));
+
+        let mut cmd = meerkat::ReplCmdParser::new().parse(lex_stream).map_err(|e| {
+            let diag = parse_error("<input>", input, e);
+            std::iter::once(diag.message).chain(diag.notes).collect::<Vec<_>>().join(", ")
+        })?;
+        resolve::resolve_cmd(&mut cmd, scope);
+        Ok(cmd)
+    }
+
+    fn parse_error(
+        file: &str,
+        source: &str,
+        error: ParseError<usize, Token, String>,
+    ) -> Diagnostic {
+        let expected_note = |expected: Vec<String>| match expected.len() {
+            0 => None,
+            1 => Some(format!("expected {}", expected[0])),
+            _ => Some(format!("expected one of {}", expected.join(", "))),
+        };
+
+        let (span, message, note) = match error {
+            ParseError::InvalidToken { location } => (
+                Span::new(location, location + 1),
+                "invalid token".to_string(),
+                None,
+            ),
+            ParseError::UnrecognizedEof { location, expected } => (
+                Span::new(location, location),
+                "unexpected end of input".to_string(),
+                expected_note(expected),
+            ),
+            ParseError::UnrecognizedToken {
+                token: (l, Token::Error, r),
+                ..
+            } => (
+                Span::new(l, r),
+                format!("invalid token `{}`", &source[l..r]),
+                None,
+            ),
+            ParseError::UnrecognizedToken {
+                token: (l, _, r),
+                expected,
+            } => (
+                Span::new(l, r),
+                format!("unexpected `{}`", &source[l..r]),
+                expected_note(expected),
+            ),
+            ParseError::ExtraToken { token: (l, _, r) } => (
+                Span::new(l, r),
+                format!("unexpected `{}` after the end of input", &source[l..r]),
+                None,
+            ),
+            ParseError::User { error } => (Span::default(), error, None),
+        };
+
+        let diag = Diagnostic::new(file, source, span, message);
+        match note {
+            Some(note) => diag.with_note(note),
+            None => diag,
+        }
     }
 }
@@ -0,0 +1,238 @@
+//! what names refer to, once parsed
+//!
+//! the lexer has a single identifier token, so `Done` parses as a variable
+//! and `Done(3)` as a call, as do builtins, e.g. `map(xs, f)`.
+//! - a name declared as a variant of a type of the program is resolved to
+//!   its constructor, unless bound by a let, a parameter or a match arm. in a
+//!   pattern, such a name is always the constructor. other capitalized names
+//!   are ordinary names, e.g. `var Total`.
+//! - a builtin list op, e.g. `map`, is resolved to the op unless bound, or
+//!   declared by the service or imported. one not applied is a function of
+//!   its arguments, e.g. `def f = map;`.
+use std::collections::HashSet;
+
+use crate::ast::{Decl, Expr, ListOp, Pattern, Prog, ReplCmd, RowEdit, Service, Span, Stmt};
+
+/// names an expr of a service may refer to, besides builtins
+pub struct Scope {
+    ctors: HashSet<String>,    // variants declared by the types of all services
+    declared: HashSet<String>, // names of the service and public names it imports
+}
+
+impl Scope {
+    pub fn new(prog: &Prog, srv: Option<&Service>) -> Self {
+        let ctors = prog
+            .services
+            .iter()
+            .flat_map(|srv| srv.decls.iter())
+            .filter_map(|decl| match decl {
+                Decl::TypeDecl { variants, .. } => Some(variants.iter().map(|v| v.name.clone())),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        let mut declared = HashSet::new();
+        if let Some(srv) = srv {
+            declared.extend(srv.private_names());
+            declared.extend(srv.pub_names());
+            for imported in prog.services.iter().filter(|other| srv.imports().contains(&other.name)) {
+                declared.extend(imported.pub_names());
+            }
+        }
+        Scope { ctors, declared }
+    }
+
+    /// scope of service `srv_name` of `prog`, if declared
+    pub fn of_srv(prog: &Prog, srv_name: &String) -> Self {
+        Scope::new(prog, prog.services.iter().find(|srv| &srv.name == srv_name))
+    }
+
+    fn ctor(&self, name: &String, var_binded: &HashSet<String>) -> bool {
+        self.ctors.contains(name) && !var_binded.contains(name)
+    }
+
+    fn builtin(&self, name: &String, var_binded: &HashSet<String>) -> Option<ListOp> {
+        if var_binded.contains(name) || self.declared.contains(name) {
+            return None;
+        }
+        ListOp::from_name(name)
+    }
+}
+
+/// resolve names in all services and tests, a var, def or table named as a
+/// variant is an error at its decl
+pub fn resolve_prog(prog: &mut Prog) -> Result<(), (Span, String)> {
+    let scopes = prog
+        .services
+        .iter()
+        .map(|srv| Scope::new(prog, Some(srv)))
+        .collect::<Vec<_>>();
+    let test_scopes = prog
+        .tests
+        .iter()
+        .map(|test| Scope::of_srv(prog, &test.name))
+        .collect::<Vec<_>>();
+
+    for (srv, scope) in prog.services.iter_mut().zip(scopes.iter()) {
+        for decl in srv.decls.iter_mut() {
+            match decl {
+                Decl::VarDecl { name, val, span, .. } | Decl::DefDecl { name, val, span, .. } => {
+                    if scope.ctors.contains(name) {
+                        return Err((*span, format!("{} is already declared as a variant", name)));
+                    }
+                    resolve(val, scope, &HashSet::new());
+                }
+                Decl::TableDecl { name, span, .. } if scope.ctors.contains(name) => {
+                    return Err((*span, format!("{} is already declared as a variant", name)));
+                }
+                Decl::Import { .. } | Decl::TableDecl { .. } | Decl::TypeDecl { .. } => {}
+            }
+        }
+    }
+    for (test, scope) in prog.tests.iter_mut().zip(test_scopes.iter()) {
+        for cmd in test.commands.iter_mut() {
+            resolve_cmd(cmd, scope);
+        }
+    }
+    Ok(())
+}
+
+pub fn resolve_cmd(cmd: &mut ReplCmd, scope: &Scope) {
+    match cmd {
+        ReplCmd::Do(expr) | ReplCmd::Assert(expr) => resolve(expr, scope, &HashSet::new()),
+    }
+}
+
+fn resolve(expr: &mut Expr, scope: &Scope, var_binded: &HashSet<String>) {
+    match expr {
+        Expr::Number { .. } | Expr::Float { .. } | Expr::Bool { .. } | Expr::String { .. } => {}
+        Expr::Variable { ident } => {
+            if scope.ctor(ident, var_binded) {
+                *expr = Expr::Variant {
+                    name: ident.clone(),
+                    args: vec![],
+                };
+            } else if let Some(op) = scope.builtin(ident, var_binded) {
+                // a function applying the op to its params
+                let params = op.params();
+                let args = params.iter().map(|p| Expr::Variable { ident: p.clone() }).collect();
+                *expr = Expr::Func {
+                    param_typs: vec![None; params.len()],
+                    params,
+                    body: Box::new(Expr::ListOp { op, args }),
+                };
+            }
+        }
+        Expr::FuncApply { func, args } => {
+            args.iter_mut().for_each(|arg| resolve(arg, scope, var_binded));
+            match func.unspanned() {
+                // a variant applied to its fields, e.g. Done(3)
+                Expr::Variable { ident } if scope.ctor(ident, var_binded) => {
+                    *expr = Expr::Variant {
+                        name: ident.clone(),
+                        args: std::mem::take(args),
+                    };
+                }
+                // a builtin applied, e.g. map(xs, f)
+                Expr::Variable { ident } => {
+                    if let Some(op) = scope.builtin(ident, var_binded) {
+                        *expr = Expr::ListOp {
+                            op,
+                            args: std::mem::take(args),
+                        };
+                    }
+                }
+                _ => resolve(func, scope, var_binded),
+            }
+        }
+        Expr::KeyVal { value, .. } => resolve(value, scope, var_binded),
+        Expr::Vector { val } | Expr::List { val } => {
+            val.iter_mut().for_each(|item| resolve(item, scope, var_binded))
+        }
+        Expr::Unop { expr, .. } => resolve(expr, scope, var_binded),
+        Expr::Binop { expr1, expr2, .. } => {
+            resolve(expr1, scope, var_binded);
+            resolve(expr2, scope, var_binded);
+        }
+        Expr::If { cond, expr1, expr2 } => {
+            resolve(cond, scope, var_binded);
+            resolve(expr1, scope, var_binded);
+            resolve(expr2, scope, var_binded);
+        }
+        Expr::Func { params, body, .. } => {
+            let mut new_binds = var_binded.clone();
+            new_binds.extend(params.iter().cloned());
+            resolve(body, scope, &new_binds);
+        }
+        Expr::Let { name, val, body, .. } => {
+            resolve(val, scope, var_binded);
+            let mut new_binds = var_binded.clone();
+            new_binds.insert(name.clone());
+            resolve(body, scope, &new_binds);
+        }
+        Expr::Match { expr, arms } => {
+            resolve(expr, scope, var_binded);
+            for arm in arms {
+                if let Pattern::Var(name) = &arm.pattern {
+                    if scope.ctors.contains(name) {
+                        arm.pattern = Pattern::Variant {
+                            name: name.clone(),
+                            binds: vec![],
+                        };
+                    }
+                }
+                let mut new_binds = var_binded.clone();
+                new_binds.extend(arm.pattern.binds());
+                resolve(&mut arm.body, scope, &new_binds);
+            }
+        }
+        Expr::Action { assns, inserts, edits } => {
+            assns.iter_mut().for_each(|assn| resolve(&mut assn.src, scope, var_binded));
+            inserts.iter_mut().for_each(|insert| resolve(&mut insert.row, scope, var_binded));
+            edits.iter_mut().for_each(|edit| resolve_edit(edit, scope, var_binded));
+        }
+        Expr::ActionBlock { stmts } => {
+            stmts.iter_mut().for_each(|stmt| resolve_stmt(stmt, scope, var_binded))
+        }
+        Expr::Select { where_clause, .. } => resolve(where_clause, scope, var_binded),
+        Expr::Table { records, .. } => {
+            records.iter_mut().for_each(|record| resolve(record, scope, var_binded))
+        }
+        Expr::TableColumn { .. } => {}
+        Expr::RecordField { expr, .. } | Expr::TupleIndex { expr, .. } => {
+            resolve(expr, scope, var_binded)
+        }
+        Expr::Fold { args } | Expr::ListOp { args, .. } | Expr::Variant { args, .. } => {
+            args.iter_mut().for_each(|arg| resolve(arg, scope, var_binded))
+        }
+        Expr::Spanned { expr, .. } => resolve(expr, scope, var_binded),
+    }
+}
+
+fn resolve_stmt(stmt: &mut Stmt, scope: &Scope, var_binded: &HashSet<String>) {
+    match stmt {
+        Stmt::Assn(assn) => resolve(&mut assn.src, scope, var_binded),
+        Stmt::Insert(insert) => resolve(&mut insert.row, scope, var_binded),
+        Stmt::Edit(edit) => resolve_edit(edit, scope, var_binded),
+        Stmt::If { cond, then_stmts, else_stmts } => {
+            resolve(cond, scope, var_binded);
+            for stmt in then_stmts.iter_mut().chain(else_stmts) {
+                resolve_stmt(stmt, scope, var_binded);
+            }
+        }
+        Stmt::Do(action) => resolve(action, scope, var_binded),
+    }
+}
+
+fn resolve_edit(edit: &mut RowEdit, scope: &Scope, var_binded: &HashSet<String>) {
+    match edit {
+        RowEdit::Update { sets, where_clause, .. } => {
+            for set in sets {
+                resolve(&mut set.src, scope, var_binded);
+            }
+            resolve(where_clause, scope, var_binded);
+        }
+        RowEdit::Delete { where_clause, .. } => resolve(where_clause, scope, var_binded),
+    }
+}
@@ -38,6 +38,35 @@ impl kameo::prelude::Message<Msg> for DefActor {
                 Msg::Unit
             }
 
+            Msg::Unsubscribe { from_addr, .. } => {
+                self.pubsub.unsubscribe(&from_addr);
+                Msg::Unit
+            }
+
+            Msg::DefUpdate {
+                expr,
+                arg_to_vals,
+                arg_to_vars,
+                txn,
+            } => {
+                info!("{} updated to {}", self.name, expr);
+                self.state.update_expr(expr, arg_to_vals, arg_to_vars);
+
+                // no change of txn will reach a def depending on none of the
+                // vars it writes, re-evaluate now as part of txn
+                if !self.state.is_affected_by(&txn) {
+                    self.set_value(self.state.eval_expr());
+                    self.pubsub
+                        .publish(Msg::PropChange {
+                            from_name: self.name.clone(),
+                            val: self.value.clone(),
+                            preds: HashSet::from([txn]),
+                        })
+                        .await;
+                }
+                Msg::Unit
+            }
+
             // Msg::LockRequest {
             //     lock,
             //     from_mgr_addr,
@@ -83,7 +112,7 @@ impl kameo::prelude::Message<Msg> for DefActor {
                         .tell(Msg::UsrReadDefResult {
                             txn_id: txn_id.clone(),
                             name: self.name.clone(),
-                            result: self.value.clone().into(),
+                            result: self.result(),
                             preds: self.state.get_all_applied_txns(), // todo!("switch to undropped txns later")
                         })
                         .await;
@@ -163,7 +192,8 @@ impl DefActor {
         let changes = self.state.search_batch();
         info!("{:?} Search batch found: {:?}", self.name, changes);
         if changes.len() > 0 {
-            self.value = self.state.apply_batch(&changes);
+            let value = self.state.apply_batch(&changes);
+            self.set_value(value);
             info!("{:?} Successfully apply batch function, got new value: {}", self.name, self.value);
             let preds = self.state.get_preds_of_changes(&changes);
 
@@ -183,7 +213,7 @@ impl DefActor {
                     .tell(Msg::UsrReadDefResult {
                         txn_id: txn.clone(),
                         name: self.name.clone(),
-                        result: self.value.clone().into(),
+                        result: self.result(),
                         preds: self.state.get_all_applied_txns(), // todo!("switch to undropped txns later")
                     })
                     .await;
@@ -200,7 +230,7 @@ impl DefActor {
                 let _ = from_mgr_addr
                     .tell(Msg::TestReadDefResult {
                         test_id: test_id.clone(),
-                        result: self.value.clone().into(),
+                        result: self.result(),
                     })
                     .await;
                 self.test_read_request = None;
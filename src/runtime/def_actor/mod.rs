@@ -1,5 +1,5 @@
 use futures::future::Either;
-use kameo::actor::ActorRef;
+use log::warn;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -7,7 +7,9 @@ use super::pubsub::PubSub;
 use crate::ast::Expr;
 use crate::runtime::manager::Manager;
 use crate::runtime::transaction::{TxnId, TxnPred};
+use crate::runtime::transport::Addr;
 use crate::runtime::TestId;
+use crate::static_analysis::var_analysis::qualify::source_text;
 use state::ChangeState;
 
 pub mod handler;
@@ -25,15 +27,18 @@ pub mod state;
 pub struct DefActor {
     pub name: String,
     pub value: Expr, // expr of def
+    // error evaluating the def, whose value is then the last one evaluated,
+    // which defs reading it go on with
+    pub error: Option<String>,
     // pub is_assert_actor_of: Option<(TestId, ActorRef<Manager>)>,
 
     pub pubsub: PubSub,
     // pub lock_state: LockState,
 
     // read request is for transactions reading this def
-    pub read_requests: HashMap<TxnId, (ActorRef<Manager>, Vec<TxnId>)>,
+    pub read_requests: HashMap<TxnId, (Addr<Manager>, Vec<TxnId>)>,
     // test read request is for assertion def actor, one shot request
-    pub test_read_request: Option<(TestId, (ActorRef<Manager>, Vec<TxnId>))>,
+    pub test_read_request: Option<(TestId, (Addr<Manager>, Vec<TxnId>))>,
 
     pub state: ChangeState,
 }
@@ -42,14 +47,19 @@ impl DefActor {
     pub fn new(
         name: String,
         expr: Expr,                                    // def's expr
-        value: Expr,                                   // def's initialized value
+        value: Result<Expr, String>,                   // def's initialized value
         arg_to_values: HashMap<String, Expr>,          // def's args to their initialized values
         arg_to_vars: HashMap<String, HashSet<String>>, // args to their transitively dependent vars
         // if arg itself is var, then arg_to_vars[arg] = {arg}
     ) -> DefActor {
+        let (value, error) = match value {
+            Ok(value) => (value, None),
+            Err(e) => (expr.clone(), Some(e)),
+        };
         DefActor {
             name,
             value,
+            error,
             // is_assert_actor_of: testid_and_manager,
             pubsub: PubSub::new(),
             // lock_state: LockState::new(),
@@ -59,3 +69,27 @@ impl DefActor {
         }
     }
 }
+
+impl DefActor {
+    /// value of a new evaluation, on an error the last value is kept
+    pub fn set_value(&mut self, value: Result<Expr, String>) {
+        match value {
+            Ok(value) => {
+                self.value = value;
+                self.error = None;
+            }
+            Err(e) => {
+                warn!("{}", source_text(&format!("{}: {}", self.name, e)));
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// value read by transactions and tests, or the error evaluating it
+    pub fn result(&self) -> Result<Expr, String> {
+        match &self.error {
+            None => Ok(self.value.clone()),
+            Some(e) => Err(e.clone()),
+        }
+    }
+}
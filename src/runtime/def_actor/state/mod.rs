@@ -56,6 +56,48 @@ impl ChangeState {
         }
     }
 
+    /// replace expr of def on code update, keeping values of args that are
+    /// still in use. values of new args and of args depending on no var
+    /// (which no change will ever update) are taken from `arg_to_values`
+    pub fn update_expr(
+        &mut self,
+        expr: Expr,
+        arg_to_values: HashMap<String, Expr>,
+        arg_to_vars: HashMap<String, HashSet<String>>,
+    ) {
+        self.arg_to_values.retain(|arg, _| arg_to_values.contains_key(arg));
+        for (arg, val) in arg_to_values {
+            let is_const = arg_to_vars.get(&arg).map_or(true, |vars| vars.is_empty());
+            if is_const || !self.arg_to_values.contains_key(&arg) {
+                self.arg_to_values.insert(arg, val);
+            }
+        }
+
+        let mut var_to_args = HashMap::new();
+        for (arg, vars) in arg_to_vars {
+            for var in vars {
+                var_to_args
+                    .entry(var)
+                    .or_insert(HashSet::new())
+                    .insert(arg.clone());
+            }
+        }
+
+        self.expr = expr.clone();
+        self.pending_changes.update_expr(expr, var_to_args);
+    }
+
+    /// whether changes propagating txn will reach this def
+    pub fn is_affected_by(&self, txn: &Txn) -> bool {
+        txn.assns
+            .iter()
+            .any(|assn| self.pending_changes.var_to_args.contains_key(&assn.dest))
+    }
+
+    pub fn eval_expr(&self) -> Result<Expr, String> {
+        eval_def_expr(&self.expr, &self.arg_to_values)
+    }
+
     pub fn receive_change(
         &mut self,
         from_name: String,
@@ -81,10 +123,13 @@ impl ChangeState {
         self.pending_changes.search_largest_batch()
     }
 
-    pub fn apply_batch(&mut self, changes: &HashSet<ChangeId>) -> Expr {
+    pub fn apply_batch(&mut self, changes: &HashSet<ChangeId>) -> Result<Expr, String> {
         self.pending_changes.remove_batch_from_pending(changes);
 
-        for change_id in changes.iter() {
+        // in receiving order, so a later change of an arg overrides earlier ones
+        let mut changes = changes.iter().collect::<Vec<_>>();
+        changes.sort();
+        for change_id in changes {
             let change = &self.id_to_change[change_id];
             info!("change being applied: {}", &change.from_name);
 
@@ -47,6 +47,13 @@ impl PendingChanges {
         }
     }
 
+    /// changes already pending keep their requirements computed from
+    /// the old expr
+    pub fn update_expr(&mut self, expr: Expr, var_to_args: HashMap<String, HashSet<String>>) {
+        self.expr = expr;
+        self.var_to_args = var_to_args;
+    }
+
     pub fn add_change(&mut self, change: &PropChange) {
         // change depends on (arg, t)
         //
@@ -54,7 +61,7 @@ impl PendingChanges {
         // then for all arg in var_to_inputs[var] should see t,
         // namely change depends a change on arg, whose preds contains t
         // recorded as (arg, t)
-        for Txn { id: txn_id, assns, inserts} in change.preds.iter() {
+        for txn @ Txn { id: txn_id, assns, .. } in change.preds.iter() {
 
             for Assn { dest, .. } in assns.iter() {
                 if let Some(args) = self.var_to_args.get(dest) {
@@ -68,8 +75,9 @@ impl PendingChanges {
                 }
             }
 
-            for insert in inserts.iter() {
-                if let Some(args) = self.var_to_args.get(&insert.table_name) {
+            // a table written by inserts, updates or deletes
+            for table_name in txn.tables() {
+                if let Some(args) = self.var_to_args.get(&table_name) {
                     for arg in args.iter()
                     {
                         self.change_to_reqs
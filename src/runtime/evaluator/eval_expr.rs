@@ -1,16 +1,42 @@
 use log::info;
 
-use crate::{ast::{Assn, BinOp, Expr, Record, UnOp}, runtime::manager::assert};
+use crate::{ast::{Assn, BinOp, Expr, Insert, ListOp, Pattern, Record, RowEdit, Stmt, UnOp}, runtime::manager::assert};
 use core::panic;
+use ordered_float::OrderedFloat;
 use std::{collections::{HashMap, HashSet}, iter::zip, mem, ops::Deref, vec};
 
-use super::{Evaluator, Val};
+use super::{utils::{canonical, entry_val}, Evaluator, Val};
+
+/// values of a table record, without their column names
+pub(super) fn record_vals(record: &Expr) -> Vec<Expr> {
+    match record {
+        Expr::Vector { val } => val.iter().map(|entry| entry_val(entry).clone()).collect(),
+        record => vec![record.clone()],
+    }
+}
+
+/// numeric value of an int or a float
+fn as_float(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Number { val } => Some(*val as f64),
+        Expr::Float { val } => Some(val.0),
+        _ => None,
+    }
+}
 
 impl Evaluator {
     pub fn calc_unop(op: UnOp, expr: &Expr) -> Result<Expr, String> {
         if let Expr::Number { val } = expr {
             match op {
-                UnOp::Neg => Ok(Expr::Number { val: -val }),
+                UnOp::Neg => val
+                    .checked_neg()
+                    .map(|val| Expr::Number { val })
+                    .ok_or_else(|| format!("integer overflow in -{}", val)),
+                _ => panic!(),
+            }
+        } else if let Expr::Float { val } = expr {
+            match op {
+                UnOp::Neg => Ok(Expr::Float { val: -*val }),
                 _ => panic!(),
             }
         } else if let Expr::Bool { val } = expr {
@@ -28,16 +54,49 @@ impl Evaluator {
     }
 
     pub fn calc_binop(op: BinOp, expr1: &Expr, expr2: &Expr) -> Result<Expr, String> {
+        if op == BinOp::Neq {
+            return match Self::calc_binop(BinOp::Eq, expr1, expr2)? {
+                Expr::Bool { val } => Ok(Expr::Bool { val: !val }),
+                _ => unreachable!("== evaluates to a bool"),
+            };
+        }
+
         if let (Expr::Number { val: val1 }, Expr::Number { val: val2 }) = (expr1, expr2) {
             let (val1, val2) = (*val1, *val2);
+            let checked = |val: Option<i64>| {
+                val.map(|val| Expr::Number { val })
+                    .ok_or_else(|| format!("integer overflow in {} {} {}", val1, op, val2))
+            };
             match op {
-                BinOp::Add => Ok(Expr::Number { val: val1 + val2 }),
-                BinOp::Sub => Ok(Expr::Number { val: val1 - val2 }),
-                BinOp::Mul => Ok(Expr::Number { val: val1 * val2 }),
-                BinOp::Div => Ok(Expr::Number { val: val1 / val2 }),
+                BinOp::Add => checked(val1.checked_add(val2)),
+                BinOp::Sub => checked(val1.checked_sub(val2)),
+                BinOp::Mul => checked(val1.checked_mul(val2)),
+                BinOp::Div if val2 == 0 => Err(format!("division by zero in {} / {}", val1, val2)),
+                BinOp::Div => checked(val1.checked_div(val2)),
+                BinOp::Mod if val2 == 0 => Err(format!("division by zero in {} % {}", val1, val2)),
+                BinOp::Mod => checked(val1.checked_rem(val2)),
                 BinOp::Eq => Ok(Expr::Bool { val: val1 == val2 }),
                 BinOp::Lt => Ok(Expr::Bool { val: val1 < val2 }),
                 BinOp::Gt => Ok(Expr::Bool { val: val1 > val2 }),
+                BinOp::Le => Ok(Expr::Bool { val: val1 <= val2 }),
+                BinOp::Ge => Ok(Expr::Bool { val: val1 >= val2 }),
+                _ => panic!(),
+            }
+        } else if let (Some(val1), Some(val2)) = (as_float(expr1), as_float(expr2)) {
+            // at least one of them is a float, an int operand is promoted
+            match op {
+                BinOp::Add => Ok(Expr::Float { val: OrderedFloat(val1 + val2) }),
+                BinOp::Sub => Ok(Expr::Float { val: OrderedFloat(val1 - val2) }),
+                BinOp::Mul => Ok(Expr::Float { val: OrderedFloat(val1 * val2) }),
+                BinOp::Div if val2 == 0.0 => Err(format!("division by zero in {} / {}", expr1, expr2)),
+                BinOp::Div => Ok(Expr::Float { val: OrderedFloat(val1 / val2) }),
+                BinOp::Mod if val2 == 0.0 => Err(format!("division by zero in {} % {}", expr1, expr2)),
+                BinOp::Mod => Ok(Expr::Float { val: OrderedFloat(val1 % val2) }),
+                BinOp::Eq => Ok(Expr::Bool { val: val1 == val2 }),
+                BinOp::Lt => Ok(Expr::Bool { val: val1 < val2 }),
+                BinOp::Gt => Ok(Expr::Bool { val: val1 > val2 }),
+                BinOp::Le => Ok(Expr::Bool { val: val1 <= val2 }),
+                BinOp::Ge => Ok(Expr::Bool { val: val1 >= val2 }),
                 _ => panic!(),
             }
         } else if let (Expr::Bool { val: val1 }, Expr::Bool { val: val2 }) = (expr1, expr2) {
@@ -45,21 +104,27 @@ impl Evaluator {
             match op {
                 BinOp::And => Ok(Expr::Bool { val: val1 && val2 }),
                 BinOp::Or => Ok(Expr::Bool { val: val1 || val2 }),
+                BinOp::Eq => Ok(Expr::Bool { val: val1 == val2 }),
                 _ => panic!(),
             }
         } else if let (Expr::String { val: val1 }, Expr::String { val: val2 }) = (expr1, expr2) {
-            
+            // strings are ordered lexicographically
             match op {
+                BinOp::Add => Ok(Expr::String { val: format!("{}{}", val1, val2) }),
                 BinOp::Eq => Ok(Expr::Bool { val: val1 == val2 }),
-                _ => panic!(),
+                BinOp::Lt => Ok(Expr::Bool { val: val1 < val2 }),
+                BinOp::Gt => Ok(Expr::Bool { val: val1 > val2 }),
+                BinOp::Le => Ok(Expr::Bool { val: val1 <= val2 }),
+                BinOp::Ge => Ok(Expr::Bool { val: val1 >= val2 }),
+                _ => Err(format!("binary operator {} cannot be applied to strings", op)),
             }
         } else if let (Expr::Table {records: records1,.. }, Expr::Table { records: records2, .. }) = (expr1,expr2) {
             // println!("First table: {:?}", records1);
             // println!("Second table: {:?}", records2);
             match op {
                 BinOp::Eq => {
-                    let set1: HashSet<_> = records1.iter().collect();        // using sets to ignore order for equality of records
-                    let set2: HashSet<_> = records2.iter().collect();
+                    let set1: HashSet<_> = records1.iter().map(record_vals).collect();        // using sets to ignore order for equality of records
+                    let set2: HashSet<_> = records2.iter().map(record_vals).collect();
                     Ok(Expr::Bool { val: set1 == set2 })
                 },
                 _ => panic!(),
@@ -69,12 +134,30 @@ impl Evaluator {
             // println!("Second vector: {:?}", records2);
             match op {
                 BinOp::Eq => {
-                    let set1: HashSet<_> = records1.iter().collect();
-                    let set2: HashSet<_> = records2.iter().collect();
+                    let set1: HashSet<_> = records1.iter().map(record_vals).collect();
+                    let set2: HashSet<_> = records2.iter().map(record_vals).collect();
                     Ok(Expr::Bool { val: set1 == set2 })
                 },
                 _ => panic!()
             }
+        } else if let (Expr::Variant { .. }, Expr::Variant { .. }) = (expr1, expr2) {
+            // same variant with the same fields
+            match op {
+                BinOp::Eq => Ok(Expr::Bool { val: canonical(expr1) == canonical(expr2) }),
+                _ => Err(format!("binary operator {} cannot be applied to {} and {}", op, expr1, expr2)),
+            }
+        } else if let (Expr::List { .. }, Expr::List { .. }) = (expr1, expr2) {
+            // same elements in the same order
+            match op {
+                BinOp::Eq => Ok(Expr::Bool { val: canonical(expr1) == canonical(expr2) }),
+                _ => Err(format!("binary operator {} cannot be applied to {} and {}", op, expr1, expr2)),
+            }
+        } else if let (Expr::Vector { .. }, Expr::Vector { .. }) = (expr1, expr2) {
+            // tuples, or records with the same fields in any order
+            match op {
+                BinOp::Eq => Ok(Expr::Bool { val: canonical(expr1) == canonical(expr2) }),
+                _ => Err(format!("binary operator {} cannot be applied to {} and {}", op, expr1, expr2)),
+            }
         } else {
             Err(format!(
                 "calculate binop expression cannot be applied 
@@ -84,6 +167,203 @@ impl Evaluator {
         }
     }
 
+    /// value of field `name` of an evaluated record
+    pub fn calc_field(record: &Expr, name: &String) -> Result<Expr, String> {
+        let Expr::Vector { val } = record else {
+            return Err(format!("{} is not a record", record));
+        };
+        val.iter()
+            .find_map(|entry| match entry {
+                Expr::KeyVal { key, value } if key == name => Some(*value.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| format!("record {} has no field {}", record, name))
+    }
+
+    /// the Expr::Action doing stmts in one transaction,
+    /// stmts are done in order, each seeing the writes of those before it
+    ///
+    /// each name written is assigned once, to its value in terms of the
+    /// values from before the action, by substituting earlier writes in
+    /// later ones, e.g. `x = x + 1; y = x;` gives `x = x + 1; y = x + 1;`
+    /// - a nested action is done along, its args are read at the time of the
+    ///   transaction as any other name, e.g. `x = x + 1; do f(x);` does
+    ///   `f` with `x + 1`
+    /// - a write in a branch of an if is made conditional, e.g.
+    ///   `if b then { x = 1; }` gives `x = if b then 1 else x`, and an
+    ///   insert or edit is done if its cond `b` holds
+    pub fn flatten_stmts(&mut self, stmts: &[Stmt]) -> Result<Expr, String> {
+        let mut assns = vec![];
+        let mut inserts = vec![];
+        let mut edits = vec![];
+        self.sequence_stmts(stmts, &mut assns, &mut inserts, &mut edits)?;
+        Ok(Expr::Action { assns, inserts, edits })
+    }
+
+    /// do stmts after `assns`, the writes so far
+    fn sequence_stmts(
+        &mut self,
+        stmts: &[Stmt],
+        assns: &mut Vec<Assn>,
+        inserts: &mut Vec<Insert>,
+        edits: &mut Vec<RowEdit>,
+    ) -> Result<(), String> {
+        for stmt in stmts {
+            let writes = assns
+                .iter()
+                .map(|assn| (assn.dest.clone(), assn.src.clone()))
+                .collect::<HashMap<_, _>>();
+            match stmt {
+                Stmt::Assn(Assn { dest, src }) => {
+                    let mut src = src.clone();
+                    self.subst(&mut src, &writes);
+                    write(assns, Assn { dest: dest.clone(), src });
+                }
+                Stmt::Insert(insert) => {
+                    let mut insert = insert.clone();
+                    self.subst_insert(&mut insert, &writes);
+                    inserts.push(insert);
+                }
+                Stmt::Edit(edit) => {
+                    let mut edit = edit.clone();
+                    self.subst_edit_reads(&mut edit, &writes);
+                    edits.push(edit);
+                }
+                Stmt::Do(action) => {
+                    let action = self.nested_action(action.clone())?;
+                    let Expr::Action { assns: nested_assns, inserts: nested_inserts, edits: nested_edits } = action else {
+                        return Err(format!("do requires action expression, got {}", action));
+                    };
+                    // the nested action reads the values before it
+                    for mut assn in nested_assns {
+                        self.subst(&mut assn.src, &writes);
+                        write(assns, assn);
+                    }
+                    for mut insert in nested_inserts {
+                        self.subst_insert(&mut insert, &writes);
+                        inserts.push(insert);
+                    }
+                    for mut edit in nested_edits {
+                        self.subst_edit_reads(&mut edit, &writes);
+                        edits.push(edit);
+                    }
+                }
+                Stmt::If { cond, then_stmts, else_stmts } => {
+                    let mut cond = cond.clone();
+                    self.subst(&mut cond, &writes);
+
+                    let (mut then_assns, mut else_assns) = (assns.clone(), assns.clone());
+                    let (mut then_inserts, mut then_edits) = (vec![], vec![]);
+                    let (mut else_inserts, mut else_edits) = (vec![], vec![]);
+                    self.sequence_stmts(then_stmts, &mut then_assns, &mut then_inserts, &mut then_edits)?;
+                    self.sequence_stmts(else_stmts, &mut else_assns, &mut else_inserts, &mut else_edits)?;
+
+                    let not_cond = Expr::Unop { op: UnOp::Not, expr: Box::new(cond.clone()) };
+                    for (branch_cond, branch_inserts, branch_edits) in
+                        [(&cond, then_inserts, then_edits), (&not_cond, else_inserts, else_edits)]
+                    {
+                        for mut insert in branch_inserts {
+                            insert.cond = Some(and(branch_cond, insert.cond));
+                            inserts.push(insert);
+                        }
+                        for mut edit in branch_edits {
+                            let cond = edit.cond_mut();
+                            *cond = Some(and(branch_cond, cond.take()));
+                            edits.push(edit);
+                        }
+                    }
+
+                    let mut dests = vec![];
+                    for assn in then_assns.iter().chain(&else_assns) {
+                        if !dests.contains(&assn.dest) && !assns.contains(assn) {
+                            dests.push(assn.dest.clone());
+                        }
+                    }
+                    for dest in dests {
+                        // a name not written in a branch keeps its value so far
+                        let src_of = |branch: &Vec<Assn>| {
+                            branch
+                                .iter()
+                                .find(|assn| assn.dest == dest)
+                                .map_or(Expr::Variable { ident: dest.clone() }, |assn| assn.src.clone())
+                        };
+                        let src = Expr::If {
+                            cond: Box::new(cond.clone()),
+                            expr1: Box::new(src_of(&then_assns)),
+                            expr2: Box::new(src_of(&else_assns)),
+                        };
+                        write(assns, Assn { dest, src });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the Expr::Action of a nested action, its function is applied to the
+    /// args unevaluated, so the reads in them are left to the transaction
+    fn nested_action(&mut self, action: Expr) -> Result<Expr, String> {
+        match action {
+            Expr::FuncApply { func, args } => {
+                let Expr::Func { params, mut body, .. } = self.nested_action(*func)? else {
+                    return Err("cannot apply non-function".to_string());
+                };
+                if params.len() != args.len() {
+                    return Err(format!("function expects {} arguments, got {}", params.len(), args.len()));
+                }
+                let var_to_expr = zip(params, args).collect::<HashMap<String, Expr>>();
+                self.subst(&mut body, &var_to_expr);
+                self.nested_action(*body)
+            }
+            Expr::ActionBlock { stmts } => self.flatten_stmts(&stmts),
+            mut action => {
+                self.eval_expr(&mut action)?;
+                Ok(action)
+            }
+        }
+    }
+
+    /// value of a builtin on lists, applied to evaluated args
+    pub fn calc_list_op(&mut self, op: ListOp, args: Vec<Expr>) -> Result<Expr, String> {
+        let mut args = args.into_iter();
+        let mut val = match args.next() {
+            Some(Expr::List { val }) => val,
+            Some(arg) => return Err(format!("{} cannot be applied to {}, it is not a list", op, arg)),
+            None => return Err(format!("{} expects a list", op)),
+        };
+        let args = args.collect::<Vec<_>>();
+        match (op, args.as_slice()) {
+            (ListOp::Map, [func]) => {
+                let val = val
+                    .into_iter()
+                    .map(|el| self.apply(func, vec![el]))
+                    .collect::<Result<_, _>>()?;
+                Ok(Expr::List { val })
+            }
+            (ListOp::Filter, [pred]) => {
+                let mut kept = vec![];
+                for el in val {
+                    match self.apply(pred, vec![el.clone()])? {
+                        Expr::Bool { val: true } => kept.push(el),
+                        Expr::Bool { val: false } => {}
+                        other => return Err(format!("filter predicate gives {}, not a bool", other)),
+                    }
+                }
+                Ok(Expr::List { val: kept })
+            }
+            (ListOp::Reduce, [func, init]) => val
+                .into_iter()
+                .try_fold(init.clone(), |acc, el| self.apply(func, vec![acc, el])),
+            (ListOp::Length, []) => Ok(Expr::Number { val: val.len() as i64 }),
+            (ListOp::Head, []) => val.into_iter().next().ok_or_else(|| "head of an empty list".to_string()),
+            (ListOp::Append, [el]) => {
+                val.push(el.clone());
+                Ok(Expr::List { val })
+            }
+            _ => Err(format!("{} cannot be applied to {} arguments", op, args.len() + 1)),
+        }
+    }
+
     /// inplace evaluator of Expr
     /// todo: change implementation to context stack,
     /// - better performance
@@ -91,9 +371,10 @@ impl Evaluator {
     pub fn eval_expr(&mut self, expr: &mut Expr) -> Result<(), String> {
         match expr {
             Expr::Number { val } => Ok(()),
+            Expr::Float { val } => Ok(()),
             Expr::Bool { val } => Ok(()),
             Expr::String {val} => Ok(()),
-            Expr::Vector { val } => {
+            Expr::Vector { val } | Expr::List { val } => {
                 for expr in val {
                     self.eval_expr(expr)?;
                 }
@@ -108,17 +389,14 @@ impl Evaluator {
 
                 val.map(|val| *expr = val)
             }
-            Expr::KeyVal { key, value } => {
-                self.eval_expr(value)?;
-                *expr = *value.clone();
-                Ok(())
-            }
+            // entry of a record keeps its field name
+            Expr::KeyVal { key, value } => self.eval_expr(value),
 
             Expr::Unop { op, expr: expr1 } => {
                 self.eval_expr(expr1)?;
                 match expr1.as_mut() {
                     // note: as_mut() has same effect as &mut **expr1 here
-                    Expr::Number { .. } | Expr::Bool { .. } => {
+                    Expr::Number { .. } | Expr::Float { .. } | Expr::Bool { .. } => {
                         *expr = Self::calc_unop(*op, expr1)?;
                         Ok(())
                     }
@@ -130,14 +408,34 @@ impl Evaluator {
                 }
             }
 
-            Expr::Binop { op, expr1, expr2 } => {
+            // short-circuit: the right hand side is evaluated only when
+            // the left one does not decide, e.g. `x != 0 && 10 / x > 1`
+            Expr::Binop { op: op @ (BinOp::And | BinOp::Or), expr1, expr2 } => {
                 self.eval_expr(expr1)?;
-                assert!(!matches!(&**expr1, Expr::KeyVal { .. }));
+                let Expr::Bool { val } = **expr1 else {
+                    return Err(format!("binary operator {:?} cannot be applied to {}", op, **expr1));
+                };
+                if val == (*op == BinOp::Or) {
+                    *expr = Expr::Bool { val };
+                    return Ok(());
+                }
 
+                self.eval_expr(expr2)?;
+                match **expr2 {
+                    Expr::Bool { .. } => {
+                        *expr = mem::take(expr2);
+                        Ok(())
+                    }
+                    _ => Err(format!("binary operator {:?} cannot be applied to {}", op, **expr2)),
+                }
+            }
+
+            Expr::Binop { op, expr1, expr2 } => {
+                self.eval_expr(expr1)?;
                 self.eval_expr(expr2)?;
                 use Expr::*;
                 match (expr1.as_mut(), expr2.as_mut()) {
-                    (Number { .. }, Number { .. }) | (Bool { .. }, Bool { .. }) | (String { .. }, String { .. }) | (Table {..},Table{..}) | (Table{..}, Vector { .. }) => {
+                    (Number { .. } | Float { .. }, Number { .. } | Float { .. }) | (Bool { .. }, Bool { .. }) | (String { .. }, String { .. }) | (Table {..},Table{..}) | (Table{..}, Vector { .. }) | (Vector { .. }, Vector { .. }) | (List { .. }, List { .. }) | (Variant { .. }, Variant { .. }) => {
                         *expr = Self::calc_binop(*op, expr1, expr2)?;
                         Ok(())
                     }
@@ -165,9 +463,17 @@ impl Evaluator {
                 }
             }
 
-            Expr::Func { params, body } => {
-                // functions are values
-                self.subst(expr, &self.reactive_name_to_vals.clone());
+            Expr::Func { params, body, .. } => {
+                // functions are values, names read by an action in the body
+                // are left to be read when the action is done
+                let free_vars = expr.free_var(&self.reactive_names, &HashSet::new());
+                let vals = self
+                    .reactive_name_to_vals
+                    .iter()
+                    .filter(|(name, _)| free_vars.contains(*name))
+                    .map(|(name, val)| (name.clone(), val.clone()))
+                    .collect();
+                self.subst(expr, &vals);
                 Ok(())
             }
 
@@ -175,7 +481,7 @@ impl Evaluator {
                 self.eval_expr(func)?;
 
                 match func.as_mut() {
-                    Expr::Func { params, body } => {
+                    Expr::Func { params, body, .. } => {
                         if params.len() != args.len() {
                             Err(format!(
                                 "function expects {} arguments, got {}",
@@ -206,30 +512,77 @@ impl Evaluator {
                 }
             }
 
-            Expr::Action { assns, inserts } => {
+            Expr::Let { name, val, body, .. } => {
+                // evaluated as the application of a function of name to val
+                self.eval_expr(val)?;
+                let var_to_expr = HashMap::from([(name.clone(), *val.clone())]);
+                self.subst(body, &var_to_expr);
+
+                *expr = std::mem::take(body);
+                self.eval_expr(expr)
+            }
+
+            Expr::Variant { args, .. } => {
+                for arg in args {
+                    self.eval_expr(arg)?;
+                }
+                Ok(())
+            }
+
+            Expr::Match { expr: matched, arms } => {
+                // the first arm whose pattern matches, its binds are
+                // substituted in its body as the params of a function
+                self.eval_expr(matched)?;
+                let Some(arm) = arms.iter_mut().find(|arm| match (&arm.pattern, matched.as_ref()) {
+                    (Pattern::Variant { name, .. }, Expr::Variant { name: variant, .. }) => name == variant,
+                    (Pattern::Variant { .. }, _) => false,
+                    (Pattern::Var(_), _) => true,
+                }) else {
+                    return Err(format!("no arm of match matches {}", matched));
+                };
+                let vals = match matched.as_mut() {
+                    Expr::Variant { args, .. } if matches!(arm.pattern, Pattern::Variant { .. }) => mem::take(args),
+                    matched => vec![mem::take(matched)],
+                };
+                let binds = arm.pattern.binds();
+                if binds.len() != vals.len() {
+                    return Err(format!("pattern {} expects {} fields, got {}", arm.pattern, binds.len(), vals.len()));
+                }
+
+                let var_to_expr = zip(binds, vals).collect::<HashMap<String, Expr>>();
+                self.subst(&mut arm.body, &var_to_expr);
+                *expr = mem::take(&mut arm.body);
+                self.eval_expr(expr)
+            }
+
+            Expr::Action { assns, .. } => {
                 // for assn in assns.iter_mut() {
                 //     self.eval_assn(assn)?;
                 // }
                 Ok(())
             }
+            Expr::ActionBlock { stmts } => {
+                *expr = self.flatten_stmts(stmts)?;
+                Ok(())
+            }
             Expr::Select {
                 table_name,
                 column_names,
                 where_clause,
             } => {
                 
-                let table = self.reactive_name_to_vals.get(table_name).unwrap().clone();
+                let table = self.search_table(table_name)?;
                 let (schema, records) = match table {
                     Expr::Table {schema, records, ..} => (schema, records),
-                    _ => panic!("Table is not a table")
+                    _ => return Err(format!("{} is not a table", table_name)),
                 };
 
                 let original_context = self.reactive_name_to_vals.clone();
 
                 // Build new schema for selected columns
-                let selected_schema = if column_names.len() != 0 {   
-                    schema.iter()
-                        .filter(|field| column_names.contains(&field.name))
+                let selected_schema = if column_names.len() != 0 {   // in the order columns are selected, as their values
+                    column_names.iter()
+                        .filter_map(|col| schema.iter().find(|field| &field.name == col))
                         .cloned()
                         .collect()
                 } else {              // if no column names mentioned, select all columns
@@ -239,15 +592,14 @@ impl Evaluator {
                 let mut selected_records = Vec::new();
 
                 for record in records {
+                    // values of inserted rows keep their column name, those of
+                    // selected records do not, both are bound by position
                     let record_vals = match record {
-                        Expr::Vector { val } => val.clone(),
-                        _ => panic!("Record is not a vector")
+                        Expr::Vector { .. } => record_vals(&record),
+                        _ => return Err(format!("record {} of {} is not a vector", record, table_name)),
                     };
-
-                    for key_val in record_vals.iter() {
-                        if let Expr::KeyVal { key, value } = key_val {
-                            self.reactive_name_to_vals.insert(key.clone(), *value.clone());
-                        }
+                    for (field, val) in schema.iter().zip(record_vals.iter()) {
+                        self.reactive_name_to_vals.insert(field.name.clone(), val.clone());
                     }
                     let mut evaluated_where = where_clause.deref().clone();
                     // evaluate where condition with the value from current record
@@ -295,6 +647,30 @@ impl Evaluator {
             }
             Expr::Table { .. } => Ok(()),
 
+            // field of a record named table_name
+            Expr::TableColumn { table_name, column_name }
+                if matches!(self.reactive_name_to_vals.get(table_name), Some(Expr::Vector { .. })) =>
+            {
+                let record = self.reactive_name_to_vals[table_name].clone();
+                *expr = Self::calc_field(&record, column_name)?;
+                Ok(())
+            }
+            Expr::RecordField { expr: record, name } => {
+                self.eval_expr(record)?;
+                *expr = Self::calc_field(record, name)?;
+                Ok(())
+            }
+            Expr::TupleIndex { expr: tuple, idx } => {
+                self.eval_expr(tuple)?;
+                match tuple.as_ref() {
+                    Expr::Vector { val } if *idx < val.len() => {
+                        *expr = entry_val(&val[*idx]).clone();
+                        Ok(())
+                    }
+                    _ => Err(format!("tuple {} has no entry {}", tuple, idx)),
+                }
+            }
+
             Expr::TableColumn { table_name, column_name } => {
                 info!("Eval tablecolumn eval env: {:#?}", self.reactive_name_to_vals);
                 if let Some(val) = self.reactive_name_to_vals.get(column_name) {   // get the value from the column name in the context which was added in the select eval
@@ -313,13 +689,13 @@ impl Evaluator {
                         for record in records {
                             let val = match record {
                                 Expr::Vector { val } => {
-                                    val[column_id].clone()
+                                    entry_val(&val[column_id]).clone()
                                 }
-                                _ => panic!("Not a vector")
+                                _ => return Err(format!("record {} of {} is not a vector", record, table_name)),
                             };
                             column_vals.push(val);
                         }
-                        *expr = self.fold(&column_vals, args[2].clone(), &args[1]);
+                        *expr = self.fold(&column_vals, args[2].clone(), &args[1])?;
                         
                         Ok(())
                     } else {
@@ -329,7 +705,34 @@ impl Evaluator {
                     Err(format!("First arg should be a iterator (column for now)"))
                 }
             }
+            Expr::ListOp { op, args } => {
+                for arg in args.iter_mut() {
+                    self.eval_expr(arg)?;
+                }
+                *expr = self.calc_list_op(*op, mem::take(args))?;
+                Ok(())
+            }
+            // spans are erased once typechecked, see `Prog::erase_spans`
+            Expr::Spanned { expr: spanned, .. } => {
+                *expr = mem::take(spanned.as_mut());
+                self.eval_expr(expr)
+            }
         }
     }
 }
 
+/// `cond && expr`, or cond if there is no expr
+fn and(cond: &Expr, expr: Option<Expr>) -> Expr {
+    match expr {
+        Some(expr) => Expr::Binop { op: BinOp::And, expr1: Box::new(cond.clone()), expr2: Box::new(expr) },
+        None => cond.clone(),
+    }
+}
+
+/// write of an action, replacing an earlier write to the same name
+fn write(assns: &mut Vec<Assn>, assn: Assn) {
+    match assns.iter_mut().find(|a| a.dest == assn.dest) {
+        Some(prev) => *prev = assn,
+        None => assns.push(assn),
+    }
+}
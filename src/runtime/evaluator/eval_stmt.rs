@@ -1,37 +1,54 @@
-use std::{collections::HashSet, hash::Hash};
+use std::{collections::{HashMap, HashSet}, hash::Hash};
 
-use crate::ast::{Assn, Decl, Entry, Expr, Insert, Record};
+use crate::ast::{Assn, Decl, Entry, Expr, Field, Insert, Record, RowEdit};
 
 use log::info;
 
-use super::{Evaluator, Val};
+use super::{eval_expr::record_vals, Evaluator, Val};
 
 impl Evaluator {
     pub fn eval_decl(&mut self, decl: &mut Decl) -> Result<(), String> {
         match decl {
-            Decl::Import { srv_name } => todo!(),
-            Decl::VarDecl { name, val } => {
+            Decl::Import { srv_name, .. } => {
+                let exports = self
+                    .srv_to_exports
+                    .get(srv_name)
+                    .cloned()
+                    .ok_or_else(|| format!("imported service {} not found", srv_name))?;
+
+                // public names of imported service are reactive names in scope
+                for (name, val) in exports {
+                    self.reactive_names.insert(name.clone());
+                    self.reactive_name_to_vals.insert(name, val);
+                }
+            }
+            Decl::VarDecl { name, val, .. } => {
                 self.reactive_names.insert(name.clone());
 
                 // var should have no depend
                 assert!(val.free_var(&HashSet::new(), &HashSet::new()).is_empty());
 
-                self.eval_expr(val)?;
+                let evaled = self.eval_expr(val);
                 self.reactive_name_to_vals.insert(name.clone(), val.clone());
+                evaled?;
             }
-            Decl::DefDecl { name, val, is_pub } => {
+            Decl::DefDecl { name, val, is_pub, .. } => {
                 self.reactive_names.insert(name.clone());
 
                 // unevaled expr of def should be stored
                 self.def_name_to_exprs.insert(name.clone(), val.clone());
 
-                // then eval def
-                self.eval_expr(val)?;
+                // then eval def, on a runtime error it keeps the partially
+                // evaluated expr, which fails whatever reads it
+                let evaled = self.eval_expr(val);
                 self.reactive_name_to_vals.insert(name.clone(), val.clone());
+                evaled?;
             }
-            Decl::TableDecl { name, fields } => {
+            Decl::TableDecl { name, fields, .. } => {
                 self.reactive_name_to_vals.insert(name.clone(), Expr::Table {schema: fields.clone(), records:Vec::new() });
             }
+            // variants are values of their own, only checked statically
+            Decl::TypeDecl { .. } => {}
         }
 
         Ok(())
@@ -45,13 +62,27 @@ impl Evaluator {
         // self.env.insert(assn.dest.clone(), assn.src.clone());
         Ok(())
     }
+    /// order entries of an inserted row as the columns of its table,
+    /// since values of a record are read by the position of their column
+    pub fn order_row(&self, insert: &mut Insert) -> Result<(), String> {
+        let Some(Expr::Table { schema, .. }) = self.reactive_name_to_vals.get(&insert.table_name) else {
+            return Err(format!("Table {} not declared", insert.table_name));
+        };
+        if let Expr::Vector { val } = &mut insert.row {
+            val.sort_by_key(|entry| match entry {
+                Expr::KeyVal { key, .. } => schema.iter().position(|field| &field.name == key),
+                _ => None,
+            });
+        }
+        Ok(())
+    }
+
     pub fn eval_insert(&mut self, insert: &mut Insert) -> Result<(), String> {
         self.eval_expr(&mut insert.row)?;
         if let Expr::Vector { val } = &insert.row {
-            let found_table = self
-                .reactive_name_to_vals
-                .get_mut(&insert.table_name)
-                .expect("Table not declared");
+            let Some(found_table) = self.reactive_name_to_vals.get_mut(&insert.table_name) else {
+                return Err(format!("Table {} not declared", insert.table_name));
+            };
             if let Expr::Table { records: target_records, .. } = found_table {
                 let mut curr_record = Vec::new();
                 for keyval in val {
@@ -62,13 +93,113 @@ impl Evaluator {
                 
                 target_records.push(Expr::Vector { val: curr_record });
             } else {
-                panic!("Not a table");
+                return Err(format!("{} is not a table", insert.table_name));
             }
             
         }
         Ok(())
     }
 
+    /// apply edit to the manager's copy of its table
+    pub fn eval_edit(&mut self, edit: &RowEdit) -> Result<(), String> {
+        let Some(mut table) = self.reactive_name_to_vals.get(edit.table_name()).cloned() else {
+            return Err(format!("Table {} not declared", edit.table_name()));
+        };
+        self.edit_rows(&mut table, edit)?;
+        self.reactive_name_to_vals.insert(edit.table_name().clone(), table);
+        Ok(())
+    }
+
+    /// whether the condition of an insert or edit holds, if it has one
+    pub fn eval_cond(&mut self, cond: Option<Expr>) -> Result<bool, String> {
+        let Some(mut cond) = cond else {
+            return Ok(true);
+        };
+        self.eval_expr(&mut cond)?;
+        match cond {
+            Expr::Bool { val } => Ok(val),
+            cond => Err(format!("condition {} is not a bool", cond)),
+        }
+    }
+
+    /// subst values of reactive names an edit reads, e.g. writes so far of
+    /// its action or values read by its txn, but not of its columns, which
+    /// are bound by each row, except in its cond
+    pub fn subst_edit_reads(&mut self, edit: &mut RowEdit, vals: &HashMap<String, Expr>) {
+        let mut cond = edit.cond_mut().take();
+        if let Some(cond) = &mut cond {
+            self.subst(cond, vals);
+        }
+
+        let mut vals = vals.clone();
+        if let Some(Expr::Table { schema, .. }) = self.reactive_name_to_vals.get(edit.table_name()) {
+            for field in schema.iter() {
+                vals.remove(&field.name);
+            }
+        }
+        self.subst_edit(edit, &vals);
+        *edit.cond_mut() = cond;
+    }
+
+    /// apply edit to the rows of table, its where clause and new values are
+    /// evaluated with the columns of each row in scope, as in select;
+    /// on an error the table is left unchanged
+    pub fn edit_rows(&mut self, table: &mut Expr, edit: &RowEdit) -> Result<(), String> {
+        let Expr::Table { schema, records } = table else {
+            return Err(format!("{} is not a table", edit.table_name()));
+        };
+
+        let original_context = self.reactive_name_to_vals.clone();
+        let mut edited = Vec::new();
+        for record in records.iter() {
+            for (field, val) in schema.iter().zip(record_vals(record)) {
+                self.reactive_name_to_vals.insert(field.name.clone(), val);
+            }
+            let row = self.edit_row(schema, record, edit);
+            self.reactive_name_to_vals = original_context.clone();
+            edited.extend(row?);
+        }
+
+        *records = edited;
+        Ok(())
+    }
+
+    /// the row record is edited to, none if it is deleted
+    fn edit_row(&mut self, schema: &[Field], record: &Expr, edit: &RowEdit) -> Result<Option<Expr>, String> {
+        let where_clause = match edit {
+            RowEdit::Update { where_clause, .. } | RowEdit::Delete { where_clause, .. } => where_clause,
+        };
+        let mut cond = where_clause.clone();
+        self.eval_expr(&mut cond)?;
+        let Expr::Bool { val: matched } = cond else {
+            return Err(format!("where clause of {} gives {}, not a bool", edit, cond));
+        };
+
+        match edit {
+            _ if !matched => Ok(Some(record.clone())),
+            RowEdit::Delete { .. } => Ok(None),
+            RowEdit::Update { sets, .. } => {
+                let mut record = record.clone();
+                let Expr::Vector { val: entries } = &mut record else {
+                    return Err(format!("record {} of {} is not a vector", record, edit.table_name()));
+                };
+                for Assn { dest, src } in sets {
+                    let Some(i) = schema.iter().position(|field| &field.name == dest) else {
+                        return Err(format!("column {} not found in table {}", dest, edit.table_name()));
+                    };
+                    let mut val = src.clone();
+                    self.eval_expr(&mut val)?;
+                    // an inserted row keeps the names of its columns
+                    match &mut entries[i] {
+                        Expr::KeyVal { value, .. } => **value = val,
+                        entry => *entry = val,
+                    }
+                }
+                Ok(Some(record))
+            }
+        }
+    }
+
     pub fn eval_assert(&mut self, expr: &mut Expr) -> Result<(), String> {
         self.eval_expr(expr)?;
 
@@ -1,5 +1,6 @@
 use crate::ast::{
-    Assn, DataType, Decl, Entry, Expr, Field, Insert, Prog, Record, ReplCmd, Service, Test,
+    Assn, DataType, Decl, Entry, Expr, Field, Insert, Prog, Record, ReplCmd, RowEdit, Service,
+    Test,
 };
 use std::{
     collections::{HashMap, HashSet},
@@ -7,7 +8,10 @@ use std::{
     fmt::Display,
 };
 
+use log::warn;
+
 use super::{manager::Manager, message::Msg};
+use crate::static_analysis::var_analysis::qualify::source_text;
 
 mod eval_expr;
 mod eval_stmt;
@@ -15,9 +19,9 @@ mod utils;
 
 #[derive(Debug, Clone)]
 pub enum Val {
-    Number(i32),
+    Number(i64),
     Bool(bool),
-    Action(Vec<Assn>, Vec<Insert>),
+    Action(Vec<Assn>, Vec<Insert>, Vec<RowEdit>),
     Func(Vec<String>, Box<Expr>),
 }
 
@@ -34,6 +38,11 @@ pub struct Evaluator {
     /// exprvar_name_to_val: HashMap<String, Expr>,
     pub def_name_to_exprs: HashMap<String, Expr>,
 
+    /// imported service name -> its public names' values
+    pub srv_to_exports: HashMap<String, HashMap<String, Expr>>,
+
+    /// var/def name -> error of its initial eval, see `eval_srv`
+    pub decl_errors: HashMap<String, String>,
 }
 
 impl Evaluator {
@@ -43,39 +52,105 @@ impl Evaluator {
             reactive_names: HashSet::new(),
             reactive_name_to_vals,
             def_name_to_exprs: HashMap::new(),
+            srv_to_exports: HashMap::new(),
+            decl_errors: HashMap::new(),
         }
     }
+
+    /// error `e` of evaluating `expr`, or the error of a decl it reads
+    /// that failed, rather than an error on the partial value of that decl
+    pub fn eval_error(&self, expr: &Expr, e: String) -> String {
+        expr.free_var(&self.reactive_names, &HashSet::new())
+            .iter()
+            .find_map(|name| self.decl_errors.get(name).cloned())
+            .unwrap_or(e)
+    }
 }
 
-/// used for def actor eval their expression
+/// used for def actor eval their expression, a runtime error, e.g.
+/// division by zero, is the error of the def
 pub fn eval_def_expr(
     def_expr: &Expr,
     env: &HashMap<String, Expr>,
-) -> Expr {
+) -> Result<Expr, String> {
     let mut eval = Evaluator::new(env.clone());
     let mut evaled_expr = def_expr.clone();
-    eval.eval_expr(&mut evaled_expr);
-    evaled_expr
+    eval.eval_expr(&mut evaled_expr)
+        .map_err(|e| format!("evaluating {} failed: {}", def_expr, e))?;
+    Ok(evaled_expr)
+}
+
+/// used for table actor apply an update or delete to its rows
+pub fn edit_table(table: &mut Expr, edit: &RowEdit) -> Result<(), String> {
+    Evaluator::new(HashMap::new()).edit_rows(table, edit)
 }
 
-/// used for manager eval assns when action is triggered
-pub fn eval_assns(assns: &Vec<Assn>, env: HashMap<String, Expr>) -> Vec<Assn> {
+/// used for manager eval assns when action is triggered, each name is
+/// assigned once, to an expr of the values read (see `flatten_stmts`)
+/// a runtime error, e.g. division by zero, fails the whole transaction
+pub fn eval_assns(assns: &Vec<Assn>, env: HashMap<String, Expr>) -> Result<Vec<Assn>, String> {
     let mut eval = Evaluator::new(env);
     let mut evaled_assns = assns.clone();
     for assn in evaled_assns.iter_mut() {
-        eval.eval_assn(assn);
+        eval.eval_assn(assn)
+            .map_err(|e| format!("evaluating {} failed: {}", assn, e))?;
     }
 
-    evaled_assns
+    Ok(evaled_assns)
 }
 
-/// used for initial eval of all declarations in a service
-pub fn eval_srv(srv: &Service) -> Evaluator {
+/// used for manager eval rows of inserts as eval_assns, with the values
+/// read by the transaction, an insert whose condition is false is dropped
+pub fn eval_inserts(inserts: &[Insert], env: HashMap<String, Expr>) -> Result<Vec<Insert>, String> {
+    let mut eval = Evaluator::new(env);
+    let mut evaled_inserts = vec![];
+    for insert in inserts {
+        let mut insert = insert.clone();
+        let fail = |e| format!("evaluating insert into {} failed: {}", insert.table_name, e);
+        if !eval.eval_cond(insert.cond.take()).map_err(fail)? {
+            continue;
+        }
+        eval.eval_expr(&mut insert.row).map_err(fail)?;
+        evaled_inserts.push(insert);
+    }
+
+    Ok(evaled_inserts)
+}
+
+/// used for manager drop edits whose condition is false, as eval_inserts,
+/// the others are evaluated by their table
+pub fn eval_edit_conds(edits: &[RowEdit], env: HashMap<String, Expr>) -> Result<Vec<RowEdit>, String> {
+    let mut eval = Evaluator::new(env);
+    let mut evaled_edits = vec![];
+    for edit in edits {
+        let mut edit = edit.clone();
+        let cond = edit.cond_mut().take();
+        if eval.eval_cond(cond).map_err(|e| format!("evaluating {} failed: {}", edit, e))? {
+            evaled_edits.push(edit);
+        }
+    }
+
+    Ok(evaled_edits)
+}
+
+/// used for initial eval of all declarations in a service,
+/// with values of public names of the services it imports
+pub fn eval_srv(srv: &Service, srv_to_exports: HashMap<String, HashMap<String, Expr>>) -> Evaluator {
     let mut srv = srv.clone();
     let mut eval = Evaluator::new(HashMap::new());
+    eval.srv_to_exports = srv_to_exports;
     for decl in srv.decls.iter_mut() {
-        let _ = eval.eval_decl(decl);
-        // println!("{}", decl);
+        let unevaled = decl.clone();
+        // a decl failing to evaluate is still bound, to its partial value
+        if let Err(e) = eval.eval_decl(decl) {
+            if let Decl::VarDecl { name, val, .. } | Decl::DefDecl { name, val, .. } = unevaled {
+                let e = eval.eval_error(&val, format!("evaluating {} failed: {}", val, e));
+                warn!("{}", source_text(&format!("{}: {}", name, e)));
+                eval.decl_errors.insert(name, e);
+            } else {
+                warn!("{}", source_text(&format!("evaluating {} failed: {}", unevaled, e)));
+            }
+        }
     }
     eval
 }
@@ -85,8 +160,12 @@ impl From<Val> for Expr {
         match val {
             Val::Number(i) => Expr::Number { val: i },
             Val::Bool(b) => Expr::Bool { val: b },
-            Val::Action(assns, inserts) => Expr::Action { assns, inserts },
-            Val::Func(params, body) => Expr::Func { params, body },
+            Val::Action(assns, inserts, edits) => Expr::Action { assns, inserts, edits },
+            Val::Func(params, body) => Expr::Func {
+                param_typs: vec![None; params.len()],
+                params,
+                body,
+            },
         }
     }
 }
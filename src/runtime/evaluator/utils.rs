@@ -1,9 +1,42 @@
-use std::{collections::{HashMap, HashSet}, hash::Hash};
+use std::{collections::{HashMap, HashSet}, hash::Hash, iter::zip};
 
-use crate::ast::Expr;
+use crate::ast::{Expr, Insert, RowEdit, Stmt};
 
 use super::{Evaluator, Val};
 
+/// value of a record entry, without its field or column name
+pub fn entry_val(entry: &Expr) -> &Expr {
+    match entry {
+        Expr::KeyVal { value, .. } => value,
+        entry => entry,
+    }
+}
+
+/// value with fields of its records sorted by name,
+/// so records equal up to the order of their fields compare equal
+pub fn canonical(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Vector { val } => {
+            let mut val = val.iter().map(canonical).collect::<Vec<_>>();
+            val.sort_by(|entry1, entry2| match (entry1, entry2) {
+                (Expr::KeyVal { key: key1, .. }, Expr::KeyVal { key: key2, .. }) => key1.cmp(key2),
+                _ => std::cmp::Ordering::Equal,
+            });
+            Expr::Vector { val }
+        }
+        Expr::List { val } => Expr::List { val: val.iter().map(canonical).collect() },
+        Expr::Variant { name, args } => Expr::Variant {
+            name: name.clone(),
+            args: args.iter().map(canonical).collect(),
+        },
+        Expr::KeyVal { key, value } => Expr::KeyVal {
+            key: key.clone(),
+            value: Box::new(canonical(value)),
+        },
+        expr => expr.clone(),
+    }
+}
+
 impl Evaluator {
     pub fn gen_var(&mut self) -> String {
         self.var_id_cnt += 1;
@@ -11,14 +44,19 @@ impl Evaluator {
         var
     }
 
-    pub fn fold(&mut self, vals: &Vec<Expr>, identity: Expr, operation: &Expr) -> Expr {
+    pub fn fold(&mut self, vals: &Vec<Expr>, identity: Expr, operation: &Expr) -> Result<Expr, String> {
         let mut result = identity;
         for val in vals {
-            let mut func_apply = Expr::FuncApply { func: Box::new(operation.clone()), args: vec![result, val.clone()] };
-            self.eval_expr(&mut func_apply);
-            result = func_apply;
+            result = self.apply(operation, vec![result, val.clone()])?;
         }
-        result
+        Ok(result)
+    }
+
+    /// value of func applied to args
+    pub fn apply(&mut self, func: &Expr, args: Vec<Expr>) -> Result<Expr, String> {
+        let mut func_apply = Expr::FuncApply { func: Box::new(func.clone()), args };
+        self.eval_expr(&mut func_apply)?;
+        Ok(func_apply)
     }
 
     pub fn search_table(&mut self, name: &String) -> Result<Expr, String> {
@@ -33,9 +71,10 @@ impl Evaluator {
     pub fn subst(&mut self, expr: &mut Expr, var_to_expr: &HashMap<String, Expr>) {
         match expr {
             Expr::Number { val } => {}
+            Expr::Float { val } => {}
             Expr::Bool { val } => {}
             Expr::String {val} => {}
-            Expr::Vector { val } => {
+            Expr::Vector { val } | Expr::List { val } => {
                 for expr in val {
                     self.subst(expr, var_to_expr);
                 }
@@ -60,7 +99,7 @@ impl Evaluator {
                 self.subst(expr1, var_to_expr);
                 self.subst(expr2, var_to_expr);
             }
-            Expr::Func { params, body } => {
+            Expr::Func { params, body, .. } => {
                 // assume we want to subst x with M
                 let param_set: HashSet<String> = params.clone().into_iter().collect();
                 for (x, m) in var_to_expr.iter() {
@@ -90,35 +129,140 @@ impl Evaluator {
                 
             }
 
+            Expr::Let { name, val, body, .. } => {
+                self.subst(val, var_to_expr);
+
+                // body is substituted as the body of a function of name
+                for (x, m) in var_to_expr.iter() {
+                    if x == name {
+                        continue;
+                    }
+
+                    let free_var_in_m = m.free_var(&self.reactive_names, &self.reactive_names);
+                    if free_var_in_m.contains(name) {
+                        let new_var = self.gen_var();
+                        let renames = HashMap::from([(name.clone(), new_var.clone())]);
+                        body.alpha_rename(&self.reactive_names, &renames);
+                        *name = new_var;
+                    }
+
+                    let single_subst: HashMap<String, Expr> = HashMap::from([(x.clone(), m.clone())]);
+                    self.subst(body, &single_subst);
+                }
+            }
+
+            Expr::Match { expr, arms } => {
+                self.subst(expr, var_to_expr);
+
+                // each arm is substituted as the body of a function of its binds
+                for arm in arms {
+                    let mut func = Expr::Func {
+                        params: arm.pattern.binds(),
+                        param_typs: vec![],
+                        body: Box::new(std::mem::take(&mut arm.body)),
+                    };
+                    self.subst(&mut func, var_to_expr);
+                    let Expr::Func { params, body, .. } = func else {
+                        unreachable!("subst keeps a function a function")
+                    };
+                    for (bind, param) in zip(arm.pattern.binds_mut(), params) {
+                        *bind = param;
+                    }
+                    arm.body = *body;
+                }
+            }
+
             Expr::FuncApply { func, args } => {
                 self.subst(func, var_to_expr);
                 for arg in args {
                     self.subst(arg, var_to_expr);
                 }
             }
-            Expr::Action { assns, inserts } => {
+            Expr::Action { assns, inserts, edits } => {
                 for assn in assns {
                     // dest should not be substituted, only src should
                     self.subst(&mut assn.src, var_to_expr);
                 }
                 for insert in inserts {
-                    self.subst(&mut insert.row, var_to_expr);
+                    self.subst_insert(insert, var_to_expr);
+                }
+                for edit in edits {
+                    self.subst_edit(edit, var_to_expr);
+                }
+            }
+            Expr::ActionBlock { stmts } => {
+                for stmt in stmts {
+                    self.subst_stmt(stmt, var_to_expr);
                 }
             }
             Expr::Select { where_clause, .. } => {
                 self.subst(where_clause, var_to_expr);
             },
-            Expr::TableColumn { .. } => {},        // since table and column names are typically string literals
+            Expr::TableColumn { table_name, column_name } => {
+                // tables are looked up by their name, a record takes its value
+                match var_to_expr.get(table_name) {
+                    None | Some(Expr::Table { .. }) => {}
+                    Some(Expr::Variable { ident }) => *table_name = ident.clone(),
+                    Some(record) => {
+                        *expr = Expr::RecordField {
+                            expr: Box::new(record.clone()),
+                            name: column_name.clone(),
+                        }
+                    }
+                }
+            }
+            Expr::RecordField { expr, .. } | Expr::TupleIndex { expr, .. } => {
+                self.subst(expr, var_to_expr);
+            }
             Expr::Table { records, .. } => {
                 for record in records {
                     self.subst(record, var_to_expr);
                 }
             },
-            Expr::Fold { args } => {
+            Expr::Fold { args } | Expr::ListOp { args, .. } | Expr::Variant { args, .. } => {
                 for arg in args {
                     self.subst(arg, var_to_expr);
                 }
             }
+            Expr::Spanned { expr, .. } => self.subst(expr, var_to_expr),
+        }
+    }
+    /// subst in a statement of an action, which binds no variable
+    fn subst_stmt(&mut self, stmt: &mut Stmt, var_to_expr: &HashMap<String, Expr>) {
+        match stmt {
+            Stmt::Assn(assn) => self.subst(&mut assn.src, var_to_expr),
+            Stmt::Insert(insert) => self.subst_insert(insert, var_to_expr),
+            Stmt::Edit(edit) => self.subst_edit(edit, var_to_expr),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                self.subst(cond, var_to_expr);
+                for stmt in then_stmts.iter_mut().chain(else_stmts) {
+                    self.subst_stmt(stmt, var_to_expr);
+                }
+            }
+            Stmt::Do(action) => self.subst(action, var_to_expr),
+        }
+    }
+    /// subst in the row of an insert, and in its condition
+    pub fn subst_insert(&mut self, insert: &mut Insert, var_to_expr: &HashMap<String, Expr>) {
+        self.subst(&mut insert.row, var_to_expr);
+        if let Some(cond) = &mut insert.cond {
+            self.subst(cond, var_to_expr);
+        }
+    }
+    /// subst in the condition and new values of an edit, as in a where
+    /// clause of select, e.g. args of the action it is in
+    pub fn subst_edit(&mut self, edit: &mut RowEdit, var_to_expr: &HashMap<String, Expr>) {
+        match edit {
+            RowEdit::Update { sets, where_clause, .. } => {
+                for set in sets {
+                    self.subst(&mut set.src, var_to_expr);
+                }
+                self.subst(where_clause, var_to_expr);
+            }
+            RowEdit::Delete { where_clause, .. } => self.subst(where_clause, var_to_expr),
+        }
+        if let Some(cond) = edit.cond_mut() {
+            self.subst(cond, var_to_expr);
         }
     }
 }
@@ -1,17 +1,17 @@
 use core::panic;
 use std::collections::{BTreeMap, HashMap};
 
-use kameo::actor::ActorRef;
+use serde::{Deserialize, Serialize};
 
-use super::{manager::Manager, transaction::TxnId};
+use super::{manager::Manager, transaction::TxnId, transport::Addr};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LockKind {
     Read,
     Write,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Lock {
     pub lock_kind: LockKind,
     pub txn_id: TxnId,
@@ -60,7 +60,7 @@ impl Lock {
 /// 2. peek and pop min, delete arbitrary waiting lock
 pub struct LockState {
     pub granted_locks: BTreeMap<TxnId, Lock>, // current lock granted
-    pub waiting_locks: BTreeMap<TxnId, (Lock, ActorRef<Manager>)>, // locks waiting to be granted
+    pub waiting_locks: BTreeMap<TxnId, (Lock, Addr<Manager>)>, // locks waiting to be granted
 }
 
 impl LockState {
@@ -73,7 +73,7 @@ impl LockState {
 
     /// only allow lock older than all granted locks to wait
     /// return true if lock added to waiting list
-    pub fn add_wait(&mut self, lock: Lock, who_request: ActorRef<Manager>) -> bool {
+    pub fn add_wait(&mut self, lock: Lock, who_request: Addr<Manager>) -> bool {
         if let Some((oldest, _)) = self.granted_locks.first_key_value() {
             // if receive lock younger than oldest granted lock, ignore
             if lock.txn_id > *oldest {
@@ -87,11 +87,11 @@ impl LockState {
         return true;
     }
 
-    fn pop_oldest_wait(&mut self) -> Option<(Lock, ActorRef<Manager>)> {
+    fn pop_oldest_wait(&mut self) -> Option<(Lock, Addr<Manager>)> {
         self.waiting_locks.pop_first().map(|(_, res)| res)
     }
 
-    pub fn grant_oldest_wait(&mut self) -> Option<(Lock, ActorRef<Manager>)> {
+    pub fn grant_oldest_wait(&mut self) -> Option<(Lock, Addr<Manager>)> {
         assert!(self.check_granted_isvalid());
         if let Some((_, lock)) = self.granted_locks.first_key_value() {
             if lock.is_write() {
@@ -23,13 +23,13 @@ use std::{
     error::Error,
 };
 
-use tokio::sync::mpsc::Sender;
+use log::info;
 
 use crate::{
-    ast::{Assn, Expr, Insert},
+    ast::{Assn, Expr, Insert, RowEdit},
     runtime::{
         def_actor::state,
-        evaluator::eval_assns,
+        evaluator::{eval_assns, eval_edit_conds, eval_inserts},
         lock::{Lock, LockKind},
         manager::{
             action::{DirectReadState, TransReadState, TxnManager, WriteState},
@@ -37,8 +37,12 @@ use crate::{
         },
         message::{CmdMsg, Msg},
         transaction::{Txn, TxnId},
+        transport::ClientAddr,
+    },
+    static_analysis::var_analysis::{
+        qualify::{source_name, source_text},
+        read_write::{calc_read_sets as calc_read_set, calc_touched_names, calc_write_set},
     },
-    static_analysis::var_analysis::read_write::{calc_read_sets as calc_read_set, calc_write_set},
 };
 
 impl Manager {
@@ -48,13 +52,14 @@ impl Manager {
         txn_id: TxnId,
         assns: Vec<Assn>,
         inserts: Vec<Insert>,
-        from_client: Sender<CmdMsg>,
+        edits: Vec<RowEdit>,
+        from_client: ClientAddr,
     ) {
         // static info of txn, the read and write set, which may overlap
-        let direct_read_set = calc_read_set(&assns, &self.evaluator.reactive_names);
+        let direct_read_set = calc_read_set(&assns, &inserts, &edits, &self.evaluator.reactive_names);
         let write_set = calc_write_set(&assns);
 
-        let txn = Txn::new(txn_id.clone(), assns, inserts);
+        let txn = Txn::new(txn_id.clone(), assns, inserts, edits);
 
         // set up txn manager
         let txn_mgr = TxnManager::new(
@@ -163,24 +168,111 @@ impl Manager {
     /// 5. evaluate the transaction and send write requests
     /// (if all reads finished, which is handled by Manager::handler when
     /// receive new ReadVarResult message)
-    pub async fn reeval_and_request_writes(&self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
+    /// if evaluation fails, the txn is aborted and rejected to the client
+    pub async fn reeval_and_request_writes(&mut self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
         let txn_mgr = self.txn_mgrs.get(txn_id).unwrap();
         assert!(txn_mgr.all_read_finished());
 
         let env = txn_mgr.get_read_results();
-        let assns = eval_assns(&txn_mgr.txn.assns, env);
+        let evaled = eval_assns(&txn_mgr.txn.assns, env.clone()).and_then(|assns| {
+            let inserts = eval_inserts(&txn_mgr.txn.inserts, env.clone())?;
+            Ok((assns, inserts, eval_edit_conds(&txn_mgr.txn.edits, env.clone())?))
+        });
+        let (assns, inserts, mut edits) = match evaled {
+            Ok(evaled) => evaled,
+            Err(reason) => return self.reject_txn(txn_id, reason).await,
+        };
+        // edits are sent with the values they read, the table evaluates
+        // them with only the columns of its rows
+        for edit in edits.iter_mut() {
+            self.evaluator.subst_edit_reads(edit, &env);
+        }
+        let txn_mgr = self.txn_mgrs.get_mut(txn_id).unwrap();
+        txn_mgr.set_table_writes(inserts, edits);
+        let txn = txn_mgr.txn.clone();
 
         for Assn { dest, src } in assns {
             self.tell_to_name(
                 &dest,
                 Msg::UsrWriteVarRequest {
                     from_mgr_addr: self.address.clone().unwrap(),
-                    txn: txn_mgr.txn.id.clone(),
+                    txn: txn.id.clone(),
                     write_val: src,
                 },
             )
             .await?;
         }
+        self.request_table_writes(&txn).await?;
+
+        // a txn writing nothing commits at once
+        if self.all_write_finished(txn_id) {
+            self.commit_txn(txn_id).await?;
+        }
+        Ok(())
+    }
+
+    /// abort the txn and reject it to the client, which does not retry it,
+    /// e.g. on a runtime error evaluating what it writes or reads
+    pub async fn reject_txn(&mut self, txn_id: &TxnId, reason: String) -> Result<(), Box<dyn Error>> {
+        self.request_abort_locks(txn_id).await?;
+        self.abort_lock(txn_id);
+        let reason = source_text(&reason);
+        self.get_client_sender(txn_id)
+            .send(CmdMsg::TransactionRejected { txn_id: txn_id.clone(), reason })
+            .await?;
+        Ok(())
+    }
+
+    /// 5'. inserts and edits of the txn are only written once it commits,
+    /// so an aborted and retried txn writes its tables once
+    async fn request_table_writes(&mut self, txn: &Txn) -> Result<(), Box<dyn Error>> {
+        for insert in txn.inserts.iter() {
+            if let Err(e) = self.eval_insert(insert) {
+                info!("Insert into the manager's table {} failed: {}", insert.table_name, e);
+            }
+        }
+        for edit in txn.edits.iter() {
+            if let Err(e) = self.evaluator.eval_edit(edit) {
+                info!("Edit {} of the manager's table failed: {}", edit, e);
+            }
+        }
+
+        // each table written applies all its writes of the txn at once
+        for table_name in txn.tables() {
+            if let Some(actor) = self.tablename_to_actors.get(&table_name) {
+                actor
+                    .tell(Msg::UserWriteTableRequest {
+                        from_mgr_addr: self.address.clone().unwrap(),
+                        txn: txn.clone(),
+                    })
+                    .await?;
+            } else {
+                info!("No table actor for table: {}", table_name);
+                self.add_finished_table_write(&txn.id, table_name);
+            }
+        }
+        Ok(())
+    }
+
+    /// 6'. release locks once all writes finished, and reply to the client
+    pub async fn commit_txn(&mut self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
+        self.release_locks(txn_id).await?;
+        if self.is_code_update(txn_id) {
+            self.finish_code_update().await;
+            return Ok(());
+        }
+
+        info!("release all locks, send commit transaction");
+        let txn_mgr = self.txn_mgrs.get(txn_id).unwrap();
+        let writes = txn_mgr
+            .writes
+            .keys()
+            .chain(txn_mgr.table_writes.keys())
+            .map(|name| source_name(name))
+            .collect();
+        self.get_client_sender(txn_id)
+            .send(CmdMsg::TransactionCommitted { txn_id: txn_id.clone(), writes })
+            .await?;
         Ok(())
     }
 
@@ -190,7 +282,9 @@ impl Manager {
     pub async fn request_abort_locks(&self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
         let txn_mgr = self.txn_mgrs.get(txn_id).unwrap();
 
-        for name in txn_mgr.direct_reads.keys() {
+        // read locks are taken on the vars read transitively,
+        // defs read directly hold no lock
+        for name in txn_mgr.trans_reads.keys() {
             if txn_mgr.writes.contains_key(name) {
                 continue; // should be aborted for write lock
             }
@@ -253,19 +347,44 @@ impl Manager {
         Ok(expr == Expr::Bool { val: true })
     }
 
-    pub fn eval_insert(&mut self, insert: &Insert) -> Result<bool,String> { 
-        
-        self.evaluator.eval_insert(&mut insert.clone());
-        Ok(true)
+    pub fn eval_insert(&mut self, insert: &Insert) -> Result<(), String> {
+        self.evaluator.eval_insert(&mut insert.clone())
     }
 
-    pub fn eval_action(&mut self, mut expr: Expr) -> Result<(Vec<Assn>, Vec<Insert>), String> {
+    /// a name is public to clients if it is a `pub def` of this service,
+    /// or a public name imported from another service
+    pub fn is_public(&self, name: &String) -> bool {
+        self.pub_names.contains(name)
+            || self
+                .evaluator
+                .srv_to_exports
+                .values()
+                .any(|exports| exports.contains_key(name))
+    }
+
+    /// check that a client action only touches public names
+    pub fn check_public(&self, action: &Expr) -> Result<(), String> {
+        for name in calc_touched_names(action, &self.evaluator.reactive_names) {
+            let is_reactive = self.evaluator.reactive_names.contains(&name)
+                || self.evaluator.reactive_name_to_vals.contains_key(&name);
+            if is_reactive && !self.is_public(&name) {
+                let name = source_name(&name);
+                return Err(format!("{} is not public in service {}", name, self.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn eval_action(&mut self, mut expr: Expr) -> Result<(Vec<Assn>, Vec<Insert>, Vec<RowEdit>), String> {
         self.evaluator.eval_expr(&mut expr)?;
 
-        if let Expr::Action { assns, inserts } = expr {
-            Ok((assns.clone(), inserts.clone()))
+        if let Expr::Action { assns, mut inserts, edits } = expr {
+            for insert in inserts.iter_mut() {
+                self.evaluator.order_row(insert)?;
+            }
+            Ok((assns, inserts, edits))
         } else {
-            Err(format!("do requires action expression"))
+            Err(format!("do requires action expression, got {}", expr))
         }
     }
 }
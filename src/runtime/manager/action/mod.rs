@@ -3,13 +3,12 @@
 use std::collections::{HashMap, HashSet};
 
 use serde::de;
-use tokio::sync::mpsc::Sender;
 
 use crate::{
     ast::Expr,
     runtime::{
-        message::CmdMsg,
         transaction::{Txn, TxnId},
+        transport::ClientAddr,
     },
 };
 
@@ -20,13 +19,15 @@ pub mod txn_manager;
 pub struct TxnManager {
     pub txn: Txn,
     /// channel to client who submitted the txn
-    pub from_client: Sender<CmdMsg>,
+    pub from_client: ClientAddr,
 
     /// map of each read to the state
     pub direct_reads: HashMap<String, DirectReadState>, // direct read
     pub trans_reads: HashMap<String, TransReadState>, // transitive read (var only)
     /// .. to write state
     pub writes: HashMap<String, WriteState>,
+    /// tables written, they take no lock and are written at commit
+    pub table_writes: HashMap<String, WriteState>,
     /// preds to apply this transaction
     pub preds: HashSet<Txn>,
 }
@@ -56,7 +57,7 @@ pub enum WriteState {
 impl TxnManager {
     pub fn new(
         txn: Txn,
-        from_client: Sender<CmdMsg>,
+        from_client: ClientAddr,
         direct_reads: HashSet<String>,
         dep_tran_vars: &HashMap<String, HashSet<String>>,
         writes: HashSet<String>,
@@ -85,12 +86,19 @@ impl TxnManager {
                 .map(|name| (name.clone(), WriteState::Requested)),
         );
 
+        let table_write_states = HashMap::from_iter(
+            txn.tables()
+                .into_iter()
+                .map(|name| (name, WriteState::Granted)),
+        );
+
         TxnManager {
             txn,
             from_client,
             direct_reads: direct_read_states,
             trans_reads: trans_read_states,
             writes: write_states,
+            table_writes: table_write_states,
             preds: HashSet::new(),
         }
     }
@@ -1,17 +1,15 @@
 use std::collections::{HashMap, HashSet};
 
-use tokio::sync::mpsc::Sender;
-
 use crate::{
-    ast::Expr,
+    ast::{Expr, Insert, RowEdit},
     runtime::{
         lock::LockKind,
         manager::{
             action::{DirectReadState, TransReadState, TxnManager, WriteState},
             Manager,
         },
-        message::CmdMsg,
         transaction::{Txn, TxnId},
+        transport::ClientAddr,
     },
 };
 
@@ -57,6 +55,21 @@ impl TxnManager {
         self.writes.insert(name, WriteState::Writed);
     }
 
+    /// when a table has applied the writes of the transaction ..
+    /// inserts and edits as evaluated, a table only written by those whose
+    /// condition is false is not written
+    pub fn set_table_writes(&mut self, inserts: Vec<Insert>, edits: Vec<RowEdit>) {
+        self.txn.inserts = inserts;
+        self.txn.edits = edits;
+        let tables = self.txn.tables();
+        self.table_writes.retain(|name, _| tables.contains(name));
+    }
+
+    pub fn add_finished_table_write(&mut self, name: String) {
+        assert!(self.table_writes.get(&name) == Some(WriteState::Granted).as_ref());
+        self.table_writes.insert(name, WriteState::Writed);
+    }
+
     /// check if all locks are granted
     pub fn all_lock_granted(&self) -> bool {
         self.trans_reads
@@ -86,6 +99,7 @@ impl TxnManager {
     /// check if all writes are finished
     pub fn all_write_finished(&self) -> bool {
         self.writes.iter().all(|(_, v)| *v == WriteState::Writed)
+            && self.table_writes.iter().all(|(_, v)| *v == WriteState::Writed)
     }
 
     /// record that the transaction is aborted due to lock aborted
@@ -106,7 +120,7 @@ impl TxnManager {
             || self.writes.iter().any(|(_, v)| *v == WriteState::Aborted)
     }
 
-    pub fn get_client_sender(&self) -> Sender<CmdMsg> {
+    pub fn get_client_sender(&self) -> ClientAddr {
         self.from_client.clone()
     }
 }
@@ -177,10 +191,11 @@ impl Manager {
     delegate_to_txn!(mut add_grant_lock(name: String, kind: LockKind, pred_id: Option<TxnId>));
     delegate_to_txn!(mut add_finished_read(name: String, result: Expr, pred: HashSet<Txn>));
     delegate_to_txn!(mut add_finished_write(name: String));
+    delegate_to_txn!(mut add_finished_table_write(name: String));
     delegate_to_txn!(mut abort_lock());
     delegate_to_txn!(imm all_lock_granted() -> bool);
     delegate_to_txn!(imm all_read_finished() -> bool);
     delegate_to_txn!(imm all_write_finished() -> bool);
     delegate_to_txn!(imm is_aborted() -> bool);
-    delegate_to_txn!(imm get_client_sender() -> Sender<CmdMsg>);
+    delegate_to_txn!(imm get_client_sender() -> ClientAddr);
 }
@@ -6,7 +6,8 @@
 //! - each assertion spawns a def actor, subscribing to all free reactive names in the assertion
 //! - once all transactions required by the assertion are committed and applied by the actor
 //!   we decide if the assertions succeeds or not
-//! - (todo) if some required transactions never arrives, assertion timeout
+//! - if some required transactions never arrives, the test runner times the
+//!   assertion out (see `TIMEOUT_INTERVAL`), its def actor is left pending
 //!
 //! We treat each assertion as a weaker form of transaction + 2 phase process
 //! - no explicit transaction id
@@ -31,6 +32,7 @@ pub struct TestManager {
     pub test_id: TestId,
     pub assert_actor: ActorRef<DefActor>,
     pub trans_reads: HashMap<String, TestTransReadState>,
+    pub is_read: bool, // report value of the expr instead of pass/fail
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
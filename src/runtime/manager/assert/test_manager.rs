@@ -1,4 +1,3 @@
-use core::panic;
 use std::collections::{HashMap, HashSet};
 
 use kameo::actor::ActorRef;
@@ -16,6 +15,7 @@ use crate::{
         transaction::TxnId,
         TestId,
     },
+    static_analysis::var_analysis::qualify::source_text,
 };
 
 impl TestManager {
@@ -42,6 +42,7 @@ impl TestManager {
             test_id,
             assert_actor,
             trans_reads: trans_read_states,
+            is_read: false,
         }
     }
 
@@ -79,28 +80,41 @@ impl Manager {
         self.test_mgrs.insert(test_id, test_mgr);
     }
 
-    pub async fn on_test_finish(&mut self, test_id: TestId, test_result: Expr) {
-        // unwrap test result to bool value
-        let result = match test_result {
-            Expr::Bool { val } => val,
-            _ => panic!("test result should be bool"),
-        };
+    pub async fn on_test_finish(&mut self, test_id: TestId, test_result: Result<Expr, String>) {
+        // deallocate actor
+        let test_mgr = self
+            .test_mgrs
+            .remove(&test_id)
+            .expect(&format!("Test {:?} not found", test_id));
+        let test_result = test_result.map_err(|e| source_text(&e));
+
+        if test_mgr.is_read {
+            self.from_developer
+                .send(CmdMsg::ReadCompleted {
+                    test_id,
+                    value: test_result,
+                })
+                .await
+                .unwrap();
+            return;
+        }
+
+        // unwrap test result to bool value, an error is a runtime error,
+        // e.g. division by zero
+        let result = test_result.and_then(|val| match val {
+            Expr::Bool { val } => Ok(val),
+            val => Err(format!("result should be bool, got {}", val)),
+        });
 
         // send AssertSucceeded back to developer channel
         self.from_developer
             .send(CmdMsg::AssertCompleted {
                 test_id,
-                result: result,
+                result,
             })
             .await
             .unwrap();
 
-        // deallocate actor
-        let _ = self
-            .test_mgrs
-            .remove(&test_id)
-            .expect(&format!("Test {:?} not found", test_id));
-
         // todo!(): remove subscriptions
         // let _ = actor_ref.stop_gracefully().await;
     }
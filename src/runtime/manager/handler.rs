@@ -5,7 +5,7 @@ use std::time::Duration;
 use std::{collections::HashSet, error::Error};
 
 use crate::runtime::message::{CmdMsg, Msg};
-use crate::runtime::transaction::Txn;
+use crate::static_analysis::var_analysis::qualify::source_text;
 use kameo::mailbox::Signal;
 use kameo::{error::Infallible, prelude::*};
 
@@ -21,80 +21,114 @@ impl kameo::prelude::Message<CmdMsg> for Manager {
         use CmdMsg::*;
         info!("MANAGER {} RECEIVE form Command Line: ", self.name);
         match msg {
-            TryAssert { name, test: bool_expr, test_id } => {
+            TryAssert { name, test: mut bool_expr, test_id } => {
                 info!("Try Test {}: expr {}", name, bool_expr);
+                self.namespace.qualify_expr(&mut bool_expr);
                 self.add_new_test(test_id, name, bool_expr).await;
 
                 let _ = self.request_assertion_preds(test_id).await;
+                // an assert reading no var needs no pred
+                if self.all_pred_granted(test_id) {
+                    let _ = self.request_assertion_result(test_id).await;
+                }
                 None
             }
 
-            DoAction { from_client_addr, txn_id, action } => {
+            TryRead { name, mut expr, test_id } => {
+                info!("Try Read {}: expr {}", name, expr);
+                self.namespace.qualify_expr(&mut expr);
+                self.add_new_test(test_id, name, expr).await;
+                self.test_mgrs.get_mut(&test_id).unwrap().is_read = true;
+
+                let _ = self.request_assertion_preds(test_id).await;
+                if self.all_pred_granted(test_id) {
+                    let _ = self.request_assertion_result(test_id).await;
+                }
+                None
+            }
+
+            DoAction { from_client_addr, txn_id, mut action } => {
                 info!("Do Action");
+                self.namespace.qualify_expr(&mut action);
+                // clients can only trigger actions the service made public
+                if let Err(reason) = self.check_public(&action) {
+                    info!("Reject action {}: {}", action, reason);
+                    let _ = from_client_addr
+                        .send(TransactionRejected { txn_id, reason })
+                        .await;
+                    return None;
+                }
+
                 // todo better modularity here
-                if let Ok((assns, inserts)) = self.eval_action(action.clone()) {
-                    // let txn_mgr = self.new_txn(
-                    //     txn_id.clone(),
-                    //     assns.clone(),
-                    //     inserts.clone(),
-                    //     from_client_addr
-                    // );
-                    // self.txn_mgrs.insert(txn_id.clone(), txn_mgr);
-                    self.add_new_txn(txn_id.clone(), assns.clone(), inserts.clone(),from_client_addr);
-
-                    if inserts.is_empty() {
-                        info!("No inserts to process");
+                let (assns, inserts, edits) = match self.eval_action(action.clone()) {
+                    Ok(writes) => writes,
+                    Err(reason) => {
+                        info!("Reject action {}: {}", action, reason);
+                        let reason = source_text(&reason);
+                        let _ = from_client_addr
+                            .send(TransactionRejected { txn_id, reason })
+                            .await;
+                        return None;
                     }
-                    let curr_txn = Txn {
-                        id: txn_id.clone(),
-                        assns: assns,
-                        inserts: inserts.clone()
-                    };
-                    for insert in inserts {
-                        if let Ok(true) = self.eval_insert(&insert) {
-                            info!("Sending user write request to table actor ");
-                            
-                            if let Some(actor) = self.tablename_to_actors.get(&insert.table_name) {
-                                let mgr_addr = self.address
-                                    .as_ref()
-                                    .expect("Manager address not set");
-                                actor
-                                    .tell(Msg::UserWriteTableRequest {
-                                        from_mgr_addr: mgr_addr.clone(),
-                                        txn: curr_txn.clone(),
-                                    }).await
-                                    .unwrap();
-                            } else {
-                                info!("No table actor for table: {}", insert.table_name);
-                            }
-                        }
-                    }
-                } else {
-                    info!("Expr:: Action not found, it is {:?}", &action);
-                }
+                };
+                // let txn_mgr = self.new_txn(
+                //     txn_id.clone(),
+                //     assns.clone(),
+                //     inserts.clone(),
+                //     from_client_addr
+                // );
+                // self.txn_mgrs.insert(txn_id.clone(), txn_mgr);
+                // inserts and edits are buffered in the txn, and written at commit
+                self.add_new_txn(txn_id.clone(), assns, inserts, edits, from_client_addr);
 
                 // request locks
                 let _ = self.request_locks(&txn_id).await;
 
+                // a txn touching no var has no lock to wait for
+                if self.all_lock_granted(&txn_id) {
+                    let _ = self.request_reads(&txn_id).await;
+                    if self.all_read_finished(&txn_id) {
+                        let _ = self.request_writes(&txn_id).await;
+                    }
+                }
+
                 None
             }
 
             TransactionAborted { txn_id } => {
                 info!("Transaction Aborted");
+                if self.is_code_update(&txn_id) {
+                    let _ = self.retry_code_update(&txn_id).await;
+                    return None;
+                }
                 let client_sender = self.get_client_sender(&txn_id);
                 client_sender.send(CmdMsg::TransactionAborted { txn_id }).await.unwrap();
 
                 None
             }
 
-            CodeUpdate { srv } => {
+            CodeUpdate { srv, imports } => {
                 info!("Code Update");
-                self.alloc_service(&srv).await;
+                // update of a running service, replied once applied
+                if self.service.is_some() {
+                    let srv_name = srv.name.clone();
+                    return match self.start_code_update(srv).await {
+                        Ok(()) => None,
+                        Err(error) => Some(CodeUpdateFailed { srv_name, error }),
+                    };
+                }
+
                 // todo(): handle alloc_service asynchronously
                 // to do so, exploit the developer sender
                 // for delayed response
                 // and change logic in runtime.mod
-                Some(CodeUpdateGranted { srv_name: srv.name })
+                match self.alloc_service(&srv, &imports).await {
+                    Ok(()) => Some(CodeUpdateGranted { srv_name: srv.name }),
+                    Err(e) => Some(CodeUpdateFailed {
+                        srv_name: srv.name,
+                        error: e.to_string(),
+                    }),
+                }
             }
             _ => {
                 panic!("Manager should not receive message from REPL");
@@ -121,6 +155,11 @@ impl kameo::prelude::Message<Msg> for Manager {
                     info!("all lock granted");
                     let _ = self.request_reads(&lock.txn_id).await;
                     info!("all read requested");
+
+                    // a txn reading nothing goes on to write
+                    if self.all_read_finished(&lock.txn_id) {
+                        let _ = self.request_writes(&lock.txn_id).await;
+                    }
                 }
 
                 Msg::Unit
@@ -159,8 +198,8 @@ impl kameo::prelude::Message<Msg> for Manager {
                 self.add_finished_read(&txn_id, name, result, pred);
                 info!("add finished read");
                 if self.all_read_finished(&txn_id) {
-                    print!("{:?}", self.txn_mgrs[&txn_id].direct_reads);
-                    let _ = self.reeval_and_request_writes(&txn_id).await;
+                    info!("{:?}", self.txn_mgrs[&txn_id].direct_reads);
+                    let _ = self.request_writes(&txn_id).await;
                     // todo!() current impl isn't optimized for best concurrency
                     // if re-eval block for too long
                     // feel free to spawn a new thread
@@ -181,10 +220,17 @@ impl kameo::prelude::Message<Msg> for Manager {
                     return Msg::Unit;
                 }
 
+                let result = match result {
+                    Ok(result) => result,
+                    Err(reason) => {
+                        let _ = self.reject_txn(&txn_id, reason).await;
+                        return Msg::Unit;
+                    }
+                };
                 self.add_finished_read(&txn_id, name, result, preds);
 
                 if self.all_read_finished(&txn_id) {
-                    let _ = self.reeval_and_request_writes(&txn_id).await;
+                    let _ = self.request_writes(&txn_id).await;
                     // todo!() same above
                 }
                 Msg::Unit
@@ -200,24 +246,7 @@ impl kameo::prelude::Message<Msg> for Manager {
                 self.add_finished_write(&txn_id, name);
 
                 if self.all_write_finished(&txn_id) {
-                    let _ = self.release_locks(&txn_id).await;
-
-                    info!("release all locks, send commit transaction");
-                    let client_sender = self.get_client_sender(&txn_id);
-                    client_sender
-                        .send(CmdMsg::TransactionCommitted {
-                            txn_id: txn_id.clone(),
-                            writes: self
-                                .txn_mgrs
-                                .get(&txn_id)
-                                .unwrap()
-                                .writes
-                                .keys()
-                                .cloned()
-                                .collect(),
-                        })
-                        .await
-                        .unwrap();
+                    let _ = self.commit_txn(&txn_id).await;
                 }
                 Msg::Unit
             }
@@ -230,27 +259,21 @@ impl kameo::prelude::Message<Msg> for Manager {
                 Msg::Unit
             }
 
-            Msg::UserWriteTableFinish {txn: txn_id, ..} => {
+            Msg::UserWriteTableFinish { txn: txn_id, name } => {
                 info!("UserWriteTableFinish");
+                self.add_finished_table_write(&txn_id, name);
 
-                let client_sender = self.get_client_sender(&txn_id);
-                client_sender
-                    .send(CmdMsg::TransactionCommitted { 
-                        txn_id: txn_id.clone(),
-                        writes: self
-                            .txn_mgrs
-                            .get(&txn_id)
-                            .unwrap()
-                            .writes
-                            .keys()
-                            .cloned()
-                            .collect(),
-                     })
-                    .await.unwrap();
-
+                if self.all_write_finished(&txn_id) {
+                    let _ = self.commit_txn(&txn_id).await;
+                }
                 Msg::Unit
             }
 
+            Msg::SrvImportRequest { from_srv } => {
+                info!("Service {} imports {}", from_srv, self.name);
+                self.export_srv()
+            }
+
             Msg::LockAbort { from_name: _, lock } => {
                 info!("Lock Abort");
                 let _ = self.request_abort_locks(&lock.txn_id).await;
@@ -275,7 +298,7 @@ impl Actor for Manager {
     /// to allow manager self reference to its addr
     async fn on_start(&mut self, actor_ref: ActorRef<Self>) -> Result<(), Self::Error> {
         info!("MANAGER on_start got ActorRef with id {}", actor_ref.id());
-        self.address = Some(actor_ref.clone());
+        self.address = Some(actor_ref.into());
         Ok(())
     }
     async fn next(
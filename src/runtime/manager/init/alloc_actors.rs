@@ -7,6 +7,7 @@ use kameo::{prelude::*, spawn};
 
 use crate::runtime::manager::Manager;
 use crate::runtime::table_actor::TableActor;
+use crate::runtime::transport::Addr;
 use crate::runtime::TestId;
 use crate::{
     ast::{Expr, Prog, Service, Decl, Field},
@@ -17,7 +18,7 @@ use crate::{
 impl Manager {
     pub async fn alloc_var_actor(&mut self, name: &String, val: Expr) {
         let actor_ref = spawn(VarActor::new(name.clone(), val));
-        self.varname_to_actors.insert(name.clone(), actor_ref);
+        self.varname_to_actors.insert(name.clone(), actor_ref.into());
     }
 
     /// current impl of alloc_def_actor rely on a centralized manager
@@ -30,7 +31,42 @@ impl Manager {
         name: &String,
         expr: Expr,
     ) -> Result<ActorRef<DefActor>, Box<dyn Error>> {
-        // calculate all information for def actor, default is used for non-source code part, like assertions
+        let (def_arg_to_vals, def_arg_to_vars) = self.def_arg_info(name, &expr)?;
+
+        let mut val = expr.clone();
+        let val = match self.evaluator.eval_expr(&mut val) {
+            Ok(()) => Ok(val),
+            Err(e) => Err(self
+                .evaluator
+                .eval_error(&expr, format!("evaluating {} failed: {}", expr, e))),
+        };
+
+        let def_args = def_arg_to_vals.keys().cloned().collect::<HashSet<_>>();
+        let actor_ref = spawn(DefActor::new(
+            name.clone(),
+            expr,
+            val,
+            def_arg_to_vals,
+            def_arg_to_vars,
+        ));
+        self.defname_to_actors
+            .insert(name.clone(), actor_ref.clone().into());
+
+        // subscribe to its dependencies
+        self.subscribe_def(name, &actor_ref.clone().into(), &def_args)
+            .await?;
+
+        Ok(actor_ref)
+    }
+
+    /// calculate all information for def actor of `name`, namely its args
+    /// to their values and to their transitive vars
+    pub fn def_arg_info(
+        &self,
+        name: &String,
+        expr: &Expr,
+    ) -> Result<(HashMap<String, Expr>, HashMap<String, HashSet<String>>), Box<dyn Error>> {
+        // default is used for non-source code part, like assertions
         let def_args = self.dep_graph.get(name).map_or_else(
             || expr.free_var(&self.evaluator.reactive_names, &HashSet::new()), // incrementally calculated
             |def_args| def_args.clone(), // precalculated by centralized manager
@@ -89,30 +125,24 @@ impl Manager {
             })
             .collect::<HashMap<String, HashSet<String>>>();
 
-        let mut val = expr.clone();
-        let _ = self.evaluator.eval_expr(&mut val);
-
-        let actor_ref = spawn(DefActor::new(
-            name.clone(),
-            expr,
-            val,
-            def_arg_to_vals,
-            def_arg_to_vars,
-        ));
-        self.defname_to_actors
-            .insert(name.clone(), actor_ref.clone());
+        Ok((def_arg_to_vals, def_arg_to_vars))
+    }
 
-        // subscribe to its dependencies
-        // println!("{} subscribe to {:?}", name, def_args);
-        for name in def_args.iter() {
-            // println!("{}", name);
+    /// subscribe def actor of `name` to `deps`
+    pub async fn subscribe_def(
+        &self,
+        name: &str,
+        actor: &Addr<DefActor>,
+        deps: &HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        for dep in deps.iter() {
             // synchronously wait for response
             let back_msg = self
                 .ask_to_name(
-                    name,
+                    dep,
                     Msg::Subscribe {
-                        from_name: name.clone(),
-                        from_addr: actor_ref.clone(),
+                        from_name: name.to_string(),
+                        from_addr: actor.clone(),
                     },
                 )
                 .await?;
@@ -121,16 +151,15 @@ impl Manager {
                 panic!("Service alloc: receive wrong message type during subscription");
             }
 
-            actor_ref.tell(back_msg).await?;
+            actor.tell(back_msg).await?;
         }
-
-        Ok(actor_ref)
+        Ok(())
     }
 
     pub async fn alloc_table_actor(&mut self, name: &String, val: Expr) {
         info!("spawning table actor");
         let actor_ref = spawn(TableActor::new(name.clone(), val));
-        self.tablename_to_actors.insert(name.clone(), actor_ref);
+        self.tablename_to_actors.insert(name.clone(), actor_ref.into());
     }
 }
 
@@ -0,0 +1,149 @@
+//! cross-service import
+//!
+//! when service B imports service A, B's manager asks A's manager for
+//! - values of A's `pub def`s, which become reactive names in B's scope
+//! - actors behind every name touched by those public names, e.g. the def
+//!   actor of a public def, the var actors it transitively depends on, and
+//!   vars written by a public action
+//!
+//! B then treats these actors as if they were local: its def actors subscribe
+//! to A's actors, and its transactions request locks from A's var actors
+//! directly, so preds of a transaction flow across service boundaries.
+//! Private names of A are qualified by A at runtime, e.g. `A::x` (see
+//! `Namespace`), so only A's public names may collide with B's declarations.
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::{
+    ast::Expr,
+    runtime::{manager::Manager, message::Msg, transport::Addr},
+    static_analysis::var_analysis::read_write::calc_touched_names,
+};
+
+impl Manager {
+    /// reply to a service importing this service
+    pub fn export_srv(&self) -> Msg {
+        let exports = self
+            .pub_names
+            .iter()
+            .filter_map(|name| {
+                self.evaluator
+                    .reactive_name_to_vals
+                    .get(name)
+                    .map(|val| (name.clone(), val.clone()))
+            })
+            .collect::<HashMap<String, Expr>>();
+
+        let mut touched = HashSet::new();
+        for (name, val) in exports.iter() {
+            touched.insert(name.clone());
+            touched.extend(calc_touched_names(val, &self.evaluator.reactive_names));
+        }
+        // locks of a touched name are requested on its transitive vars
+        let trans_vars = touched
+            .iter()
+            .filter_map(|name| self.dep_tran_vars.get(name))
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>();
+        touched.extend(trans_vars);
+        // drop identifiers that are not reactive names, e.g. lambda params
+        touched.retain(|name| self.dep_tran_vars.contains_key(name));
+
+        let pick = |name: &String| (name.clone(), self.dep_tran_vars[name].clone());
+        Msg::SrvImportGranted {
+            srv_name: self.name.clone(),
+            exports,
+            dep_tran_vars: touched.iter().map(pick).collect(),
+            var_actors: self
+                .varname_to_actors
+                .iter()
+                .filter(|(name, _)| touched.contains(*name))
+                .map(|(name, actor)| (name.clone(), actor.clone()))
+                .collect(),
+            def_actors: self
+                .defname_to_actors
+                .iter()
+                .filter(|(name, _)| touched.contains(*name))
+                .map(|(name, actor)| (name.clone(), actor.clone()))
+                .collect(),
+            table_actors: self
+                .tablename_to_actors
+                .iter()
+                .filter(|(name, _)| touched.contains(*name))
+                .map(|(name, actor)| (name.clone(), actor.clone()))
+                .collect(),
+        }
+    }
+
+    /// import service `srv_name` managed by `srv_mgr`, return values of its
+    /// public names, and record transitive vars of all imported names
+    /// into `imported_dep_vars`
+    pub async fn import_srv(
+        &mut self,
+        srv_name: &String,
+        srv_mgr: &Addr<Manager>,
+        local_names: &HashSet<String>,
+        imported_dep_vars: &mut HashMap<String, HashSet<String>>,
+    ) -> Result<HashMap<String, Expr>, Box<dyn Error>> {
+        let back_msg = srv_mgr
+            .ask(Msg::SrvImportRequest {
+                from_srv: self.name.clone(),
+            })
+            .await?;
+
+        let Msg::SrvImportGranted {
+            exports,
+            dep_tran_vars,
+            var_actors,
+            def_actors,
+            table_actors,
+            ..
+        } = back_msg
+        else {
+            return Err(format!("Import {}: unexpected reply {:?}", srv_name, back_msg).into());
+        };
+
+        for (name, vars) in dep_tran_vars {
+            if local_names.contains(&name) {
+                return Err(format!(
+                    "Import {}: name {} is declared by both {} and {}",
+                    srv_name, name, self.name, srv_name
+                )
+                .into());
+            }
+
+            let new_id = var_actors
+                .get(&name)
+                .map(|a| a.id())
+                .or(def_actors.get(&name).map(|a| a.id()))
+                .or(table_actors.get(&name).map(|a| a.id()));
+            let old_id = self
+                .varname_to_actors
+                .get(&name)
+                .map(|a| a.id())
+                .or(self.defname_to_actors.get(&name).map(|a| a.id()))
+                .or(self.tablename_to_actors.get(&name).map(|a| a.id()));
+            if old_id.is_some() && old_id != new_id {
+                return Err(format!(
+                    "Import {}: name {} is already imported from {}",
+                    srv_name,
+                    name,
+                    self.remote_names.get(&name).unwrap_or(&self.name)
+                )
+                .into());
+            }
+
+            self.remote_names
+                .entry(name.clone())
+                .or_insert(srv_name.clone());
+            imported_dep_vars.insert(name, vars);
+        }
+
+        self.varname_to_actors.extend(var_actors);
+        self.defname_to_actors.extend(def_actors);
+        self.tablename_to_actors.extend(table_actors);
+
+        Ok(exports)
+    }
+}
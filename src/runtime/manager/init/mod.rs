@@ -9,58 +9,100 @@ use kameo::{prelude::*, spawn, Actor};
 use log::info;
 
 use crate::runtime::manager::Manager;
+use crate::runtime::transport::Addr;
 use crate::{
     ast::{Expr, Prog, Service, Decl},
     runtime::{def_actor::DefActor, evaluator::eval_srv, message::Msg, var_actor::VarActor},
-    static_analysis::var_analysis::calc_dep_srv,
+    static_analysis::var_analysis::{calc_dep_srv, qualify::Namespace, DependAnalysis},
 };
 
 pub mod alloc_actors;
+pub mod imports;
 
 impl Manager {
-    pub async fn alloc_service(&mut self, srv: &Service) {
+    pub async fn alloc_service(
+        &mut self,
+        srv: &Service,
+        imports: &HashMap<String, Addr<Manager>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let namespace = Namespace::new(srv);
+        let srv = &namespace.qualify_srv(srv);
+        self.namespace = namespace;
+
+        let local_names = srv
+            .decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::VarDecl { name, .. }
+                | Decl::DefDecl { name, .. }
+                | Decl::TableDecl { name, .. } => Some(name.clone()),
+                Decl::Import { .. } | Decl::TypeDecl { .. } => None,
+            })
+            .collect::<HashSet<String>>();
+
+        // resolve public names of imported services, together with
+        // actors and transitive vars behind them
+        let mut srv_to_exports = HashMap::new();
+        let mut imported_dep_vars = HashMap::new();
+        for srv_name in srv.imports() {
+            let srv_mgr = imports
+                .get(&srv_name)
+                .ok_or_else(|| format!("Service alloc: imported service {} not found", srv_name))?;
+            let exports = self
+                .import_srv(&srv_name, srv_mgr, &local_names, &mut imported_dep_vars)
+                .await?;
+            srv_to_exports.insert(srv_name, exports);
+        }
+        self.imported_srvs = imports.clone();
+        self.pub_names = srv.pub_names().into_iter().collect();
+
         // intial evaluation of srv
-        self.evaluator = eval_srv(srv);
+        self.evaluator = eval_srv(srv, srv_to_exports);
+        self.evaluator
+            .reactive_names
+            .extend(self.remote_names.keys().cloned());
 
-        let srv_info = calc_dep_srv(srv);
-        self.dep_graph = srv_info.dep_graph;
-        self.dep_tran_vars = srv_info.dep_vars;
+        let srv_info = calc_dep_srv(srv, &imported_dep_vars)?;
+        self.dep_graph = srv_info.dep_graph.clone();
+        self.dep_tran_vars = srv_info.dep_vars.clone();
 
         for name in srv_info.topo_order.iter() {
-            
-            if srv_info.tables.contains(name) {
-                if let Some(Decl::TableDecl { name, fields}) = srv.decls.iter().find( |decl| {
-                    matches!(decl, Decl::TableDecl { name: n , .. } if n == name)
-                }) {
-                    info!("Allocating table actor");
-                    // println!("Fields: {:?}", fields);
-                    self.alloc_table_actor(name, Expr::Table {schema: fields.to_vec(), records: vec![] }).await;
-                }
-                
+            self.alloc_actor(srv, &srv_info, name).await;
+        }
+        self.service = Some(srv.clone());
+
+        info!("Service allocated: {}", self);
+        Ok(())
+    }
+
+    /// allocate actor of var/def/table `name` declared in `srv`
+    pub async fn alloc_actor(&mut self, srv: &Service, srv_info: &DependAnalysis, name: &String) {
+        if srv_info.tables.contains(name) {
+            if let Some(Decl::TableDecl { name, fields, .. }) = srv
+                .decls
+                .iter()
+                .find(|decl| matches!(decl, Decl::TableDecl { name: n, .. } if n == name))
+            {
+                info!("Allocating table actor");
+                self.alloc_table_actor(name, Expr::Table { schema: fields.to_vec(), records: vec![] })
+                    .await;
             }
-            else if srv_info.vars.contains(name) {
+        } else if srv_info.vars.contains(name) {
             let val = self
                 .evaluator
                 .reactive_name_to_vals
                 .get(name)
-                .expect(&format!(
-                    "Service alloc: var/def is not initialized: {}",
-                    name
-                ));
-                self.alloc_var_actor(name, val.clone()).await;
-                info!("Allocating var actor");
-            } else if srv_info.defs.contains(name) {
-                let def_expr = self.evaluator.def_name_to_exprs.get(name).expect(&format!(
-                    "Service alloc: def expr is not initialized: {}",
-                    name
-                ));
+                .expect(&format!("Service alloc: var/def is not initialized: {}", name));
+            self.alloc_var_actor(name, val.clone()).await;
+            info!("Allocating var actor");
+        } else if srv_info.defs.contains(name) {
+            let def_expr = self
+                .evaluator
+                .def_name_to_exprs
+                .get(name)
+                .expect(&format!("Service alloc: def expr is not initialized: {}", name));
 
-                self.alloc_def_actor(name, def_expr.clone())
-                    .await
-                    .unwrap();
-            }
+            self.alloc_def_actor(name, def_expr.clone()).await.unwrap();
         }
-
-        info!("Service allocated: {}", self);
     }
 }
@@ -2,17 +2,20 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Display;
 
-use kameo::prelude::*;
 use tokio::sync::mpsc::Sender;
 
 use crate::runtime::manager::action::TxnManager;
+use crate::ast::Service;
 use crate::runtime::manager::assert::TestManager;
+use crate::runtime::manager::update::PendingUpdate;
 use crate::runtime::message::CmdMsg;
 use crate::runtime::TestId;
+use crate::static_analysis::var_analysis::qualify::Namespace;
 
 use super::def_actor::DefActor;
 use super::evaluator::Evaluator;
 use super::transaction::TxnId;
+use super::transport::Addr;
 use super::var_actor::VarActor;
 use super::table_actor::TableActor;
 
@@ -20,17 +23,31 @@ pub mod action;
 pub mod assert;
 pub mod handler;
 pub mod init;
+pub mod update;
 
 #[derive(Debug)]
 pub struct Manager {
     /// basic info of the manager
     pub name: String,
-    pub address: Option<ActorRef<Manager>>,
+    pub address: Option<Addr<Manager>>,
     pub from_developer: Sender<CmdMsg>, // sender to developer side
 
-    pub varname_to_actors: HashMap<String, ActorRef<VarActor>>,
-    pub defname_to_actors: HashMap<String, ActorRef<DefActor>>,
-    pub tablename_to_actors: HashMap<String, ActorRef<TableActor>>,
+    pub varname_to_actors: HashMap<String, Addr<VarActor>>,
+    pub defname_to_actors: HashMap<String, Addr<DefActor>>,
+    pub tablename_to_actors: HashMap<String, Addr<TableActor>>,
+
+    /// managers of imported services, and names of actors owned by them
+    /// (remote actors are also kept in the name to actor maps above)
+    pub imported_srvs: HashMap<String, Addr<Manager>>,
+    pub remote_names: HashMap<String, String>, // name to its service
+    /// names exported to services importing this service
+    pub pub_names: HashSet<String>,
+
+    /// code of the running service, with its private names qualified,
+    /// code updates are diffed against it
+    pub service: Option<Service>,
+    pub namespace: Namespace,
+    pub code_update: Option<PendingUpdate>,
 
     /// analysis and initial evaluation of program stored at manager
     /// todo!("probably can use for later eval of program")
@@ -60,6 +77,14 @@ impl Manager {
             defname_to_actors: HashMap::new(),
             tablename_to_actors: HashMap::new(),
 
+            imported_srvs: HashMap::new(),
+            remote_names: HashMap::new(),
+            pub_names: HashSet::new(),
+
+            service: None,
+            namespace: Namespace::default(),
+            code_update: None,
+
             evaluator: Evaluator::new(HashMap::new()),
             dep_graph: HashMap::new(),
             dep_tran_vars: HashMap::new(),
@@ -0,0 +1,63 @@
+//! difference between the code of a running service and its update
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Decl, Service};
+
+#[derive(Debug, Clone, Default)]
+pub struct ServiceDiff {
+    pub added: HashSet<String>,   // names only declared by the update
+    pub changed: HashSet<String>, // defs whose expr is changed by the update
+    pub removed: HashSet<String>, // names no longer declared by the update
+}
+
+impl ServiceDiff {
+    /// a var keeps its current value even if its initial value is changed,
+    /// changing the kind of a name or the schema of a table is refused
+    pub fn new(old: &Service, new: &Service) -> Result<ServiceDiff, String> {
+        let old_decls = name_to_decls(old);
+        let new_decls = name_to_decls(new);
+        let mut diff = ServiceDiff::default();
+
+        for (name, new_decl) in new_decls.iter() {
+            match (old_decls.get(name), new_decl) {
+                (None, _) => {
+                    diff.added.insert(name.clone());
+                }
+                (Some(Decl::VarDecl { .. }), Decl::VarDecl { .. }) => {}
+                (Some(Decl::DefDecl { val: old_val, .. }), Decl::DefDecl { val, .. }) => {
+                    if old_val != val {
+                        diff.changed.insert(name.clone());
+                    }
+                }
+                (Some(Decl::TableDecl { fields: old_fields, .. }), Decl::TableDecl { fields, .. }) => {
+                    if old_fields != fields {
+                        return Err(format!("schema of table {} cannot be changed", name));
+                    }
+                }
+                (Some(_), _) => {
+                    return Err(format!("kind of {} cannot be changed, remove it first", name));
+                }
+            }
+        }
+
+        for name in old_decls.keys() {
+            if !new_decls.contains_key(name) {
+                diff.removed.insert(name.clone());
+            }
+        }
+
+        Ok(diff)
+    }
+}
+
+fn name_to_decls(srv: &Service) -> HashMap<String, &Decl> {
+    srv.decls
+        .iter()
+        .filter_map(|decl| match decl {
+            Decl::VarDecl { name, .. }
+            | Decl::DefDecl { name, .. }
+            | Decl::TableDecl { name, .. } => Some((name.clone(), decl)),
+            Decl::Import { .. } | Decl::TypeDecl { .. } => None,
+        })
+        .collect()
+}
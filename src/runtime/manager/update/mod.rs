@@ -0,0 +1,424 @@
+//! live code update of a running service
+//!
+//! the new code of the service is diffed against the running one, see
+//! `diff`, and applied as a transaction of the manager:
+//! 1. affected defs are the changed defs and defs transitively depending on
+//!    changed or added defs, the update writes every var such def depends
+//!    on (before or after the update) with its current value, e.g. `x = x`
+//! 2. locks of these writes are requested as for a client transaction, so
+//!    the update waits for in-flight transactions and aborts/retries by
+//!    wait-die
+//! 3. once vars are read, the update is applied: new actors for added names,
+//!    `DefUpdate` swapping expr of affected def actors, which also
+//!    (un)subscribe to their new (old) dependencies, and removed actors
+//!    are unsubscribed and stopped
+//! 4. writes of the update then propagate as changes with the update txn
+//!    as pred, so affected defs re-evaluate their new expr glitch-freely
+//!
+//! imports of a running service cannot be changed by code update, but what
+//! they export is imported again by each update: an imported service may
+//! have been updated before, so the update also takes new values and
+//! transitive vars of imported names, and defs depending on changed ones
+//! are affected as changed defs are. to refresh its imports, a service
+//! importing an updated one is updated with its running code.
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use log::info;
+
+use crate::{
+    ast::{Assn, Decl, Expr, Service},
+    runtime::{
+        def_actor::DefActor,
+        evaluator::eval_srv,
+        manager::Manager,
+        message::{CmdMsg, Msg},
+        transaction::{Txn, TxnId},
+        transport::Addr,
+    },
+    static_analysis::var_analysis::{calc_dep_srv, qualify::Namespace, DependAnalysis},
+};
+
+use diff::ServiceDiff;
+
+pub mod diff;
+
+/// code update waiting for its locks
+#[derive(Debug, Clone)]
+pub struct PendingUpdate {
+    pub txn_id: TxnId,
+    pub srv: Service,
+    pub namespace: Namespace, // of the new code
+    pub assns: Vec<Assn>, // current value of affected vars written back
+    pub error: Option<String>,
+
+    /// imports as exported when the update started
+    pub srv_to_exports: HashMap<String, HashMap<String, Expr>>,
+    pub imported_dep_vars: HashMap<String, HashSet<String>>,
+    pub imports_changed: HashSet<String>, // imported names whose value or vars changed
+}
+
+impl Manager {
+    /// 1. diff new code of service, and request locks for the update
+    pub async fn start_code_update(&mut self, srv: Service) -> Result<(), String> {
+        if let Some(update) = &self.code_update {
+            return Err(format!(
+                "code update {:?} of {} is in progress",
+                update.txn_id, self.name
+            ));
+        }
+        let old_srv = self.service.as_ref().expect("service should be allocated");
+        let old_imports = old_srv.imports().into_iter().collect::<HashSet<_>>();
+        if old_imports != srv.imports().into_iter().collect() {
+            return Err(format!("imports of {} cannot be changed", self.name));
+        }
+
+        let namespace = Namespace::new(&srv);
+        let srv = namespace.qualify_srv(&srv);
+        let diff = ServiceDiff::new(old_srv, &srv)?;
+
+        let (srv_to_exports, imported_dep_vars) = self.reimport_srvs(&srv).await?;
+        let imports_changed = self.imports_changed(&srv_to_exports, &imported_dep_vars);
+        let srv_info = calc_dep_srv(&srv, &imported_dep_vars)?;
+        info!("Code update of {}: {:?}, imports changed: {:?}", self.name, diff, imports_changed);
+
+        let mut vars = HashSet::new();
+        for name in self.affected_defs(&diff, &srv_info, &imports_changed) {
+            vars.extend(self.dep_tran_vars[&name].iter().cloned());
+            vars.extend(srv_info.dep_vars[&name].iter().cloned());
+        }
+        // tables take no lock in any txn (see TableActor), so neither does
+        // the update: their schema cannot change, and a def reading a table
+        // takes a table change the same way before or after its new expr
+        vars.retain(|var| self.varname_to_actors.contains_key(var) && !diff.removed.contains(var));
+
+        let mut vars = vars.into_iter().collect::<Vec<_>>();
+        vars.sort();
+        let assns = vars
+            .into_iter()
+            .map(|var| Assn {
+                dest: var.clone(),
+                src: Expr::Variable { ident: var },
+            })
+            .collect();
+
+        self.code_update = Some(PendingUpdate {
+            txn_id: TxnId::new(),
+            srv,
+            namespace,
+            assns,
+            error: None,
+            srv_to_exports,
+            imported_dep_vars,
+            imports_changed,
+        });
+        self.request_update_locks().await.map_err(|e| e.to_string())
+    }
+
+    /// import again the services imported by `srv`, return their exports
+    /// and transitive vars of the imported names, actors of names newly
+    /// exported are added to the name to actor maps
+    async fn reimport_srvs(
+        &mut self,
+        srv: &Service,
+    ) -> Result<
+        (HashMap<String, HashMap<String, Expr>>, HashMap<String, HashSet<String>>),
+        String,
+    > {
+        let local_names = srv
+            .decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::VarDecl { name, .. }
+                | Decl::DefDecl { name, .. }
+                | Decl::TableDecl { name, .. } => Some(name.clone()),
+                Decl::Import { .. } | Decl::TypeDecl { .. } => None,
+            })
+            .collect::<HashSet<String>>();
+
+        let mut srv_to_exports = HashMap::new();
+        let mut imported_dep_vars = HashMap::new();
+        for (srv_name, srv_mgr) in self.imported_srvs.clone() {
+            let exports = self
+                .import_srv(&srv_name, &srv_mgr, &local_names, &mut imported_dep_vars)
+                .await
+                .map_err(|e| e.to_string())?;
+            srv_to_exports.insert(srv_name, exports);
+        }
+        Ok((srv_to_exports, imported_dep_vars))
+    }
+
+    /// imported names whose exported value or transitive vars are not the
+    /// ones imported before
+    fn imports_changed(
+        &self,
+        srv_to_exports: &HashMap<String, HashMap<String, Expr>>,
+        imported_dep_vars: &HashMap<String, HashSet<String>>,
+    ) -> HashSet<String> {
+        let mut changed = HashSet::new();
+        for (srv_name, exports) in srv_to_exports.iter() {
+            let old_exports = self.evaluator.srv_to_exports.get(srv_name);
+            for (name, val) in exports.iter() {
+                if old_exports.and_then(|old| old.get(name)) != Some(val) {
+                    changed.insert(name.clone());
+                }
+            }
+        }
+        for (name, vars) in imported_dep_vars.iter() {
+            if self.dep_tran_vars.get(name) != Some(vars) {
+                changed.insert(name.clone());
+            }
+        }
+        changed
+    }
+
+    /// 2. request locks of the update, as a txn without client
+    pub async fn request_update_locks(&mut self) -> Result<(), Box<dyn Error>> {
+        let update = self.code_update.clone().expect("no code update in progress");
+        self.add_new_txn(
+            update.txn_id.clone(),
+            update.assns,
+            vec![],
+            vec![],
+            self.from_developer.clone().into(),
+        );
+        self.request_locks(&update.txn_id).await?;
+
+        // an update touching no var has no lock to wait for
+        if self.all_lock_granted(&update.txn_id) {
+            self.request_writes(&update.txn_id).await?;
+        }
+        Ok(())
+    }
+
+    /// is txn (any retry of it) the code update in progress
+    pub fn is_code_update(&self, txn_id: &TxnId) -> bool {
+        self.code_update
+            .as_ref()
+            .is_some_and(|update| update.txn_id.time == txn_id.time)
+    }
+
+    /// retry the update if its locks are aborted
+    pub async fn retry_code_update(&mut self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
+        let update = self.code_update.as_mut().expect("no code update in progress");
+        if update.txn_id != *txn_id {
+            return Ok(()); // abort of an earlier try
+        }
+        update.txn_id = txn_id.retry_id();
+        self.request_update_locks().await
+    }
+
+    /// 3. apply code update carried by txn if any, then evaluate the txn
+    ///    and send write requests (if all reads finished)
+    pub async fn request_writes(&mut self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
+        if !self.is_code_update(txn_id) {
+            return self.reeval_and_request_writes(txn_id).await;
+        }
+
+        // if update fails, locks are still released by writing back vars
+        let applied = self.apply_code_update(txn_id).await.map_err(|e| e.to_string());
+        if let Err(error) = applied {
+            self.code_update.as_mut().unwrap().error = Some(error);
+        }
+        self.reeval_and_request_writes(txn_id).await
+    }
+
+    /// 4. (after writes finished and locks released)
+    pub async fn finish_code_update(&mut self) {
+        if let Some(update) = self.code_update.take() {
+            info!("Code update {:?} of {} finished", update.txn_id, self.name);
+            let srv_name = self.name.clone();
+            let msg = match update.error {
+                None => CmdMsg::CodeUpdateGranted { srv_name },
+                Some(error) => CmdMsg::CodeUpdateFailed { srv_name, error },
+            };
+            let _ = self.from_developer.send(msg).await;
+        }
+    }
+
+    /// apply the update, or restore the manager to the old code if it fails
+    async fn apply_code_update(&mut self, txn_id: &TxnId) -> Result<(), Box<dyn Error>> {
+        let PendingUpdate {
+            srv,
+            namespace,
+            srv_to_exports,
+            imported_dep_vars,
+            imports_changed,
+            ..
+        } = self.code_update.clone().unwrap();
+        let old_srv = self.service.clone().unwrap();
+        let diff = ServiceDiff::new(&old_srv, &srv)?;
+        let srv_info = calc_dep_srv(&srv, &imported_dep_vars)?;
+        let affected = self.affected_defs(&diff, &srv_info, &imports_changed);
+
+        let old_state = (
+            self.service.replace(srv.clone()),
+            std::mem::replace(&mut self.namespace, namespace),
+            self.pub_names.clone(),
+            self.evaluator.clone(),
+            self.dep_graph.clone(),
+            self.dep_tran_vars.clone(),
+        );
+        let applied = self
+            .apply_srv_diff(txn_id, &srv, srv_to_exports, &diff, &srv_info, &affected)
+            .await
+            .map_err(|e| e.to_string());
+        if applied.is_err() {
+            (
+                self.service,
+                self.namespace,
+                self.pub_names,
+                self.evaluator,
+                self.dep_graph,
+                self.dep_tran_vars,
+            ) = old_state;
+            self.stop_added_actors(&diff).await;
+        }
+        Ok(applied?)
+    }
+
+    async fn apply_srv_diff(
+        &mut self,
+        txn_id: &TxnId,
+        srv: &Service,
+        srv_to_exports: HashMap<String, HashMap<String, Expr>>,
+        diff: &ServiceDiff,
+        srv_info: &DependAnalysis,
+        affected: &HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        // analysis and evaluation of the new code, as in alloc_service
+        self.pub_names = srv.pub_names().into_iter().collect();
+        self.evaluator = eval_srv(srv, srv_to_exports);
+        self.evaluator
+            .reactive_names
+            .extend(self.remote_names.keys().cloned());
+        let old_dep_graph = std::mem::replace(&mut self.dep_graph, srv_info.dep_graph.clone());
+        self.dep_tran_vars = srv_info.dep_vars.clone();
+
+        let txn = self.txn_mgrs[txn_id].txn.clone();
+        for name in srv_info.topo_order.iter() {
+            if diff.added.contains(name) {
+                self.alloc_actor(srv, srv_info, name).await;
+            } else if affected.contains(name) {
+                self.update_def_actor(name, &old_dep_graph[name], diff, &txn)
+                    .await?;
+            }
+        }
+
+        for name in diff.removed.iter() {
+            self.stop_actor(name, &old_dep_graph[name], diff).await?;
+        }
+
+        info!("Service updated: {}", self);
+        Ok(())
+    }
+
+    /// stop actors allocated for added names by a failed update
+    async fn stop_added_actors(&mut self, diff: &ServiceDiff) {
+        for name in diff.added.iter() {
+            if let Some(Addr::Local(actor_ref)) = self.defname_to_actors.remove(name) {
+                let _ = actor_ref.stop_gracefully().await;
+            } else if let Some(Addr::Local(actor_ref)) = self.varname_to_actors.remove(name) {
+                let _ = actor_ref.stop_gracefully().await;
+            } else if let Some(Addr::Local(actor_ref)) = self.tablename_to_actors.remove(name) {
+                let _ = actor_ref.stop_gracefully().await;
+            }
+        }
+    }
+
+    /// swap expr of def actor, and subscribe to its new dependencies
+    async fn update_def_actor(
+        &mut self,
+        name: &String,
+        old_deps: &HashSet<String>,
+        diff: &ServiceDiff,
+        txn: &Txn,
+    ) -> Result<(), Box<dyn Error>> {
+        let expr = self.evaluator.def_name_to_exprs[name].clone();
+        let (arg_to_vals, arg_to_vars) = self.def_arg_info(name, &expr)?;
+        let actor = self.defname_to_actors[name].clone();
+
+        actor
+            .tell(Msg::DefUpdate {
+                expr,
+                arg_to_vals,
+                arg_to_vars,
+                txn: txn.clone(),
+            })
+            .await?;
+
+        let new_deps = self.dep_graph[name].clone();
+        for dep in old_deps.difference(&new_deps) {
+            if !diff.removed.contains(dep) {
+                self.unsubscribe_def(name, &actor, dep).await?;
+            }
+        }
+        let added_deps = new_deps.difference(old_deps).cloned().collect();
+        self.subscribe_def(name, &actor, &added_deps).await
+    }
+
+    /// unsubscribe removed name from its dependencies, and stop its actor
+    async fn stop_actor(
+        &mut self,
+        name: &String,
+        old_deps: &HashSet<String>,
+        diff: &ServiceDiff,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(actor) = self.defname_to_actors.remove(name) {
+            for dep in old_deps.iter() {
+                if !diff.removed.contains(dep) {
+                    self.unsubscribe_def(name, &actor, dep).await?;
+                }
+            }
+            // removed names are declared by this service, thus local
+            if let Addr::Local(actor_ref) = actor {
+                actor_ref.stop_gracefully().await?;
+            }
+        } else if let Some(Addr::Local(actor_ref)) = self.varname_to_actors.remove(name) {
+            actor_ref.stop_gracefully().await?;
+        } else if let Some(Addr::Local(actor_ref)) = self.tablename_to_actors.remove(name) {
+            actor_ref.stop_gracefully().await?;
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe_def(
+        &self,
+        name: &str,
+        actor: &Addr<DefActor>,
+        dep: &String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.tell_to_name(
+            dep,
+            Msg::Unsubscribe {
+                from_name: name.to_string(),
+                from_addr: actor.clone(),
+            },
+        )
+        .await
+    }
+
+    /// defs kept by the update, whose expr or transitive dependencies
+    /// change, imported ones included
+    fn affected_defs(
+        &self,
+        diff: &ServiceDiff,
+        srv_info: &DependAnalysis,
+        imports_changed: &HashSet<String>,
+    ) -> HashSet<String> {
+        srv_info
+            .defs
+            .iter()
+            .filter(|name| !diff.added.contains(*name))
+            .filter(|name| {
+                diff.changed.contains(*name)
+                    || srv_info.dep_transtive[*name].iter().any(|dep| {
+                        diff.changed.contains(dep)
+                            || diff.added.contains(dep)
+                            || imports_changed.contains(dep)
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+}
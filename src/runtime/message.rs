@@ -1,7 +1,7 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use kameo::{actor::ActorRef, Actor, Reply};
-use tokio::sync::mpsc::Sender;
+use kameo::{Actor, Reply};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     ast::{Assn, Expr, Prog, Service, Test, Insert, Field},
@@ -12,14 +12,19 @@ use crate::{
     },
 };
 
-use super::{def_actor::DefActor, manager::Manager, transaction::Txn, var_actor::VarActor};
+use super::{
+    def_actor::DefActor, manager::Manager, table_actor::TableActor, transaction::Txn,
+    transport::{Addr, ClientAddr},
+    var_actor::VarActor,
+};
 
-#[derive(Debug, Clone, Reply)]
+#[derive(Debug, Clone, Reply, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Msg {
     Unit,
 
     UsrReadVarRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         txn: TxnId,
     },
     UsrReadVarResult {
@@ -30,29 +35,29 @@ pub enum Msg {
     },
 
     UsrReadDefRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         txn_id: TxnId,
         pred: Vec<TxnId>, // to obtain read result, def has to see pred in its applied txns
     },
     UsrReadDefResult {
         txn_id: TxnId,
         name: String,
-        result: Expr,
+        result: Result<Expr, String>, // error evaluating the def rejects the txn
         preds: HashSet<Txn>,
     },
 
     TestReadDefRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         test_id: TestId,
         preds: Vec<TxnId>,
     },
     TestReadDefResult {
         test_id: TestId,
-        result: Expr,
+        result: Result<Expr, String>,
     },
 
     UsrWriteVarRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         txn: TxnId,
         write_val: Expr,
         // requires: HashSet<Txn>,
@@ -63,7 +68,7 @@ pub enum Msg {
     },
     // table operations
     UserReadTableRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         txn: TxnId,
         table_name: String,
         where_clause: Expr 
@@ -74,7 +79,7 @@ pub enum Msg {
         result: Expr,    // Expr::Table in this case
     },
     UserWriteTableRequest {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         txn: Txn,
     },
     UserWriteTableFinish {
@@ -83,7 +88,7 @@ pub enum Msg {
     },
 
     TestRequestPred {
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         test_id: TestId,
     }, // for test only
     TestRequestPredGranted {
@@ -94,7 +99,7 @@ pub enum Msg {
 
     LockRequest {
         // for notifying var/def that a lock is requested
-        from_mgr_addr: ActorRef<Manager>,
+        from_mgr_addr: Addr<Manager>,
         lock: Lock,
     },
     LockRelease {
@@ -117,7 +122,7 @@ pub enum Msg {
 
     Subscribe {
         from_name: String,
-        from_addr: ActorRef<DefActor>,
+        from_addr: Addr<DefActor>,
     },
 
     SubscribeGranted {
@@ -125,6 +130,19 @@ pub enum Msg {
         value: Expr,
         preds: HashSet<Txn>,
     },
+    // when a def stops depending on a name, or is removed by code update
+    Unsubscribe {
+        from_name: String,
+        from_addr: Addr<DefActor>,
+    },
+
+    // code update replacing expr of a def, applied as part of txn
+    DefUpdate {
+        expr: Expr,
+        arg_to_vals: HashMap<String, Expr>, // args to their values known by manager
+        arg_to_vars: HashMap<String, HashSet<String>>, // args to their transitive vars
+        txn: Txn,
+    },
 
     // propagate change of name's value, with a set of txns (pred) as prereq
     PropChange {
@@ -132,20 +150,44 @@ pub enum Msg {
         val: Expr,
         preds: HashSet<Txn>, // table probably send Hashset::new() as pred
     },
+
+    // between managers, when a service imports another service
+    SrvImportRequest {
+        from_srv: String,
+    },
+    SrvImportGranted {
+        srv_name: String,
+        exports: HashMap<String, Expr>, // public names to their values
+        // actors behind names touched by public names, and their
+        // transitively dependent vars, so that importer can subscribe,
+        // request locks and read them directly
+        dep_tran_vars: HashMap<String, HashSet<String>>,
+        var_actors: HashMap<String, Addr<VarActor>>,
+        def_actors: HashMap<String, Addr<DefActor>>,
+        table_actors: HashMap<String, Addr<TableActor>>,
+    },
 }
 
-#[derive(Debug, Clone, Reply)]
+#[derive(Debug, Clone, Reply, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum CmdMsg {
-    // Meerkat 2.0 only support non-distributed CodeUpdate
+    // first CodeUpdate allocates the service, replied synchronously;
+    // later ones update the running service, replied to the developer
+    // channel once the update is applied, or synchronously if refused
     CodeUpdate {
         srv: Service,
+        imports: HashMap<String, Addr<Manager>>, // managers of imported services
     },
     CodeUpdateGranted {
         srv_name: String,
     },
+    CodeUpdateFailed {
+        srv_name: String,
+        error: String,
+    },
 
     DoAction {
-        from_client_addr: Sender<CmdMsg>,
+        from_client_addr: ClientAddr,
         txn_id: TxnId,
         action: Expr,
     },
@@ -157,6 +199,11 @@ pub enum CmdMsg {
         txn_id: TxnId,
         writes: Vec<String>,
     },
+    // the action is refused by manager, and will not be retried
+    TransactionRejected {
+        txn_id: TxnId,
+        reason: String,
+    },
 
     TryAssert {
         name: String,
@@ -165,6 +212,18 @@ pub enum CmdMsg {
     },
     AssertCompleted {
         test_id: TestId,
-        result: bool,
+        result: Result<bool, String>, // error evaluating the assert
+    },
+
+    // read current value of expr, answered once its preds are applied
+    // as for asserts
+    TryRead {
+        name: String,
+        expr: Expr,
+        test_id: TestId,
+    },
+    ReadCompleted {
+        test_id: TestId,
+        value: Result<Expr, String>,
     },
 }
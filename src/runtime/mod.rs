@@ -18,15 +18,31 @@
 //!     of boolean_expr
 //!  4. test_manager will wait for bool_expr to be true before processing next
 //!     action, on the other hand, timeout means assertion failed
+//!  5. outcomes of asserts are recorded per test, see `report`
+//!
+//!  # multiple nodes
+//!  services of a program can be spread over several processes, each
+//!  running the same program with a `NodeConfig` choosing the services it
+//!  hosts. imported services not hosted locally are looked up on peers,
+//!  and tests run on the node hosting their service
+//!
+//!  # progress
+//!  services started and outcomes of asserts are printed to stdout as they
+//!  happen, or to stderr when a report is written to stdout, see `progress!`
 use core::panic;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::{
     ast::{Prog, ReplCmd, Service, Test},
     runtime::{
         message::CmdMsg,
+        report::{AssertOutcome, AssertRecord, TestReport},
         transaction::{TxnId, TxnPred},
+        transport::{Addr, NodeConfig},
     },
+    static_analysis::var_analysis::calc_srv_order,
 };
 use futures::future::join_all;
 use kameo::{actor::ActorRef, spawn};
@@ -41,7 +57,10 @@ use tokio::{
 pub mod evaluator;
 pub mod lock;
 pub mod message;
+pub mod repl;
+pub mod report;
 pub mod transaction;
+pub mod transport;
 
 pub mod def_actor;
 pub mod manager;
@@ -51,9 +70,38 @@ pub mod table_actor;
 
 pub type TestId = (usize, usize);
 const MPSC_CHANNEL_SIZE: usize = 100;
-const TIMEOUT_INTERVAL: u64 = 5000;
+/// default time (ms) an assert waits for its result before timing out
+pub const TIMEOUT_INTERVAL: u64 = 5000;
+
+static PROGRESS_TO_STDERR: AtomicBool = AtomicBool::new(false);
 
-pub async fn run(prog: &Prog) -> Result<(), Box<dyn std::error::Error>> {
+/// print progress to stderr, keeping stdout for a report
+pub fn progress_to_stderr() {
+    PROGRESS_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn is_progress_to_stderr() -> bool {
+    PROGRESS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// println! of progress, see `progress_to_stderr`
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::runtime::is_progress_to_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use progress;
+
+/// run tests hosted by this node, return their reports
+pub async fn run(
+    prog: &Prog,
+    node: &NodeConfig,
+    timeout: Duration,
+) -> Result<Vec<TestReport>, Box<dyn std::error::Error>> {
     let (dev_tx, mut dev_rx) = mpsc::channel::<CmdMsg>(MPSC_CHANNEL_SIZE);
 
     assert!(
@@ -61,14 +109,23 @@ pub async fn run(prog: &Prog) -> Result<(), Box<dyn std::error::Error>> {
         "There must be at least one service and one test"
     );
 
-    let mut services = HashMap::new();
-    for srv in &prog.services {
-        let srv_actor_ref = run_srv(srv, dev_tx.clone()).await?;
-        services.insert(srv.name.clone(), srv_actor_ref);
+    let services = run_srvs(prog, node, dev_tx).await?;
+
+    // tests run on the node hosting their service
+    let local_tests = prog
+        .tests
+        .iter()
+        .enumerate()
+        .filter(|(_, test)| services.contains_key(&test.name))
+        .collect::<Vec<_>>();
+    if local_tests.is_empty() && node.listen_addr().is_some() {
+        progress!("Serving {:?}, press ctrl-c to stop", services.keys());
+        tokio::signal::ctrl_c().await?;
+        return Ok(vec![]);
     }
 
     let mut test_channels = HashMap::new();
-    let test_completions = join_all(prog.tests.iter().enumerate().map(|(idx, test)| {
+    let test_completions = join_all(local_tests.into_iter().map(|(idx, test)| {
         let (tst_tx, tst_rx) = mpsc::channel::<CmdMsg>(MPSC_CHANNEL_SIZE);
         let (cli_tx, cli_rx) = mpsc::channel::<CmdMsg>(MPSC_CHANNEL_SIZE);
         test_channels.insert(idx, tst_tx);
@@ -80,6 +137,7 @@ pub async fn run(prog: &Prog) -> Result<(), Box<dyn std::error::Error>> {
             cli_tx.clone(),
             cli_rx,
             tst_rx,
+            timeout,
         )
     }));
 
@@ -89,41 +147,97 @@ pub async fn run(prog: &Prog) -> Result<(), Box<dyn std::error::Error>> {
             match msg {
                 CmdMsg::AssertCompleted { test_id, result } => {
                     let test_idx = test_id.0;
-                    test_channels
+                    // a timed out assert may complete after its test finished
+                    let _ = test_channels
                         .get(&test_idx)
                         .unwrap()
                         .send(CmdMsg::AssertCompleted { test_id, result })
-                        .await
-                        .unwrap();
+                        .await;
                 }
                 _ => panic!("Unexpected message on dev_rx: {:?}", msg),
             }
         }
     });
 
+    let mut reports = vec![];
     for result in test_completions.await {
-        result?;
+        reports.push(result?);
+    }
+
+    Ok(reports)
+}
+
+/// allocate services hosted by this node, return their managers
+pub async fn run_srvs(
+    prog: &Prog,
+    node: &NodeConfig,
+    dev_tx: Sender<CmdMsg>,
+) -> Result<HashMap<String, ActorRef<Manager>>, Box<dyn std::error::Error>> {
+    if let Some(addr) = node.listen_addr() {
+        let addr = transport::listen(&addr).await?;
+        progress!("Node listening on {}", addr);
+    }
+
+    // services are allocated after the services they import
+    let mut services = HashMap::new();
+    for srv in calc_srv_order(prog)? {
+        if !node.hosts(&srv.name) {
+            continue;
+        }
+
+        let srv_actor_ref = start_srv(srv, node, dev_tx.clone(), &services).await?;
+        services.insert(srv.name.clone(), srv_actor_ref);
     }
 
-    Ok(())
+    Ok(services)
+}
+
+/// allocate a service importing services of this node (`services`) or of
+/// its peers, and register it by name
+pub async fn start_srv(
+    srv: &Service,
+    node: &NodeConfig,
+    dev_tx: Sender<CmdMsg>,
+    services: &HashMap<String, ActorRef<Manager>>,
+) -> Result<ActorRef<Manager>, Box<dyn std::error::Error>> {
+    let mut imports = HashMap::new();
+    for srv_name in srv.imports() {
+        let srv_mgr = match services.get(&srv_name) {
+            Some(srv_actor_ref) => Addr::Local(ActorRef::clone(srv_actor_ref)),
+            None => transport::lookup_service(&node.peers, &srv_name).await?,
+        };
+        imports.insert(srv_name, srv_mgr);
+    }
+
+    let srv_actor_ref = run_srv(srv, dev_tx, imports).await?;
+    transport::register(&srv.name, &srv_actor_ref);
+    Ok(srv_actor_ref)
 }
 
 pub async fn run_srv(
     srv: &Service,
     dev_tx: Sender<CmdMsg>,
+    imports: HashMap<String, Addr<Manager>>, // managers of imported services
 ) -> Result<ActorRef<Manager>, Box<dyn std::error::Error>> {
     // initialize the service's manager
     let srv_manager = Manager::new(srv.name.clone(), dev_tx);
     let srv_actor_ref = spawn(srv_manager);
 
     // synchronously wait for manager to be initialized
-    if let Some(CmdMsg::CodeUpdateGranted { .. }) = srv_actor_ref
-        .ask(CmdMsg::CodeUpdate { srv: srv.clone() })
+    match srv_actor_ref
+        .ask(CmdMsg::CodeUpdate {
+            srv: srv.clone(),
+            imports,
+        })
         .await?
     {
-        println!("Service {} initialized", srv.name);
-    } else {
-        panic!("Service {} initialization failed", srv.name);
+        Some(CmdMsg::CodeUpdateGranted { .. }) => {
+            progress!("Service {} initialized", srv.name);
+        }
+        Some(CmdMsg::CodeUpdateFailed { error, .. }) => {
+            return Err(format!("Service {} initialization failed: {}", srv.name, error).into());
+        }
+        _ => panic!("Service {} initialization failed", srv.name),
     }
 
     Ok(srv_actor_ref)
@@ -138,18 +252,20 @@ pub async fn run_srv(
 /// * `cli_tx` - A sender channel for sending `CmdMsg` commands to the client.
 /// * `cli_rx` - A receiver channel for receiving `CmdMsg` responses from the client.
 /// * `dev_rx` - A receiver channel for receiving `CmdMsg` responses from the manager.
+/// * `timeout` - How long an assert or a `do` waits for its result before it counts as timed out.
 ///
 /// # Returns
 ///
-/// A `Result` that is `Ok` if the test completes successfully, or an error if something goes wrong.
+/// A `Result` with the outcome counts of the test's asserts, or an error if something goes wrong.
 ///
 /// # Description
 ///
 /// The function iterates over a list of commands in the test. It handles `Do` commands by
-/// initiating a transaction and waiting for commit or abort messages. It handles `Assert`
+/// initiating a transaction and waiting for commit or abort messages, a `do` rejected or not
+/// committed in time is recorded as a failed or timed out entry. It handles `Assert`
 /// commands by sending assertions to the manager and waiting for a success message or a timeout.
-/// The test proceeds to the next command only if assertions are successful or transactions
-/// are committed. The function concludes when all commands have been processed.
+/// The test proceeds to the next command once assertions are decided (or timed out) and
+/// transactions are committed. The function concludes when all commands have been processed.
 
 pub async fn run_test(
     idx: usize,
@@ -158,11 +274,13 @@ pub async fn run_test(
     cli_tx: Sender<CmdMsg>,
     mut cli_rx: Receiver<CmdMsg>,
     mut tst_rx: Receiver<CmdMsg>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    timeout: Duration,
+) -> Result<TestReport, Box<dyn std::error::Error>> {
     // start testing on the service
-    println!("testing {}", test.name);
+    progress!("testing {}", test.name);
+    let mut report = TestReport::new(idx, test.name.clone());
     let mut test_id = 0usize;
-    let mut received_passed_tests = HashMap::<TestId, bool>::new();
+    let mut received_passed_tests = HashMap::<TestId, Result<bool, String>>::new();
 
     // one handler loop keep listening to incoming messages from manager
     // the main thread keep processing actions and asserts
@@ -172,6 +290,7 @@ pub async fn run_test(
     let mut process_cmd_idx = 0;
 
     let mut retry_txid: Option<TxnId> = None;
+    let mut retries = 0; // of transactions since the last assert
     while process_cmd_idx < test.commands.len() {
         let cmd = &test.commands[process_cmd_idx];
 
@@ -179,38 +298,73 @@ pub async fn run_test(
             ReplCmd::Do(action) => {
                 let txn_id = retry_txid.take().unwrap_or_else(TxnId::new);
                 txn_to_cmd_idx.insert(txn_id.clone(), process_cmd_idx);
+                let start = tokio::time::Instant::now();
+                let deadline = start + timeout;
                 srv_actor_ref
                     .tell(CmdMsg::DoAction {
-                        txn_id,
+                        txn_id: txn_id.clone(),
                         action: action.clone(),
-                        from_client_addr: cli_tx.clone(),
+                        from_client_addr: cli_tx.clone().into(),
                     })
                     .await?;
 
-                loop {
-                    tokio::select! {
-                        maybe_msg = cli_rx.recv() => {
-                            if let Some(msg) = maybe_msg {
-                                match msg {
-                                    CmdMsg::TransactionAborted { txn_id } => {
-                                        info!("Transaction {txn_id:?} aborted. Retrying");
-                                        retry_txid = Some(txn_id.retry_id());
-                                        break;
-                                    }
-                                    CmdMsg::TransactionCommitted { txn_id, writes } => {
-                                        info!("Transaction {:?} committed", txn_id);
-                                        process_cmd_idx += 1;
-                                        break;
-                                    }
-                                    _ => panic!("unexpected message")
-                                }
-                            }
+                // a do that is not committed fails the test, none if retried
+                let outcome: Option<(AssertOutcome, Option<String>)> = loop {
+                    match tokio::time::timeout_at(deadline, cli_rx.recv()).await {
+                        Ok(Some(CmdMsg::TransactionAborted { txn_id: id })) if id == txn_id => {
+                            info!("Transaction {id:?} aborted. Retrying");
+                            retries += 1;
+                            retry_txid = Some(id.retry_id());
+                            break None;
+                        }
+                        Ok(Some(CmdMsg::TransactionCommitted { txn_id: id, .. })) if id == txn_id => {
+                            info!("Transaction {:?} committed", id);
+                            break Some((AssertOutcome::Passed, None));
+                        }
+                        Ok(Some(CmdMsg::TransactionRejected { txn_id: id, reason })) if id == txn_id => {
+                            progress!("reject do {}: {}", action, reason);
+                            info!("Transaction {:?} rejected", id);
+                            break Some((AssertOutcome::Failed, Some(format!("rejected: {}", reason))));
+                        }
+                        // reply to an earlier, timed out transaction
+                        Ok(Some(
+                            CmdMsg::TransactionAborted { .. }
+                            | CmdMsg::TransactionCommitted { .. }
+                            | CmdMsg::TransactionRejected { .. },
+                        )) => {}
+                        Ok(Some(msg)) => {
+                            let reason = format!("unexpected message {:?}", msg);
+                            progress!("fail do {}: {}", action, reason);
+                            break Some((AssertOutcome::Failed, Some(reason)));
+                        }
+                        Ok(None) => return Err("client channel closed".into()),
+                        Err(_) => {
+                            progress!("timeout do {}", action);
+                            let reason = "not committed within the timeout".to_string();
+                            break Some((AssertOutcome::TimedOut, Some(reason)));
                         }
                     }
+                };
+
+                match outcome {
+                    None => {}
+                    Some((AssertOutcome::Passed, _)) => process_cmd_idx += 1,
+                    Some((outcome, reason)) => {
+                        report.record(AssertRecord {
+                            expr: format!("do {}", action),
+                            outcome,
+                            reason,
+                            duration: start.elapsed(),
+                            retries: std::mem::take(&mut retries),
+                        });
+                        process_cmd_idx += 1;
+                    }
                 }
             }
             ReplCmd::Assert(expr) => {
                 test_id += 1;
+                let start = tokio::time::Instant::now();
+                let deadline = start + timeout;
 
                 srv_actor_ref
                     .tell(CmdMsg::TryAssert {
@@ -220,45 +374,46 @@ pub async fn run_test(
                     })
                     .await?;
 
-                loop {
+                // results of earlier timed out asserts may still arrive
+                let (outcome, reason) = loop {
                     if let Some(result) = received_passed_tests.get(&(idx, test_id)) {
-                        if *result {
-                            println!("pass test {}", expr);
-                        } else {
-                            println!("fail test {}", expr);
+                        if let Err(e) = result {
+                            progress!("error in test {}: {}", expr, e);
                         }
-                        process_cmd_idx += 1;
-                        break;
+                        let outcome = AssertOutcome::from(*result.as_ref().unwrap_or(&false));
+                        break (outcome, result.as_ref().err().cloned());
                     }
 
-                    let maybe_msg = tst_rx.recv().await;
-                    if let Some(CmdMsg::AssertCompleted {
-                        test_id: recv_id,
-                        result: test_result,
-                    }) = maybe_msg
-                    {
-                        info!(
-                            "Manager received Assertion {:?} {}",
-                            recv_id,
-                            if test_result { "passed" } else { "failed" }
-                        );
-                        received_passed_tests.insert(recv_id, test_result);
-
-                        if let Some(result) = received_passed_tests.get(&(idx, test_id)) {
-                            if *result {
-                                println!("pass test {}", expr);
-                            } else {
-                                println!("fail test {}", expr);
-                            }
-
-                            process_cmd_idx += 1;
-                            break;
+                    match tokio::time::timeout_at(deadline, tst_rx.recv()).await {
+                        Ok(Some(CmdMsg::AssertCompleted {
+                            test_id: recv_id,
+                            result: test_result,
+                        })) => {
+                            info!(
+                                "Manager received Assertion {:?} {}",
+                                recv_id,
+                                if test_result == Ok(true) { "passed" } else { "failed" }
+                            );
+                            received_passed_tests.insert(recv_id, test_result);
                         }
+                        Ok(Some(_)) => {}
+                        Ok(None) => return Err("test channel closed".into()),
+                        Err(_) => break (AssertOutcome::TimedOut, None),
                     }
-                }
+                };
+
+                progress!("{} test {}", outcome, expr);
+                report.record(AssertRecord {
+                    expr: expr.to_string(),
+                    outcome,
+                    reason,
+                    duration: start.elapsed(),
+                    retries: std::mem::take(&mut retries),
+                });
+                process_cmd_idx += 1;
             }
         }
     }
-    println!("testing {} finished", test.name);
-    Ok(())
+    progress!("testing {} finished", test.name);
+    Ok(report)
 }
@@ -3,16 +3,14 @@
 //!             below is a premature implementation
 //! - choice 2: use https://github.com/tqwewe/kameo (for now)
 
-use kameo::actor::ActorRef;
-
-use super::{def_actor::DefActor, message::Msg};
+use super::{def_actor::DefActor, message::Msg, transport::Addr};
 
 /// - (layer 1: actor system) communicate messages between local / remote nodes
 ///    use kameo
 /// - (layer 2: pub/subscribers) maintain network topology between nodes
 ///    similar to kameo/actors/src/pubsub.rs
 pub struct PubSub {
-    subscribers: Vec<ActorRef<DefActor>>, // todo: generalize to all actors
+    subscribers: Vec<Addr<DefActor>>, // todo: generalize to all actors
 }
 
 impl PubSub {
@@ -22,10 +20,14 @@ impl PubSub {
         }
     }
 
-    pub fn subscribe(&mut self, subscriber: ActorRef<DefActor>) {
+    pub fn subscribe(&mut self, subscriber: Addr<DefActor>) {
         self.subscribers.push(subscriber);
     }
 
+    pub fn unsubscribe(&mut self, subscriber: &Addr<DefActor>) {
+        self.subscribers.retain(|s| s.id() != subscriber.id());
+    }
+
     /// developer note: don't use future.join_all() overhead there
     /// https://github.com/tqwewe/kameo/issues/157
     pub async fn publish(&self, msg: Msg) {
@@ -0,0 +1,542 @@
+//! interactive REPL against running services
+//!
+//! services of the program are allocated as for tests, then each line typed
+//! by the developer is one of
+//!   do <expr>;          run an action on the current service
+//!   assert(<expr>);     check expr holds on the current service
+//!   :print <expr>       print current value of a name (or any expression)
+//!   :services           list services, the current one marked by *
+//!   :use <service>      switch the current service
+//!   :update <file>      update running services to the code of file,
+//!                       then run the @test blocks of file
+//!   :help               print this list
+//!   :quit               exit, as does ctrl-d
+//!
+//! `do` and `assert` go through `CmdMsg::DoAction` and `CmdMsg::TryAssert`
+//! exactly as commands of a `@test` block, `:print` reuses the assert path
+//! with `CmdMsg::TryRead`. `:update` sends `CmdMsg::CodeUpdate` to managers
+//! of running services, and starts services new to the program.
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::time::Duration;
+
+use kameo::actor::ActorRef;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::{
+    ast::{Expr, Prog, ReplCmd, Service, Span, Test},
+    parser::{
+        resolve::Scope,
+        parser::{parse_repl_cmd, parse_source},
+    },
+    runtime::{
+        manager::Manager,
+        message::CmdMsg,
+        report::{print_summary, AssertOutcome, AssertRecord, TestReport},
+        run_srvs, start_srv,
+        transaction::TxnId,
+        transport::NodeConfig,
+        TestId, MPSC_CHANNEL_SIZE,
+    },
+    static_analysis::{
+        typecheck::{typecheck_prog, typecheck_repl_expr, TypeError},
+        var_analysis::{calc_srv_order, cycles::check_cycles},
+    },
+};
+
+const HELP: &str = "\
+do <expr>;          run an action on the current service
+assert(<expr>);     check expr holds on the current service
+:print <expr>       print current value of a name (or any expression)
+:services           list services, the current one marked by *
+:use <service>      switch the current service
+:update <file>      update running services to the code of file,
+                    then run the @test blocks of file
+:help               print this list
+:quit               exit, as does ctrl-d";
+
+/// test index of repl asserts, tests of the program are not run by the repl
+const REPL_TEST_IDX: usize = 0;
+
+/// how a `do` ended, retries of aborted transactions aside
+enum DoOutcome {
+    Committed(Vec<String>), // names written
+    Rejected(String),
+    TimedOut,
+}
+
+struct Repl<'a> {
+    prog: Prog,
+    node: &'a NodeConfig,
+    services: HashMap<String, ActorRef<Manager>>,
+    current: String,
+
+    dev_tx: Sender<CmdMsg>,
+    cli_tx: Sender<CmdMsg>,
+    cli_rx: Receiver<CmdMsg>,
+    dev_rx: Receiver<CmdMsg>,
+    test_id: usize,
+    timeout: Duration, // of asserts, reads, and each attempt of a do
+    retries: u32,      // of transactions since the last recorded assert
+}
+
+pub async fn run_repl(
+    prog: &Prog,
+    node: &NodeConfig,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let (dev_tx, dev_rx) = mpsc::channel::<CmdMsg>(MPSC_CHANNEL_SIZE);
+    let (cli_tx, cli_rx) = mpsc::channel::<CmdMsg>(MPSC_CHANNEL_SIZE);
+
+    let services = run_srvs(prog, node, dev_tx.clone()).await?;
+    let current = prog
+        .services
+        .iter()
+        .find(|srv| services.contains_key(&srv.name))
+        .ok_or("no service hosted by this node")?
+        .name
+        .clone();
+
+    let mut repl = Repl {
+        prog: prog.clone(),
+        node,
+        services,
+        current,
+        dev_tx,
+        cli_tx,
+        cli_rx,
+        dev_rx,
+        test_id: 0,
+        timeout,
+        retries: 0,
+    };
+    println!("type :help for commands");
+
+    let mut editor = DefaultEditor::new()?;
+    loop {
+        let prompt = format!("{}> ", repl.current);
+        // readline blocks, let other tasks of the runtime move on meanwhile
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+
+        match repl.eval_line(line).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+impl Repl<'_> {
+    /// return false if the developer quits
+    async fn eval_line(&mut self, line: &str) -> Result<bool, String> {
+        let (cmd, arg) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(cmd, arg)| (cmd, arg.trim()));
+
+        match cmd {
+            ":quit" | ":q" => return Ok(false),
+            ":help" | ":h" => println!("{}", HELP),
+            ":services" => self.list_services(),
+            ":use" => {
+                if !self.services.contains_key(arg) {
+                    return Err(format!("service {} is not hosted by this node", arg));
+                }
+                self.current = arg.to_string();
+            }
+            ":update" => self.update(arg).await?,
+            ":print" => {
+                let expr = self.parse_expr(arg)?;
+                let value = self.read(expr).await?;
+                println!("{} = {}", arg, value);
+            }
+            _ if cmd.starts_with(':') => return Err(format!("unknown command {}", cmd)),
+
+            // language level commands, as in @test blocks
+            _ => {
+                // the trailing semicolon is optional at the prompt
+                let input = if line.ends_with(';') {
+                    line.to_string()
+                } else {
+                    format!("{};", line)
+                };
+                match self.parse_cmd(&input)? {
+                    ReplCmd::Do(action) => match self.do_action(action).await? {
+                        DoOutcome::Committed(writes) => println!("committed, wrote {:?}", writes),
+                        DoOutcome::Rejected(reason) => return Err(format!("rejected: {}", reason)),
+                        DoOutcome::TimedOut => return Err("do timed out".to_string()),
+                    },
+                    ReplCmd::Assert(expr) => {
+                        let (outcome, _, _) = self.assert(expr.clone()).await?;
+                        println!("{} test {}", outcome, expr);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn list_services(&self) {
+        for srv in self.prog.services.iter() {
+            let mark = if srv.name == self.current { "*" } else { " " };
+            let hosted = if self.services.contains_key(&srv.name) {
+                ""
+            } else {
+                " (remote)"
+            };
+            println!("{} {}{} pub: {:?}", mark, srv.name, hosted, srv.pub_names());
+        }
+    }
+
+    /// update services to the code of `file`, then run its tests
+    async fn update(&mut self, file: &str) -> Result<(), String> {
+        let input = fs::read_to_string(file).map_err(|e| format!("cannot read {}: {}", file, e))?;
+        let mut prog = parse_source(file, &input)
+            .map_err(|diag| format!("{} does not parse, nothing updated\n{}", file, diag))?;
+        check_cycles(&prog).map_err(|cycles| {
+            let diags = cycles
+                .iter()
+                .map(|cycle| cycle.to_diagnostic(file, &input).to_string())
+                .collect::<Vec<_>>();
+            format!("{} has dependency cycles, nothing updated\n{}", file, diags.join("\n"))
+        })?;
+        typecheck_prog(&prog).map_err(|errors| {
+            let diags = errors
+                .iter()
+                .map(|e| e.to_diagnostic(file, &input).to_string())
+                .collect::<Vec<_>>();
+            format!("{} does not typecheck, nothing updated\n{}", file, diags.join("\n"))
+        })?;
+        prog.erase_spans();
+        self.check_importers(&calc_srv_order(&prog)?)?;
+
+        // services missing from file keep running with their old code
+        let mut services = prog.services.clone();
+        for srv in self.prog.services.iter() {
+            if !services.iter().any(|new_srv| new_srv.name == srv.name) {
+                println!("Service {} is not in {}, kept running", srv.name, file);
+                services.push(srv.clone());
+            }
+        }
+        let new_prog = Prog {
+            services,
+            tests: prog.tests.clone(),
+        };
+
+        // a kept service importing an updated one is updated with its
+        // running code, which imports the new exports again. importers
+        // hosted by other nodes are not refreshed
+        let mut updated = HashSet::new();
+        for srv in calc_srv_order(&new_prog)? {
+            let in_file = prog.services.iter().any(|new_srv| new_srv.name == srv.name);
+            if self.services.contains_key(&srv.name) {
+                if in_file {
+                    self.update_srv(srv).await?;
+                    println!("Service {} updated", srv.name);
+                } else if srv.imports().iter().any(|name| updated.contains(name)) {
+                    self.update_srv(srv).await?;
+                    println!("Service {} imports refreshed", srv.name);
+                } else {
+                    continue;
+                }
+                updated.insert(srv.name.clone());
+            } else if in_file && self.node.hosts(&srv.name) {
+                let srv_actor_ref = start_srv(srv, self.node, self.dev_tx.clone(), &self.services)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.services.insert(srv.name.clone(), srv_actor_ref);
+            }
+        }
+        self.prog = new_prog;
+
+        let mut reports = vec![];
+        for (idx, test) in prog.tests.iter().enumerate() {
+            if self.services.contains_key(&test.name) {
+                reports.push(self.run_test(idx, test).await?);
+            }
+        }
+        if !reports.is_empty() {
+            print_summary(&reports);
+        }
+        Ok(())
+    }
+
+    /// services are updated one by one, imported ones first, so while a
+    /// service is updated its importers still run their old code: each step
+    /// must typecheck with them, e.g. a pub def they read cannot be removed
+    /// or retyped
+    fn check_importers(&self, srvs_in_order: &[&Service]) -> Result<(), String> {
+        let mut updated = HashMap::new();
+        for srv in srvs_in_order {
+            updated.insert(srv.name.clone(), (*srv).clone());
+            let running = self
+                .prog
+                .services
+                .iter()
+                .filter(|old_srv| !updated.contains_key(&old_srv.name))
+                .collect::<Vec<_>>();
+            if running.is_empty() {
+                continue;
+            }
+
+            let mut services = updated.values().cloned().collect::<Vec<_>>();
+            services.extend(running.iter().map(|old_srv| (*old_srv).clone()));
+            let step = Prog { services, tests: vec![] };
+            if let Err(errors) = typecheck_prog(&step) {
+                let importers = running
+                    .iter()
+                    .filter(|old_srv| old_srv.imports().contains(&srv.name))
+                    .map(|old_srv| old_srv.name.as_str())
+                    .collect::<Vec<_>>();
+                let msgs = errors.iter().map(|e| e.message.clone()).collect::<Vec<_>>();
+                return Err(format!(
+                    "updating {} breaks {:?} running old code, nothing updated\n{}",
+                    srv.name,
+                    importers,
+                    msgs.join("\n")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_srv(&mut self, srv: &Service) -> Result<(), String> {
+        let reply = self.services[&srv.name]
+            .ask(CmdMsg::CodeUpdate {
+                srv: srv.clone(),
+                imports: HashMap::new(), // a running service keeps its imports
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(CmdMsg::CodeUpdateFailed { error, .. }) = reply {
+            return Err(format!("update of {} refused: {}", srv.name, error));
+        }
+
+        loop {
+            match self.dev_rx.recv().await.ok_or("manager is gone")? {
+                CmdMsg::CodeUpdateGranted { srv_name } if srv_name == srv.name => return Ok(()),
+                CmdMsg::CodeUpdateFailed { srv_name, error } if srv_name == srv.name => {
+                    return Err(format!("update of {} failed: {}", srv.name, error))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// run commands of a @test block, as `runtime::run_test` does
+    async fn run_test(&mut self, idx: usize, test: &Test) -> Result<TestReport, String> {
+        let current = std::mem::replace(&mut self.current, test.name.clone());
+        println!("testing {}", test.name);
+        let mut report = TestReport::new(idx, test.name.clone());
+        self.retries = 0;
+        for cmd in test.commands.iter() {
+            match cmd {
+                ReplCmd::Do(action) => {
+                    let start = tokio::time::Instant::now();
+                    let (outcome, reason) = match self.do_action(action.clone()).await {
+                        Ok(DoOutcome::Committed(_)) => continue,
+                        Ok(DoOutcome::Rejected(reason)) => {
+                            println!("reject do {}: {}", action, reason);
+                            (AssertOutcome::Failed, format!("rejected: {}", reason))
+                        }
+                        Ok(DoOutcome::TimedOut) => {
+                            println!("timeout do {}", action);
+                            let reason = "not committed within the timeout".to_string();
+                            (AssertOutcome::TimedOut, reason)
+                        }
+                        Err(e) => {
+                            println!("fail do {}: {}", action, e);
+                            (AssertOutcome::Failed, e)
+                        }
+                    };
+                    report.record(AssertRecord {
+                        expr: format!("do {}", action),
+                        outcome,
+                        reason: Some(reason),
+                        duration: start.elapsed(),
+                        retries: std::mem::take(&mut self.retries),
+                    });
+                }
+                ReplCmd::Assert(expr) => match self.assert(expr.clone()).await {
+                    Ok((outcome, reason, duration)) => {
+                        println!("{} test {}", outcome, expr);
+                        report.record(AssertRecord {
+                            expr: expr.to_string(),
+                            outcome,
+                            reason,
+                            duration,
+                            retries: std::mem::take(&mut self.retries),
+                        });
+                    }
+                    Err(e) => {
+                        self.current = current;
+                        return Err(e);
+                    }
+                },
+            }
+        }
+        println!("testing {} finished", test.name);
+        self.current = current;
+        Ok(report)
+    }
+
+    /// parse a `do` or an assert on the current service, typechecked as a
+    /// command of a test of it
+    fn parse_cmd(&self, input: &str) -> Result<ReplCmd, String> {
+        let mut cmd = parse_repl_cmd(input, &Scope::of_srv(&self.prog, &self.current))?;
+        let span = Span::new(0, input.len());
+        let test = Test {
+            name: self.current.clone(),
+            commands: vec![cmd.clone()],
+            span,
+            cmd_spans: vec![span],
+        };
+        let prog = Prog {
+            services: self.prog.services.clone(),
+            tests: vec![test],
+        };
+        typecheck_prog(&prog).map_err(|errors| type_errors(input, &errors))?;
+        cmd.erase_spans();
+        Ok(cmd)
+    }
+
+    /// parse `<expr>` by parsing it as an assert, it can have any type
+    fn parse_expr(&self, input: &str) -> Result<Expr, String> {
+        let input = format!("assert({});", input);
+        let scope = Scope::of_srv(&self.prog, &self.current);
+        let ReplCmd::Assert(mut expr) = parse_repl_cmd(&input, &scope)? else {
+            unreachable!()
+        };
+        let span = Span::new(0, input.len());
+        typecheck_repl_expr(&self.prog, &self.current, &expr, span)
+            .map_err(|errors| type_errors(&input, &errors))?;
+        expr.erase_spans();
+        Ok(expr)
+    }
+
+    fn manager(&self) -> &ActorRef<Manager> {
+        &self.services[&self.current]
+    }
+
+    fn next_test_id(&mut self) -> TestId {
+        self.test_id += 1;
+        (REPL_TEST_IDX, self.test_id)
+    }
+
+    /// run action until committed, retrying aborted transactions, each
+    /// attempt within the timeout
+    async fn do_action(&mut self, action: Expr) -> Result<DoOutcome, String> {
+        let mut txn_id = TxnId::new();
+        loop {
+            let deadline = tokio::time::Instant::now() + self.timeout;
+            self.manager()
+                .tell(CmdMsg::DoAction {
+                    from_client_addr: self.cli_tx.clone().into(),
+                    txn_id: txn_id.clone(),
+                    action: action.clone(),
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+
+            loop {
+                let Ok(msg) = tokio::time::timeout_at(deadline, self.cli_rx.recv()).await else {
+                    return Ok(DoOutcome::TimedOut);
+                };
+                match msg.ok_or("manager is gone")? {
+                    CmdMsg::TransactionCommitted { txn_id: id, mut writes } if id == txn_id => {
+                        writes.sort();
+                        return Ok(DoOutcome::Committed(writes));
+                    }
+                    CmdMsg::TransactionAborted { txn_id: id } if id == txn_id => {
+                        self.retries += 1;
+                        txn_id = id.retry_id();
+                        break;
+                    }
+                    CmdMsg::TransactionRejected { txn_id: id, reason } if id == txn_id => {
+                        return Ok(DoOutcome::Rejected(reason));
+                    }
+                    _ => {} // reply to an earlier transaction
+                }
+            }
+        }
+    }
+
+    /// outcome of assert, the error evaluating it if any, and the time taken
+    async fn assert(
+        &mut self,
+        expr: Expr,
+    ) -> Result<(AssertOutcome, Option<String>, Duration), String> {
+        let test_id = self.next_test_id();
+        let start = tokio::time::Instant::now();
+        let deadline = start + self.timeout;
+        self.manager()
+            .tell(CmdMsg::TryAssert {
+                name: self.current.clone(),
+                test: expr,
+                test_id,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        loop {
+            let Ok(msg) = tokio::time::timeout_at(deadline, self.dev_rx.recv()).await else {
+                return Ok((AssertOutcome::TimedOut, None, start.elapsed()));
+            };
+            match msg.ok_or("manager is gone")? {
+                CmdMsg::AssertCompleted { test_id: id, result } if id == test_id => {
+                    if let Err(e) = &result {
+                        println!("error: {}", e);
+                    }
+                    let outcome = (*result.as_ref().unwrap_or(&false)).into();
+                    return Ok((outcome, result.err(), start.elapsed()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn read(&mut self, expr: Expr) -> Result<Expr, String> {
+        let test_id = self.next_test_id();
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        self.manager()
+            .tell(CmdMsg::TryRead {
+                name: self.current.clone(),
+                expr,
+                test_id,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        loop {
+            let Ok(msg) = tokio::time::timeout_at(deadline, self.dev_rx.recv()).await else {
+                return Err("read timed out".to_string());
+            };
+            match msg.ok_or("manager is gone")? {
+                CmdMsg::ReadCompleted { test_id: id, value } if id == test_id => return value,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// type errors of input at the prompt, rendered as by `:update`
+fn type_errors(input: &str, errors: &[TypeError]) -> String {
+    let diags = errors
+        .iter()
+        .map(|e| e.to_diagnostic("<input>", input).to_string())
+        .collect::<Vec<_>>();
+    format!("{} does not typecheck\n{}", input, diags.join("\n"))
+}
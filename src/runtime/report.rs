@@ -0,0 +1,231 @@
+//! outcome of asserts and summary of @test blocks
+//!
+//! besides the summary printed after tests, reports can be written as
+//! - JUnit XML: a `<testsuite>` per @test block, a `<testcase>` per assert,
+//!   failed asserts carry `<failure>`, timed out ones `<error type="timeout">`,
+//!   with the reason as message
+//! - JSON: `{"passed": n, "failed": n, "timed_out": n, "asserts": [..]}`,
+//!   each assert as `{"test": <service>, "test_idx": <n>, "expr": <expr>,
+//!   "status": "pass"|"fail"|"timeout", "reason": <reason>|null,
+//!   "duration_ms": <ms>, "retries": <n>}`
+//!
+//! a `do` that is rejected or not committed in time is recorded as an assert
+//! `do <action>` that failed or timed out, with the reason why
+use std::fmt::Display;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::runtime::progress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssertOutcome {
+    Passed,
+    Failed,
+    TimedOut, // required transactions not applied within the timeout
+}
+
+impl Display for AssertOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssertOutcome::Passed => write!(f, "pass"),
+            AssertOutcome::Failed => write!(f, "fail"),
+            AssertOutcome::TimedOut => write!(f, "timeout"),
+        }
+    }
+}
+
+impl From<bool> for AssertOutcome {
+    fn from(result: bool) -> Self {
+        if result {
+            AssertOutcome::Passed
+        } else {
+            AssertOutcome::Failed
+        }
+    }
+}
+
+/// format reports are written in, besides the printed summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text, // summary only
+    Junit,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssertRecord {
+    pub expr: String,
+    pub outcome: AssertOutcome,
+    // why it did not pass, if more than a false or late assert: the error
+    // evaluating it, or why its `do` was not committed
+    pub reason: Option<String>,
+    pub duration: Duration, // from sending the assert to its outcome
+    pub retries: u32,       // of transactions done since the previous assert
+}
+
+/// asserts of one @test block
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    pub idx: usize,   // index of the @test block in the program
+    pub name: String, // service tested
+    pub asserts: Vec<AssertRecord>,
+}
+
+impl TestReport {
+    pub fn new(idx: usize, name: String) -> Self {
+        TestReport {
+            idx,
+            name,
+            asserts: vec![],
+        }
+    }
+
+    pub fn record(&mut self, record: AssertRecord) {
+        self.asserts.push(record);
+    }
+
+    pub fn count(&self, outcome: AssertOutcome) -> usize {
+        self.asserts
+            .iter()
+            .filter(|record| record.outcome == outcome)
+            .count()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.asserts.iter().map(|record| record.duration).sum()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.asserts
+            .iter()
+            .all(|record| record.outcome == AssertOutcome::Passed)
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} timed out",
+            self.count(AssertOutcome::Passed),
+            self.count(AssertOutcome::Failed),
+            self.count(AssertOutcome::TimedOut)
+        )
+    }
+}
+
+/// print outcome of each @test block and their total,
+/// return whether all asserts passed
+pub fn print_summary(reports: &[TestReport]) -> bool {
+    progress!("test summary:");
+    for report in reports.iter() {
+        progress!("  @test({}) #{}: {}", report.name, report.idx, report);
+    }
+    let total = total(reports);
+    progress!("total: {}", total);
+    total.is_success()
+}
+
+/// all asserts in one report
+fn total(reports: &[TestReport]) -> TestReport {
+    TestReport {
+        asserts: reports
+            .iter()
+            .flat_map(|report| report.asserts.iter().cloned())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+pub fn to_json(reports: &[TestReport]) -> String {
+    let asserts = reports
+        .iter()
+        .flat_map(|report| {
+            report.asserts.iter().map(|record| {
+                json!({
+                    "test": report.name,
+                    "test_idx": report.idx,
+                    "expr": record.expr,
+                    "status": record.outcome.to_string(),
+                    "reason": record.reason,
+                    "duration_ms": record.duration.as_secs_f64() * 1000.0,
+                    "retries": record.retries,
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let total = total(reports);
+    let report = json!({
+        "passed": total.count(AssertOutcome::Passed),
+        "failed": total.count(AssertOutcome::Failed),
+        "timed_out": total.count(AssertOutcome::TimedOut),
+        "asserts": asserts,
+    });
+    let json = serde_json::to_string_pretty(&report).expect("json value should serialize");
+    json + "\n"
+}
+
+pub fn to_junit(reports: &[TestReport]) -> String {
+    let total = total(reports);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        total.asserts.len(),
+        total.count(AssertOutcome::Failed),
+        total.count(AssertOutcome::TimedOut),
+        total.duration().as_secs_f64()
+    );
+
+    for report in reports.iter() {
+        xml += &format!(
+            "  <testsuite name=\"@test({}) #{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&report.name),
+            report.idx,
+            report.asserts.len(),
+            report.count(AssertOutcome::Failed),
+            report.count(AssertOutcome::TimedOut),
+            report.duration().as_secs_f64()
+        );
+        for record in report.asserts.iter() {
+            xml += &format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n",
+                escape_xml(&report.name),
+                escape_xml(&record.expr),
+                record.duration.as_secs_f64()
+            );
+            xml += &format!(
+                "      <properties><property name=\"retries\" value=\"{}\"/></properties>\n",
+                record.retries
+            );
+            match record.outcome {
+                AssertOutcome::Passed => {}
+                AssertOutcome::Failed => {
+                    let reason = record.reason.as_deref().unwrap_or("assert evaluated to false");
+                    xml += &format!("      <failure message=\"{}\"/>\n", escape_xml(reason));
+                }
+                AssertOutcome::TimedOut => {
+                    let reason = record.reason.as_deref().unwrap_or("assert timed out");
+                    xml += &format!(
+                        "      <error type=\"timeout\" message=\"{}\"/>\n",
+                        escape_xml(reason)
+                    );
+                }
+            }
+            xml += "    </testcase>\n";
+        }
+        xml += "  </testsuite>\n";
+    }
+    xml += "</testsuites>\n";
+    xml
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -28,10 +28,16 @@ impl kameo::prelude::Message<Msg> for TableActor {
                 }
             }
 
+            Msg::Unsubscribe { from_addr, .. } => {
+                info!("Unsubscribe from {:?}", from_addr);
+                self.pubsub.unsubscribe(&from_addr);
+                Msg::Unit
+            }
+
             Msg::UserReadTableRequest {
                 from_mgr_addr, txn, table_name, ..
             } => {
-                // we assume tables are insert only
+                // table writes are not locked by txns
                 // thus no causal consistency is guaranteed
                 // therefore no bookkeeping of last applied txn is needed or returned
                 let _ = from_mgr_addr.tell(Msg::UserReadTableResult {
@@ -44,7 +50,7 @@ impl kameo::prelude::Message<Msg> for TableActor {
             },
 
             Msg::UserWriteTableRequest { from_mgr_addr, txn} => {
-                info!("Table Actor {} inserting row {:?}", self.name, txn.inserts);
+                info!("Table Actor {} applying writes of {:?}", self.name, txn.id);
 
                 from_mgr_addr
                     .tell(Msg::UserWriteTableFinish { txn: txn.id.clone(), name: self.name.clone() }).await
@@ -53,18 +59,39 @@ impl kameo::prelude::Message<Msg> for TableActor {
 
                 self.latest_write_txn = Some(txn.clone());
 
-                for insert in &txn.inserts {
-                    self.value.update(insert); 
+                // only the writes to this table, inserts before updates and deletes
+                let inserts = txn.inserts.iter().filter(|insert| insert.table_name == self.name);
+                let edits = txn.edits.iter().filter(|edit| *edit.table_name() == self.name).collect::<Vec<_>>();
+                for insert in inserts {
+                    self.value.update(insert);
+                    if edits.is_empty() {
+                        self.pubsub
+                            .publish(Msg::PropChange {
+                                from_name: self.name.clone(),
+                                val: insert.row.clone(), // only send new record
+                                preds: HashSet::from([txn.clone()]), // the only pred is reflexively itself
+                            })
+                            .await;
+                        info!("Prop change message sent to subscribers");
+                    }
+                }
+
+                if !edits.is_empty() {
+                    for edit in edits {
+                        if let Err(e) = self.value.edit(edit) {
+                            info!("Table Actor {} failed to apply {}: {}", self.name, edit, e);
+                        }
+                    }
+                    // rows are changed in place, so the whole table is sent
                     self.pubsub
-                    .publish(Msg::PropChange {
-                        from_name: self.name.clone(), 
-                        val: insert.row.clone(), // only send new record
-                        preds: HashSet::from([txn.clone()]), // the only pred is reflexively itself
-                    })
-                    .await;
-                info!("Prop change message sent to subscribers");
+                        .publish(Msg::PropChange {
+                            from_name: self.name.clone(),
+                            val: self.value.clone().into(),
+                            preds: HashSet::from([txn.clone()]),
+                        })
+                        .await;
                 }
-    
+
                 Msg::Unit
             }
 
@@ -1,6 +1,7 @@
 use core::panic;
 
-use crate::ast::{ Expr, Insert};
+use crate::ast::{ Expr, Insert, RowEdit};
+use crate::runtime::evaluator::edit_table;
 
 #[derive(Debug, Clone)]
 pub enum TableValueState {
@@ -21,6 +22,12 @@ impl TableValueState {
             panic!("Not a table");
         };
     }
+
+    /// update or delete the rows matching the edit's where clause
+    pub fn edit(&mut self, edit: &RowEdit) -> Result<(), String> {
+        let TableValueState::Val(table) = self;
+        edit_table(table, edit)
+    }
 }
 
 impl Into<Expr> for TableValueState {
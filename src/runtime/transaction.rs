@@ -1,20 +1,43 @@
+use std::cmp;
 use std::hash::{Hash, Hasher};
-use tokio::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::ast::{Assn, Insert, Expr};
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, Ord, Clone, Debug, Hash)]
+use crate::ast::{Assn, Insert, Expr, RowEdit};
+
+/// latest txn time issued by this process
+static LATEST_TIME: AtomicU64 = AtomicU64::new(0);
+/// random id of this process, distinct from other processes issuing txns
+static PROCESS_ID: LazyLock<u64> = LazyLock::new(|| uuid::Uuid::new_v4().as_u64_pair().0);
+
+#[derive(PartialEq, Eq, Clone, Debug, Hash, Serialize, Deserialize)]
 pub struct TxnId {
-    pub time: Instant,
-    // TODO: add address or uuid to break ties
+    // nanoseconds since unix epoch, comparable between processes of a machine
+    pub time: u64,
     pub iteration: u32,
+    // process issuing the txn, breaks ties of txns of different processes
+    pub process: u64,
 }
 
 impl TxnId {
     pub fn new() -> TxnId {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_nanos() as u64;
+        // strictly increasing within a process, so txns never share an id
+        let next = |latest: u64| now.max(latest + 1);
+        let latest = LATEST_TIME
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |latest| Some(next(latest)))
+            .unwrap();
+
         TxnId {
-            time: Instant::now(),
+            time: next(latest),
             iteration: 0,
+            process: *PROCESS_ID,
         }
     }
 
@@ -22,19 +45,25 @@ impl TxnId {
         TxnId {
             time: self.time,
             iteration: self.iteration + 1,
+            process: self.process,
         }
     }
 }
 
+impl Ord for TxnId {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.time
+            .cmp(&other.time)
+            // NOTE: the order is flipped here, because we want higher iterations to have higher
+            // priority, which means they must compare as Ordering::Less, opposite of usual ordering.
+            .then(other.iteration.cmp(&self.iteration))
+            .then(self.process.cmp(&other.process))
+    }
+}
+
 impl PartialOrd for TxnId {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(
-            self.time
-                .cmp(&other.time)
-                // NOTE: the order is flipped here, because we want higher iterations to have higher
-                // priority, which means they must compare as Ordering::Less, opposite of usual ordering.
-                .then(other.iteration.cmp(&self.iteration)),
-        )
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -45,7 +74,7 @@ pub struct WriteToName {
     pub expr: Expr,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TxnPred {
     pub id: TxnId,
     pub writes: Vec<String>,
@@ -54,11 +83,12 @@ pub struct TxnPred {
 // (txid, writes)
 // writes := a list of updates to state vars
 // Clone, PartialEq, Eq, Hash, Debug
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Txn {
     pub id: TxnId,
     pub assns: Vec<Assn>,
     pub inserts: Vec<Insert>,
+    pub edits: Vec<RowEdit>,
 }
 
 impl PartialEq for Txn {
@@ -75,8 +105,20 @@ impl Hash for Txn {
 }
 
 impl Txn {
-    pub fn new(id: TxnId, assns: Vec<Assn>, inserts: Vec<Insert>) -> Txn {
-        Txn { id, assns, inserts }
+    pub fn new(id: TxnId, assns: Vec<Assn>, inserts: Vec<Insert>, edits: Vec<RowEdit>) -> Txn {
+        Txn { id, assns, inserts, edits }
+    }
+
+    /// tables written by the txn, in the order they are first written
+    pub fn tables(&self) -> Vec<String> {
+        let mut tables = vec![];
+        let names = self.inserts.iter().map(|insert| &insert.table_name);
+        for name in names.chain(self.edits.iter().map(|edit| edit.table_name())) {
+            if !tables.contains(name) {
+                tables.push(name.clone());
+            }
+        }
+        tables
     }
 
     pub fn new_without_id(assns: Vec<Assn>) -> Txn {
@@ -84,6 +126,7 @@ impl Txn {
             id: TxnId::new(),
             assns,
             inserts: Vec::new(),
+            edits: Vec::new(),
         }
     }
 }
@@ -0,0 +1,157 @@
+//! connections between nodes
+//!
+//! frames are encoded by `codec` and prefixed by their length as a big
+//! endian u32, at most `MAX_FRAME_SIZE`.
+//! each connection has a writer task draining a channel of encoded frames,
+//! and a reader task dispatching received frames to this node.
+use std::path::PathBuf;
+
+use log::{info, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::mpsc::{self, Sender};
+
+use super::node::{self, Frame};
+use super::TransportError;
+use crate::codec;
+use crate::runtime::MPSC_CHANNEL_SIZE;
+
+const TCP_SCHEME: &str = "tcp://";
+const UNIX_SCHEME: &str = "unix://";
+/// larger frames are not sent, and close the connection they are read on
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    /// `tcp://host:port`, `unix://path`, or `host:port` for tcp
+    fn parse(addr: &str) -> Result<Endpoint, TransportError> {
+        if let Some(path) = addr.strip_prefix(UNIX_SCHEME) {
+            Ok(Endpoint::Unix(PathBuf::from(path)))
+        } else {
+            let host = addr.strip_prefix(TCP_SCHEME).unwrap_or(addr);
+            if host.is_empty() || !host.contains(':') {
+                return Err(TransportError(format!("invalid node address {}", addr)));
+            }
+            Ok(Endpoint::Tcp(host.to_string()))
+        }
+    }
+}
+
+pub async fn listen(addr: &str) -> Result<String, TransportError> {
+    match Endpoint::parse(addr)? {
+        Endpoint::Tcp(host) => {
+            let listener = TcpListener::bind(&host).await?;
+            let bound = format!("{}{}", TCP_SCHEME, listener.local_addr()?);
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            info!("accept connection from {}", peer);
+                            let _ = stream.set_nodelay(true);
+                            serve(stream);
+                        }
+                        Err(e) => warn!("accept failed: {}", e),
+                    }
+                }
+            });
+            Ok(bound)
+        }
+        Endpoint::Unix(path) => {
+            // a socket file left behind by a previous run
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+            let bound = format!("{}{}", UNIX_SCHEME, path.display());
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            info!("accept connection on {}", path.display());
+                            serve(stream);
+                        }
+                        Err(e) => warn!("accept failed: {}", e),
+                    }
+                }
+            });
+            Ok(bound)
+        }
+    }
+}
+
+pub async fn connect(addr: &str) -> Result<Sender<Vec<u8>>, TransportError> {
+    info!("connect to {}", addr);
+    match Endpoint::parse(addr)? {
+        Endpoint::Tcp(host) => {
+            let stream = TcpStream::connect(&host).await?;
+            stream.set_nodelay(true)?;
+            Ok(serve(stream))
+        }
+        Endpoint::Unix(path) => Ok(serve(UnixStream::connect(&path).await?)),
+    }
+}
+
+pub fn encode_frame(frame: &Frame) -> Result<Vec<u8>, TransportError> {
+    let bytes = codec::to_json_bytes(frame)?;
+    if bytes.len() > MAX_FRAME_SIZE {
+        return Err(TransportError(format!(
+            "frame of {} bytes exceeds max frame size {}",
+            bytes.len(),
+            MAX_FRAME_SIZE
+        )));
+    }
+    Ok(bytes)
+}
+
+/// spawn reader and writer of a connection, return sender of encoded frames
+fn serve<S>(stream: S) -> Sender<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(MPSC_CHANNEL_SIZE);
+
+    tokio::spawn(async move {
+        while let Some(bytes) = rx.recv().await {
+            let len = bytes.len() as u32;
+            if let Err(e) = async {
+                writer.write_u32(len).await?;
+                writer.write_all(&bytes).await?;
+                writer.flush().await
+            }
+            .await
+            {
+                warn!("write frame failed: {}", e);
+                break;
+            }
+        }
+    });
+
+    let conn = tx.clone();
+    tokio::spawn(async move {
+        // until connection closed
+        while let Ok(len) = reader.read_u32().await {
+            if len as usize > MAX_FRAME_SIZE {
+                warn!("frame of {} bytes exceeds max frame size {}", len, MAX_FRAME_SIZE);
+                break;
+            }
+            let mut bytes = vec![0u8; len as usize];
+            if let Err(e) = reader.read_exact(&mut bytes).await {
+                warn!("read frame failed: {}", e);
+                break;
+            }
+            match codec::from_json_bytes::<Frame>(&bytes) {
+                Ok(frame) => node::dispatch(frame, &conn).await,
+                Err(e) => warn!("cannot decode frame: {}", e),
+            }
+        }
+        info!("connection closed");
+        node::closed(&conn);
+    });
+
+    tx
+}
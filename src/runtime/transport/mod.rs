@@ -0,0 +1,385 @@
+//! transport between meerkat nodes
+//!
+//! a node is an OS process hosting some services of a program. actors of
+//! a node are addressed by `Addr`, which is either an in-process `ActorRef`,
+//! or a `RemoteAddr` naming the node (its listening socket) and the actor.
+//!
+//! `Msg` between actors crossing a node boundary are sent as frames over a
+//! TCP or Unix socket, see `conn`, in the JSON encoding of `codec`. A local `Addr` inside a message is
+//! serialized as a `RemoteAddr` of this node, and registered in this node's
+//! actor table so that later frames sent to it can be dispatched, see `node`.
+//! Remote addresses pointing back to this node are resolved to local ones,
+//! so actors of the same node keep talking in process.
+//!
+//! managers of services are registered by service name, which is how an
+//! importing service finds the manager of a remote service.
+//!
+//! clients are not actors, they are addressed by `ClientAddr`, either the
+//! channel a client listens on or a `RemoteAddr` of that channel.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::time::Duration;
+
+use kameo::actor::ActorRef;
+use kameo::Actor;
+use log::info;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::mpsc::Sender;
+
+use crate::codec::CodecError;
+
+use super::{
+    def_actor::DefActor,
+    manager::Manager,
+    message::{CmdMsg, Msg},
+    table_actor::TableActor,
+    var_actor::VarActor,
+};
+
+mod conn;
+mod node;
+
+/// listening address of a node that did not specify one but talks to peers
+pub const DEFAULT_LISTEN_ADDR: &str = "tcp://127.0.0.1:0";
+/// how long to wait for a peer to serve an imported service
+pub const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+const LOOKUP_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+/// how long to wait for the reply to a request sent to a peer
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct TransportError(pub String);
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transport error: {}", self.0)
+    }
+}
+
+impl Error for TransportError {}
+
+impl From<std::io::Error> for TransportError {
+    fn from(e: std::io::Error) -> Self {
+        TransportError(e.to_string())
+    }
+}
+
+impl From<CodecError> for TransportError {
+    fn from(e: CodecError) -> Self {
+        TransportError(e.to_string())
+    }
+}
+
+/// placement of a program's services on this node
+#[derive(Debug, Clone, Default)]
+pub struct NodeConfig {
+    pub listen: Option<String>, // address this node serves its actors on
+    pub services: Vec<String>,  // services hosted by this node, all if empty
+    pub peers: Vec<String>,     // nodes to look up imported services on
+}
+
+impl NodeConfig {
+    pub fn hosts(&self, srv_name: &String) -> bool {
+        self.services.is_empty() || self.services.contains(srv_name)
+    }
+
+    /// a node importing from peers must be reachable by them
+    pub fn listen_addr(&self) -> Option<String> {
+        match &self.listen {
+            Some(addr) => Some(addr.clone()),
+            None if !self.peers.is_empty() => Some(DEFAULT_LISTEN_ADDR.to_string()),
+            None => None,
+        }
+    }
+}
+
+/// actor living on some node
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RemoteAddr {
+    pub node: String, // listening address of the node, empty if not listening
+    pub id: u64,
+}
+
+/// actor of this node, as stored in the node's actor table
+#[derive(Clone)]
+pub enum LocalActor {
+    Manager(ActorRef<Manager>),
+    Var(ActorRef<VarActor>),
+    Def(ActorRef<DefActor>),
+    Table(ActorRef<TableActor>),
+}
+
+impl LocalActor {
+    pub fn id(&self) -> u64 {
+        match self {
+            LocalActor::Manager(a) => a.id().sequence_id(),
+            LocalActor::Var(a) => a.id().sequence_id(),
+            LocalActor::Def(a) => a.id().sequence_id(),
+            LocalActor::Table(a) => a.id().sequence_id(),
+        }
+    }
+
+    pub async fn tell(&self, msg: Msg) -> Result<(), TransportError> {
+        let res = match self {
+            LocalActor::Manager(a) => a.tell(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Var(a) => a.tell(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Def(a) => a.tell(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Table(a) => a.tell(msg).await.map_err(|e| e.to_string()),
+        };
+        res.map_err(TransportError)
+    }
+
+    pub async fn ask(&self, msg: Msg) -> Result<Msg, TransportError> {
+        let res = match self {
+            LocalActor::Manager(a) => a.ask(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Var(a) => a.ask(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Def(a) => a.ask(msg).await.map_err(|e| e.to_string()),
+            LocalActor::Table(a) => a.ask(msg).await.map_err(|e| e.to_string()),
+        };
+        res.map_err(TransportError)
+    }
+}
+
+/// actors that can be addressed from other nodes
+pub trait Addressable: Actor {
+    fn to_local(actor_ref: &ActorRef<Self>) -> LocalActor;
+    fn from_local(actor: &LocalActor) -> Option<ActorRef<Self>>;
+}
+
+impl Addressable for Manager {
+    fn to_local(actor_ref: &ActorRef<Self>) -> LocalActor {
+        LocalActor::Manager(actor_ref.clone())
+    }
+    fn from_local(actor: &LocalActor) -> Option<ActorRef<Self>> {
+        match actor {
+            LocalActor::Manager(a) => Some(a.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Addressable for VarActor {
+    fn to_local(actor_ref: &ActorRef<Self>) -> LocalActor {
+        LocalActor::Var(actor_ref.clone())
+    }
+    fn from_local(actor: &LocalActor) -> Option<ActorRef<Self>> {
+        match actor {
+            LocalActor::Var(a) => Some(a.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Addressable for DefActor {
+    fn to_local(actor_ref: &ActorRef<Self>) -> LocalActor {
+        LocalActor::Def(actor_ref.clone())
+    }
+    fn from_local(actor: &LocalActor) -> Option<ActorRef<Self>> {
+        match actor {
+            LocalActor::Def(a) => Some(a.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Addressable for TableActor {
+    fn to_local(actor_ref: &ActorRef<Self>) -> LocalActor {
+        LocalActor::Table(actor_ref.clone())
+    }
+    fn from_local(actor: &LocalActor) -> Option<ActorRef<Self>> {
+        match actor {
+            LocalActor::Table(a) => Some(a.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// address of an actor, in this process or on another node
+pub enum Addr<A: Actor> {
+    Local(ActorRef<A>),
+    Remote(RemoteAddr),
+}
+
+impl<A: Addressable> Addr<A> {
+    pub async fn tell(&self, msg: Msg) -> Result<(), TransportError> {
+        match self {
+            Addr::Local(actor_ref) => A::to_local(actor_ref).tell(msg).await,
+            Addr::Remote(addr) => node::tell(addr, msg).await,
+        }
+    }
+
+    pub async fn ask(&self, msg: Msg) -> Result<Msg, TransportError> {
+        match self {
+            Addr::Local(actor_ref) => A::to_local(actor_ref).ask(msg).await,
+            Addr::Remote(addr) => node::ask(addr, msg).await,
+        }
+    }
+
+    /// address other nodes can reach this actor at
+    pub fn remote_addr(&self) -> RemoteAddr {
+        match self {
+            Addr::Local(actor_ref) => node::export(A::to_local(actor_ref)),
+            Addr::Remote(addr) => addr.clone(),
+        }
+    }
+}
+
+impl Addr<Manager> {
+    /// send a command from client/developer to the manager
+    pub async fn tell_cmd(&self, msg: CmdMsg) -> Result<(), TransportError> {
+        match self {
+            Addr::Local(actor_ref) => actor_ref
+                .tell(msg)
+                .await
+                .map_err(|e| TransportError(e.to_string())),
+            Addr::Remote(addr) => node::tell_cmd(addr, msg).await,
+        }
+    }
+}
+
+impl<A: Actor> Addr<A> {
+    /// identity of the actor, without exporting it to other nodes
+    pub fn id(&self) -> RemoteAddr {
+        match self {
+            Addr::Local(actor_ref) => RemoteAddr {
+                node: node::local_node(),
+                id: actor_ref.id().sequence_id(),
+            },
+            Addr::Remote(addr) => addr.clone(),
+        }
+    }
+}
+
+impl<A: Actor> From<ActorRef<A>> for Addr<A> {
+    fn from(actor_ref: ActorRef<A>) -> Self {
+        Addr::Local(actor_ref)
+    }
+}
+
+impl<A: Actor> Clone for Addr<A> {
+    fn clone(&self) -> Self {
+        match self {
+            Addr::Local(actor_ref) => Addr::Local(actor_ref.clone()),
+            Addr::Remote(addr) => Addr::Remote(addr.clone()),
+        }
+    }
+}
+
+impl<A: Actor> Debug for Addr<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Addr::Local(actor_ref) => write!(f, "{}", actor_ref.id()),
+            Addr::Remote(addr) => write!(f, "{}#{}", addr.node, addr.id),
+        }
+    }
+}
+
+impl<A: Addressable> Serialize for Addr<A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.remote_addr().serialize(serializer)
+    }
+}
+
+impl<'de, A: Addressable> Deserialize<'de> for Addr<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let addr = RemoteAddr::deserialize(deserializer)?;
+        Ok(match node::resolve(&addr).as_ref().and_then(A::from_local) {
+            Some(actor_ref) => Addr::Local(actor_ref),
+            None => Addr::Remote(addr),
+        })
+    }
+}
+
+/// address of a client, where managers send back results of its commands
+#[derive(Debug, Clone)]
+pub enum ClientAddr {
+    Local(Sender<CmdMsg>),
+    Remote(RemoteAddr),
+}
+
+impl ClientAddr {
+    pub async fn send(&self, msg: CmdMsg) -> Result<(), TransportError> {
+        match self {
+            ClientAddr::Local(sender) => sender
+                .send(msg)
+                .await
+                .map_err(|e| TransportError(e.to_string())),
+            ClientAddr::Remote(addr) => node::tell_client(addr, msg).await,
+        }
+    }
+}
+
+impl From<Sender<CmdMsg>> for ClientAddr {
+    fn from(sender: Sender<CmdMsg>) -> Self {
+        ClientAddr::Local(sender)
+    }
+}
+
+impl Serialize for ClientAddr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ClientAddr::Local(sender) => node::export_client(sender).serialize(serializer),
+            ClientAddr::Remote(addr) => addr.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ClientAddr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let addr = RemoteAddr::deserialize(deserializer)?;
+        Ok(match node::resolve_client(&addr) {
+            Some(sender) => ClientAddr::Local(sender),
+            None => ClientAddr::Remote(addr),
+        })
+    }
+}
+
+/// serve this node's actors on `addr`, e.g. `tcp://127.0.0.1:7000` or
+/// `unix:///tmp/meerkat.sock`, return the address actually bound
+pub async fn listen(addr: &str) -> Result<String, TransportError> {
+    node::listen(addr).await
+}
+
+/// make `actor_ref` reachable from other nodes by `name`
+pub fn register<A: Addressable>(name: &str, actor_ref: &ActorRef<A>) {
+    node::register(name, A::to_local(actor_ref));
+}
+
+/// find the actor registered by `name` on node `peer`
+pub async fn lookup<A: Addressable>(
+    peer: &str,
+    name: &str,
+) -> Result<Option<Addr<A>>, TransportError> {
+    Ok(node::lookup(peer, name).await?.map(Addr::Remote))
+}
+
+/// find the manager of service `srv_name` on any of `peers`, waiting for
+/// peers that are still starting up
+pub async fn lookup_service(
+    peers: &Vec<String>,
+    srv_name: &String,
+) -> Result<Addr<Manager>, TransportError> {
+    let deadline = tokio::time::Instant::now() + LOOKUP_TIMEOUT;
+    let mut errors = HashMap::new();
+    while tokio::time::Instant::now() < deadline {
+        for peer in peers.iter() {
+            match lookup::<Manager>(peer, srv_name).await {
+                Ok(Some(mgr)) => {
+                    info!("found service {} at {:?}", srv_name, mgr);
+                    return Ok(mgr);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    errors.insert(peer.clone(), e.0);
+                }
+            }
+        }
+        tokio::time::sleep(LOOKUP_RETRY_INTERVAL).await;
+    }
+
+    Err(TransportError(format!(
+        "service {} not found on peers {:?} {:?}",
+        srv_name, peers, errors
+    )))
+}
@@ -0,0 +1,293 @@
+//! state of this node shared by all actors of the process
+//!
+//! - actor table: actors whose address has been sent to other nodes
+//! - client table: same for channels of clients, numbered separately
+//! - name table: well-known actors, e.g. managers by service name
+//! - peers: outgoing connections by node address
+//! - pending: requests waiting for a reply frame, failed when the connection
+//!   they were sent on closes
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, OnceLock};
+
+use dashmap::DashMap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::Sender, oneshot, Mutex};
+
+use super::{conn, LocalActor, RemoteAddr, TransportError, REQUEST_TIMEOUT};
+use crate::runtime::message::{CmdMsg, Msg};
+
+/// unit of communication between two nodes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Frame {
+    Tell { to: u64, msg: Msg },
+    Cmd { to: u64, msg: CmdMsg },       // to a manager
+    ClientCmd { to: u64, msg: CmdMsg }, // to a client
+    Ask { req: u64, to: u64, msg: Msg },
+    Reply { req: u64, msg: Result<Msg, String> },
+    Lookup { req: u64, name: String },
+    Found { req: u64, id: Option<u64> },
+}
+
+struct Pending {
+    conn: Sender<Vec<u8>>, // connection the request was sent on
+    tx: oneshot::Sender<Frame>,
+}
+
+struct Node {
+    addr: OnceLock<String>,
+    actors: DashMap<u64, LocalActor>,
+    names: DashMap<String, u64>,
+    clients: DashMap<u64, Sender<CmdMsg>>,
+    next_client: AtomicU64,
+    peers: Mutex<HashMap<String, Sender<Vec<u8>>>>,
+    pending: DashMap<u64, Pending>,
+    next_req: AtomicU64,
+}
+
+static NODE: LazyLock<Node> = LazyLock::new(|| Node {
+    addr: OnceLock::new(),
+    actors: DashMap::new(),
+    names: DashMap::new(),
+    clients: DashMap::new(),
+    next_client: AtomicU64::new(0),
+    peers: Mutex::new(HashMap::new()),
+    pending: DashMap::new(),
+    next_req: AtomicU64::new(0),
+});
+
+pub fn local_node() -> String {
+    NODE.addr.get().cloned().unwrap_or_default()
+}
+
+pub async fn listen(addr: &str) -> Result<String, TransportError> {
+    let bound = conn::listen(addr).await?;
+    NODE.addr
+        .set(bound.clone())
+        .map_err(|_| TransportError(format!("node already listening on {}", local_node())))?;
+    info!("node listening on {}", bound);
+    Ok(bound)
+}
+
+pub fn export(actor: LocalActor) -> RemoteAddr {
+    let id = actor.id();
+    NODE.actors.entry(id).or_insert(actor);
+    RemoteAddr {
+        node: local_node(),
+        id,
+    }
+}
+
+pub fn resolve(addr: &RemoteAddr) -> Option<LocalActor> {
+    if addr.node != local_node() {
+        return None;
+    }
+    NODE.actors.get(&addr.id).map(|actor| actor.clone())
+}
+
+pub fn export_client(sender: &Sender<CmdMsg>) -> RemoteAddr {
+    let exported = NODE
+        .clients
+        .iter()
+        .find(|client| client.value().same_channel(sender))
+        .map(|client| *client.key());
+    let id = exported.unwrap_or_else(|| {
+        let id = NODE.next_client.fetch_add(1, Ordering::SeqCst);
+        NODE.clients.insert(id, sender.clone());
+        id
+    });
+    RemoteAddr {
+        node: local_node(),
+        id,
+    }
+}
+
+pub fn resolve_client(addr: &RemoteAddr) -> Option<Sender<CmdMsg>> {
+    if addr.node != local_node() {
+        return None;
+    }
+    NODE.clients.get(&addr.id).map(|sender| sender.clone())
+}
+
+pub fn register(name: &str, actor: LocalActor) {
+    let addr = export(actor);
+    NODE.names.insert(name.to_string(), addr.id);
+}
+
+/// connection to `node`, connecting if there is none open
+async fn connection(node: &str) -> Result<Sender<Vec<u8>>, TransportError> {
+    // hold the lock while connecting, so a peer gets a single connection
+    let mut peers = NODE.peers.lock().await;
+    match peers.get(node) {
+        Some(conn) if !conn.is_closed() => Ok(conn.clone()),
+        _ => {
+            let conn = conn::connect(node).await?;
+            peers.insert(node.to_string(), conn.clone());
+            Ok(conn)
+        }
+    }
+}
+
+async fn send_on(conn: &Sender<Vec<u8>>, node: &str, frame: &Frame) -> Result<(), TransportError> {
+    let bytes = conn::encode_frame(frame)?;
+    conn.send(bytes)
+        .await
+        .map_err(|_| TransportError(format!("connection to {} closed", node)))
+}
+
+async fn send(node: &str, frame: &Frame) -> Result<(), TransportError> {
+    let conn = connection(node).await?;
+    send_on(&conn, node, frame).await
+}
+
+/// send a request frame and wait for its reply frame
+async fn request(node: &str, frame: impl FnOnce(u64) -> Frame) -> Result<Frame, TransportError> {
+    let conn = connection(node).await?;
+    let req = NODE.next_req.fetch_add(1, Ordering::SeqCst);
+    let (tx, rx) = oneshot::channel();
+    NODE.pending.insert(
+        req,
+        Pending {
+            conn: conn.clone(),
+            tx,
+        },
+    );
+
+    if let Err(e) = send_on(&conn, node, &frame(req)).await {
+        NODE.pending.remove(&req);
+        return Err(e);
+    }
+    match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+        Ok(Ok(reply)) => Ok(reply),
+        // dropped by `closed`
+        Ok(Err(_)) => Err(TransportError(format!(
+            "connection to {} closed before reply",
+            node
+        ))),
+        Err(_) => {
+            NODE.pending.remove(&req);
+            Err(TransportError(format!(
+                "no reply from {} in {:?}",
+                node, REQUEST_TIMEOUT
+            )))
+        }
+    }
+}
+
+/// fail requests waiting for a reply on a connection that closed
+pub fn closed(conn: &Sender<Vec<u8>>) {
+    NODE.pending.retain(|_, pending| !pending.conn.same_channel(conn));
+}
+
+pub async fn tell(addr: &RemoteAddr, msg: Msg) -> Result<(), TransportError> {
+    send(&addr.node, &Frame::Tell { to: addr.id, msg }).await
+}
+
+pub async fn tell_cmd(addr: &RemoteAddr, msg: CmdMsg) -> Result<(), TransportError> {
+    send(&addr.node, &Frame::Cmd { to: addr.id, msg }).await
+}
+
+pub async fn tell_client(addr: &RemoteAddr, msg: CmdMsg) -> Result<(), TransportError> {
+    send(&addr.node, &Frame::ClientCmd { to: addr.id, msg }).await
+}
+
+pub async fn ask(addr: &RemoteAddr, msg: Msg) -> Result<Msg, TransportError> {
+    let reply = request(&addr.node, |req| Frame::Ask {
+        req,
+        to: addr.id,
+        msg,
+    })
+    .await?;
+    match reply {
+        Frame::Reply { msg, .. } => msg.map_err(TransportError),
+        _ => Err(TransportError(format!("unexpected reply {:?}", reply))),
+    }
+}
+
+pub async fn lookup(node: &str, name: &str) -> Result<Option<RemoteAddr>, TransportError> {
+    let reply = request(node, |req| Frame::Lookup {
+        req,
+        name: name.to_string(),
+    })
+    .await?;
+    match reply {
+        Frame::Found { id, .. } => Ok(id.map(|id| RemoteAddr {
+            node: node.to_string(),
+            id,
+        })),
+        _ => Err(TransportError(format!("unexpected reply {:?}", reply))),
+    }
+}
+
+/// handle a frame received on a connection, replies go back on `conn`
+pub async fn dispatch(frame: Frame, conn: &Sender<Vec<u8>>) {
+    match frame {
+        Frame::Tell { to, msg } => match NODE.actors.get(&to).map(|a| a.clone()) {
+            // told in receiving order, to keep order of messages between actors
+            Some(actor) => {
+                if let Err(e) = actor.tell(msg).await {
+                    warn!("tell to actor {} failed: {}", to, e);
+                }
+            }
+            None => warn!("tell to unknown actor {}: {:?}", to, msg),
+        },
+
+        Frame::Cmd { to, msg } => match NODE.actors.get(&to).map(|a| a.clone()) {
+            Some(LocalActor::Manager(mgr)) => {
+                if let Err(e) = mgr.tell(msg).await {
+                    warn!("command to manager {} failed: {}", to, e);
+                }
+            }
+            _ => warn!("command to unknown manager {}: {:?}", to, msg),
+        },
+
+        Frame::ClientCmd { to, msg } => match NODE.clients.get(&to).map(|c| c.clone()) {
+            Some(client) => {
+                if client.send(msg).await.is_err() {
+                    warn!("client {} is gone", to);
+                }
+            }
+            None => warn!("command to unknown client {}: {:?}", to, msg),
+        },
+
+        Frame::Ask { req, to, msg } => {
+            let actor = NODE.actors.get(&to).map(|a| a.clone());
+            let conn = conn.clone();
+            // the asked actor may itself wait on this connection
+            tokio::spawn(async move {
+                let msg = match actor {
+                    Some(actor) => actor.ask(msg).await.map_err(|e| e.0),
+                    None => Err(format!("ask to unknown actor {}", to)),
+                };
+                reply(&conn, Frame::Reply { req, msg }).await;
+            });
+        }
+
+        Frame::Lookup { req, name } => {
+            let id = NODE.names.get(&name).map(|id| *id);
+            reply(conn, Frame::Found { req, id }).await;
+        }
+
+        Frame::Reply { req, .. } | Frame::Found { req, .. } => {
+            match NODE.pending.remove(&req) {
+                Some((_, pending)) => {
+                    let _ = pending.tx.send(frame);
+                }
+                None => warn!("reply to unknown request {}", req),
+            }
+        }
+    }
+}
+
+async fn reply(conn: &Sender<Vec<u8>>, frame: Frame) {
+    match conn::encode_frame(&frame) {
+        Ok(bytes) => {
+            if conn.send(bytes).await.is_err() {
+                warn!("connection closed before reply {:?}", frame);
+            }
+        }
+        Err(e) => warn!("cannot encode reply {:?}: {}", frame, e),
+    }
+}
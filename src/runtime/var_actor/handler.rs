@@ -40,6 +40,12 @@ impl kameo::prelude::Message<Msg> for VarActor {
                 }
             }
 
+            Msg::Unsubscribe { from_addr, .. } => {
+                info!("Unsubscribe from {:?}", from_addr);
+                self.pubsub.unsubscribe(&from_addr);
+                Msg::Unit
+            }
+
             Msg::LockRequest {
                 lock,
                 from_mgr_addr: from_name,
@@ -218,7 +224,7 @@ impl VarActor {
         // if can grant new waiting lock
         if let Some((lock, mgr)) = self.lock_state.grant_oldest_wait() {
             assert!(self.lock_state.has_granted(&lock.txn_id));
-            info!("{:?} grant {:?} to manager {}", self.name, lock, mgr.id());
+            info!("{:?} grant {:?} to manager {:?}", self.name, lock, mgr);
 
             let msg = Msg::LockGranted {
                 from_name: self.name.clone(),
@@ -10,16 +10,26 @@ mod tc_stmt;
 mod tc_test;
 mod utils;
 
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
-use crate::ast::{Prog, DataType, Field};
+use crate::ast::{Decl, Expr, Prog, DataType, Field, Span, Variant};
+use crate::error::Diagnostic;
+use log::info;
+use crate::static_analysis::var_analysis::{calc_srv_order, cycles::import_cycles};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     Int,
+    Float,
     Bool,
     String,
-    Vector(Vec<Type>),
+    Vector(Vec<Type>),            // of a tuple, e.g. {int, string}
+    Record(Vec<(String, Type)>),  // fields sorted by name, e.g. {age: int, name: string}
+    List(Box<Type>),              // e.g. list<int>
+    Adt(String),                  // declared by `type status = Pending | Done(int)`
     Unit,
     Action,
 
@@ -32,86 +42,276 @@ pub enum Type {
 
 /// Type Scheme represents polymorphic types,
 /// e.g. \forall a, b, c in (a * b) -> c
-// pub struct TypeScheme {
-//     args: Vec<Type>,
-//     body: Type,
-// }
+/// defs are bound to schemes generalized at their declaration, and
+/// instantiated with fresh type vars where they are used, vars to schemes
+/// without quantified type vars
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeScheme {
+    pub vars: Vec<String>, // quantified type vars
+    pub typ: Type,
+}
+
+impl TypeScheme {
+    /// scheme without quantified type vars
+    pub fn mono(typ: Type) -> Self {
+        TypeScheme { vars: vec![], typ }
+    }
 
-// impl TypeScheme {
-//     fn free_var(&self) -> HashSet<String> {
-//         todo!()
-//     }
-// }
+    /// type vars of the scheme not quantified by it
+    pub fn free_var(&self) -> HashSet<String> {
+        let mut free_vars = self.typ.free_var();
+        for var in self.vars.iter() {
+            free_vars.remove(var);
+        }
+        free_vars
+    }
+}
 
 impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
             Type::Bool => write!(f, "bool"),
             Type::String => write!(f, "string"),
-            Type::Vector( .. ) => write!(f, "vector"),
+            Type::Vector(typs) => {
+                let typs = typs.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "{{{}}}", typs.join(", "))
+            }
+            Type::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, typ)| format!("{}: {}", name, typ))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Type::List(typ) => write!(f, "list<{}>", typ),
+            Type::Adt(name) => write!(f, "{}", name),
             Type::Unit => write!(f, "unit"),
             Type::Action => write!(f, "action"),
-            Type::Fun(args, ret) => {
-                let joined = args
-                    .iter()
-                    .map(|t| format!("{}", t))
-                    .collect::<Vec<_>>()
-                    .join(",");
-                if args.len() > 1 {
-                    write!(f, "({})->{}", joined, ret)
-                } else {
-                    write!(f, "{}->{}", joined, ret)
+            // as written in type annotations
+            Type::Fun(args, ret) => match args.as_slice() {
+                [arg @ Type::Fun(..)] => write!(f, "({}) -> {}", arg, ret),
+                [arg] => write!(f, "{} -> {}", arg, ret),
+                _ => {
+                    let joined = args
+                        .iter()
+                        .map(|t| format!("{}", t))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    write!(f, "({}) -> {}", joined, ret)
                 }
-            }
+            },
             Type::TypVar(name) => write!(f, "{}", name),
-            Type::Table( schema) => write!(f, "table {:?}", schema),
+            Type::Table(schema) => {
+                let fields = schema
+                    .iter()
+                    .map(|field| format!("{}: {}", field.name, Type::from(&field.type_)))
+                    .collect::<Vec<_>>();
+                write!(f, "table {{{}}}", fields.join(", "))
+            }
             Type::Row => write!(f, "row")
         }
     }
 }
 
+impl Display for TypeScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.vars.is_empty() {
+            write!(f, "{}", self.typ)
+        } else {
+            write!(f, "forall {}. {}", self.vars.join(" "), self.typ)
+        }
+    }
+}
+
+/// type error, located at the expr it is found in, or at the decl or test
+/// command for an error of a whole decl, e.g. a mismatched annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl TypeError {
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// render against the source the program is parsed from
+    pub fn to_diagnostic(&self, file: &str, source: &str) -> Diagnostic {
+        let mut diag = Diagnostic::new(file, source, self.span, self.message.clone());
+        diag.notes = self.notes.clone();
+        diag
+    }
+}
+
 pub struct TypecheckEnv {
     pub var_context: HashMap<String, Type>, // Expr::Var to type, todo: change this to more efficient stack of hashmap
-    pub name_context: HashMap<String, Type>, // reactive name to type
-    // pub var_to_typ_scheme: HashMap<String, TypeScheme>,
+    pub name_context: HashMap<String, TypeScheme>, // reactive name to type scheme
 
     // counter to generate new type var
     pub typevar_id: u64,
     // Type::var to type (canonical form)
     pub acc_subst: HashMap<String, Type>,
-    
+    // type vars of numeric operands not known to be int or float yet,
+    // see `default_numeric`
+    pub numeric_typevars: Vec<Type>,
+
+    // declared type name -> its variants, also of imported services
+    pub types: HashMap<String, Vec<Variant>>,
+
+    // imported service name -> types of its public names
+    pub srv_to_exports: HashMap<String, HashMap<String, Type>>,
+    // imported service name -> types it declares or imports
+    pub srv_to_types: HashMap<String, HashMap<String, Vec<Variant>>>,
+    // imported service name -> its names not declared `pub`
+    pub srv_to_private_names: HashMap<String, HashSet<String>>,
+
+    // names clients may refer to in `do`: pub defs and imported public names
+    pub pub_names: HashSet<String>,
+    // non-public names of imported services, to the service declaring them
+    pub private_imports: HashMap<String, String>,
+
+    // expr being checked, in the decl or test command being checked,
+    // where type errors are reported
+    pub span: Span,
+    // type errors found so far, checking goes on with the next decl or command
+    pub errors: Vec<TypeError>,
 }
 
 impl Display for TypecheckEnv {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "------------------\n")?;
-        for (var_name, var_typ) in self.var_context.iter() {
-            write!(f, "{}: {}\n", var_name, var_typ)?;
+        let mut names = self.name_context.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let scheme = &self.name_context[name];
+            let typ = self.resolve(&scheme.typ);
+            write!(f, "{}: {}\n", name, TypeScheme { vars: scheme.vars.clone(), typ })?;
         }
         write!(f, "------------------\n")
     }
 }
 
-pub fn typecheck_prog(prog: &Prog) {
+/// check all services and tests, return every type error found,
+/// in the order of their position in the source
+pub fn typecheck_prog(prog: &Prog) -> Result<(), Vec<TypeError>> {
+    let mut srv_to_type_env = typecheck_srvs(prog)?;
+    let mut errors = vec![];
+
+    for test in prog.tests.iter() {
+        let Some(typ_env) = srv_to_type_env.get_mut(&test.name) else {
+            errors.push(TypeError {
+                span: test.span,
+                message: format!("test instantiates a non-existing service {:?}", test.name),
+                notes: vec![],
+            });
+            continue;
+        };
+        typ_env.typecheck_test(test);
+    }
+
+    for typ_env in srv_to_type_env.into_values() {
+        errors.extend(typ_env.errors);
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    errors.sort_by_key(|e| e.span.start);
+    Err(errors)
+}
+
+/// check an expr typed at the repl prompt on service `srv_name`, e.g. by
+/// `:print`, it can have any type; the running services typecheck
+pub fn typecheck_repl_expr(
+    prog: &Prog,
+    srv_name: &String,
+    expr: &Expr,
+    span: Span,
+) -> Result<(), Vec<TypeError>> {
+    let mut srv_to_type_env = typecheck_srvs(prog)?;
+    let Some(typ_env) = srv_to_type_env.get_mut(srv_name) else {
+        return Err(vec![TypeError {
+            span,
+            message: format!("no service {:?}", srv_name),
+            notes: vec![],
+        }]);
+    };
+    typ_env.span = span;
+    typ_env.numeric_typevars.clear();
+    typ_env.infer_expr(expr).map_err(|e| vec![e])?;
+    typ_env.default_numeric().map_err(|e| vec![e])
+}
+
+/// type environment of each service, with the type errors found in it
+fn typecheck_srvs(prog: &Prog) -> Result<HashMap<String, TypecheckEnv>, Vec<TypeError>> {
     // each service has its own type environment
     let mut srv_to_type_env = HashMap::new();
+    let mut srv_to_exports = HashMap::new();
+    let mut srv_to_private_names = HashMap::new();
+    let mut srv_to_types = HashMap::new();
 
-    for srvs in prog.services.iter() {
+    // services can only be ordered once every import resolves
+    let import_errors = check_imports(prog);
+    if !import_errors.is_empty() {
+        return Err(import_errors);
+    }
+
+    // imported services are checked before services importing them
+    let srvs_in_order = calc_srv_order(prog).expect("imports should be checked before ordering");
+    for srvs in srvs_in_order {
         let mut typ_env = TypecheckEnv::new();
+        typ_env.srv_to_exports = srv_to_exports.clone();
+        typ_env.srv_to_private_names = srv_to_private_names.clone();
+        typ_env.srv_to_types = srv_to_types.clone();
         typ_env.typecheck_service(srvs);
-        print!("service: {:?}\n {}", srvs.name, typ_env);
+        info!("service: {:?}\n {}", srvs.name, typ_env);
 
+        srv_to_exports.insert(srvs.name.clone(), typ_env.exports(srvs));
+        srv_to_private_names.insert(srvs.name.clone(), srvs.private_names());
+        srv_to_types.insert(srvs.name.clone(), typ_env.types.clone());
         srv_to_type_env.insert(srvs.name.clone(), typ_env);
     }
+    Ok(srv_to_type_env)
+}
+
+/// imports of non-existing services, and cycles of services importing each other
+fn check_imports(prog: &Prog) -> Vec<TypeError> {
+    let srv_names = prog.services.iter().map(|srv| &srv.name).collect::<HashSet<_>>();
+    let mut errors = vec![];
+    for srv in prog.services.iter() {
+        for decl in srv.decls.iter() {
+            if let Decl::Import { srv_name, span } = decl {
+                if !srv_names.contains(srv_name) {
+                    errors.push(TypeError {
+                        span: *span,
+                        message: format!(
+                            "service {} imports non-existing service {}",
+                            srv.name, srv_name
+                        ),
+                        notes: vec![],
+                    });
+                }
+            }
+        }
+    }
 
-    for test in prog.tests.iter() {
-        srv_to_type_env
-            .get_mut(&test.name)
-            .expect(&format!(
-                "Test: test instantiate a non-existing service {:?}",
-                test.name
-            ))
-            .typecheck_test(test);
+    for cycle in import_cycles(prog) {
+        let notes = cycle
+            .path
+            .windows(2)
+            .skip(1)
+            .map(|pair| format!("{} imports {}", pair[0], pair[1]))
+            .collect();
+        errors.push(TypeError {
+            span: cycle.spans[0],
+            message: format!("import cycle: {}", cycle.path.join(" -> ")),
+            notes,
+        });
     }
+    errors.sort_by_key(|e| e.span.start);
+    errors
 }
@@ -1,90 +1,126 @@
-use core::panic;
+use std::collections::{HashMap, HashSet};
+use std::iter::zip;
 
-use super::{Type, TypecheckEnv};
+use super::{Type, TypeError, TypecheckEnv};
 use crate::ast::*;
 
 impl TypecheckEnv {
-    pub fn infer_expr(&mut self, expr: &Expr) -> Type {
+    pub fn infer_expr(&mut self, expr: &Expr) -> Result<Type, TypeError> {
         use Type::*;
-        match expr {
+        let typ = match expr {
             Expr::Number { val: _ } => Int,
+            Expr::Float { val: _ } => Float,
             Expr::Bool { val: _ } => Bool,
             Expr::String {val: _} => String,
-            Expr::KeyVal { key, value } => self.infer_expr(&value),
+            Expr::KeyVal { key, value } => {
+                return Err(self
+                    .error(format!("field {} is not in a record", expr))
+                    .with_note(format!("records are written as `{{{}, ..}}`", expr)));
+            }
+            // a record if its entries are named, e.g. {name: "a", age: 3},
+            // otherwise a tuple, e.g. {"a", 3}
+            Expr::Vector { val } if val.iter().any(|el| matches!(el.unspanned(), Expr::KeyVal { .. })) => {
+                let mut fields: Vec<(std::string::String, Type)> = vec![];
+                for el in val {
+                    let Expr::KeyVal { key, value } = el.unspanned() else {
+                        return Err(self.error(format!("entry {} of record {} has no field name", el, expr)));
+                    };
+                    if fields.iter().any(|(name, _)| name == key) {
+                        return Err(self.error(format!("field {} is given twice in record {}", key, expr)));
+                    }
+                    fields.push((key.clone(), self.infer_expr(value)?));
+                }
+                Type::record(fields)
+            }
             Expr::Vector { val } => {
                 let mut type_vec = Vec::new();
                 for el in val {
-                    type_vec.push(self.infer_expr(el));
+                    type_vec.push(self.infer_expr(el)?);
                 }
                 Type::Vector(type_vec)
             }
-            Expr::Variable { ident } => self
-                .var_context
-                .get(ident)
-                .or(self.name_context.get(ident))
-                .cloned()
-                .expect(&format!("cannot find var {:?} in context", ident)),
+            Expr::List { val } => {
+                let elem_typ = self.gen_typevar();
+                for el in val {
+                    let typ = self.infer_expr(el)?;
+                    if !self.unify(&elem_typ, &typ) {
+                        return Err(self.error(format!(
+                            "element {} of list {} has type {}, not {}",
+                            el,
+                            expr,
+                            self.resolve(&typ),
+                            self.resolve(&elem_typ)
+                        )));
+                    }
+                }
+                List(Box::new(elem_typ))
+            }
+            Expr::Variable { ident } => self.lookup(ident)?,
 
             Expr::Unop { op, expr } => match op {
                 UnOp::Neg => {
-                    let typ = self.infer_expr(expr);
-                    if self.unify(&typ, &Int) {
-                        Int
-                    } else {
-                        panic!("cannot unify {:?} and int", typ)
+                    let typ = self.infer_expr(expr)?;
+                    match self.find(&typ) {
+                        Int => Int,
+                        Float => Float,
+                        TypVar(_) => {
+                            self.numeric_typevars.push(typ.clone());
+                            typ
+                        }
+                        _ => return Err(self.error(format!("cannot unify {} and int", self.resolve(&typ)))),
                     }
                 }
                 UnOp::Not => {
-                    let typ = self.infer_expr(expr);
+                    let typ = self.infer_expr(expr)?;
                     if self.unify(&typ, &Bool) {
                         Bool
                     } else {
-                        panic!("cannot unify {:?} and bool", typ)
+                        return Err(self.error(format!("cannot unify {} and bool", typ)));
                     }
                 }
             },
 
             Expr::Binop { op, expr1, expr2 } => match op {
-                BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
-                    let typ1 = self.infer_expr(expr1);
-                    let typ2 = self.infer_expr(expr2);
-                    if !self.unify(&typ1, &Int) {
-                        panic!("cannot unify left hand side {:?} and int", typ1)
-                    } else if !self.unify(&typ2, &Int) {
-                        panic!("cannot unify right hand side {:?} and int", typ2)
-                    } else {
-                        Int
-                    }
+                BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                    let typ1 = self.infer_expr(expr1)?;
+                    let typ2 = self.infer_expr(expr2)?;
+                    self.unify_numeric(&typ1, &typ2)?
                 }
-                BinOp::Lt | BinOp::Gt => {
-                    let typ1 = self.infer_expr(expr1);
-                    let typ2 = self.infer_expr(expr2);
-                    if !self.unify(&typ1, &Int) {
-                        panic!("cannot unify left hand side {:?} and int", typ1)
-                    } else if !self.unify(&typ2, &Int) {
-                        panic!("cannot unify right hand side {:?} and int", typ2)
-                    } else {
-                        Bool
-                    }
+                // concatenation of strings
+                BinOp::Add => {
+                    let typ1 = self.infer_expr(expr1)?;
+                    let typ2 = self.infer_expr(expr2)?;
+                    self.unify_ordered(&typ1, &typ2)?
+                }
+                // strings are ordered lexicographically
+                BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => {
+                    let typ1 = self.infer_expr(expr1)?;
+                    let typ2 = self.infer_expr(expr2)?;
+                    self.unify_ordered(&typ1, &typ2)?;
+                    Bool
                 }
 
                 BinOp::And | BinOp::Or => {
-                    let typ1 = self.infer_expr(expr1);
-                    let typ2 = self.infer_expr(expr2);
+                    let typ1 = self.infer_expr(expr1)?;
+                    let typ2 = self.infer_expr(expr2)?;
                     if !self.unify(&typ1, &Bool) {
-                        panic!("cannot unify left hand side {:?} and bool", typ1)
+                        return Err(self.error(format!("cannot unify left hand side {} and bool", typ1)));
                     } else if !self.unify(&typ2, &Bool) {
-                        panic!("cannot unify right hand side {:?} and bool", typ2)
+                        return Err(self.error(format!("cannot unify right hand side {} and bool", typ2)));
                     } else {
                         Bool
                     }
                 }
 
-                BinOp::Eq => {
-                    let typ1 = self.infer_expr(expr1);
-                    let typ2 = self.infer_expr(expr2);
-                    if !self.unify(&typ1, &typ2) {
-                        panic!("cannot unify {:?} and {:?}", typ1, typ2)
+                BinOp::Eq | BinOp::Neq => {
+                    let typ1 = self.infer_expr(expr1)?;
+                    let typ2 = self.infer_expr(expr2)?;
+                    // ints and floats compare by value, e.g. 1 == 1.0
+                    let numeric = [self.find(&typ1), self.find(&typ2)]
+                        .iter()
+                        .all(|typ| matches!(typ, Int | Float));
+                    if !numeric && !self.unify(&typ1, &typ2) {
+                        return Err(self.error(format!("cannot unify {} and {}", typ1, typ2)));
                     } else {
                         Bool
                     }
@@ -92,176 +128,483 @@ impl TypecheckEnv {
             },
 
             Expr::If { cond, expr1, expr2 } => {
-                let cond_typ = self.infer_expr(cond);
+                let cond_typ = self.infer_expr(cond)?;
                 if !self.unify(&cond_typ, &Bool) {
-                    panic!("cannot unify {:?} and bool", cond_typ);
+                    return Err(self.error(format!("cannot unify condition {} and bool", cond_typ)));
                 }
-                let typ1 = self.infer_expr(expr1);
-                let typ2: Type = self.infer_expr(expr2);
+                let typ1 = self.infer_expr(expr1)?;
+                let typ2: Type = self.infer_expr(expr2)?;
                 if !self.unify(&typ1, &typ2) {
-                    panic!("cannot unify {:?} and {:?}", typ1, typ2);
+                    return Err(self
+                        .error(format!("cannot unify branches {} and {}", typ1, typ2))
+                        .with_note(format!("`then` branch: {}", expr1))
+                        .with_note(format!("`else` branch: {}", expr2)));
                 }
 
-                assert!(self.find(&typ1) == self.find(&typ2));
+                assert!(self.resolve(&typ1) == self.resolve(&typ2));
                 self.find(&typ1)
             }
 
-            Expr::Func { params, body } => {
+            Expr::Func { params, param_typs, body } => {
                 // check params are unique
                 let mut param_set = std::collections::HashSet::new();
                 for param in params.iter() {
                     if !param_set.insert(param) {
-                        panic!("duplicate param name: {}", param);
+                        return Err(self.error(format!("duplicate param name: {}", param)));
                     }
                 }
 
                 // frozen current context
                 let old_context = self.var_context.clone();
 
-                // first generate type variables for param, update context
+                // first generate type variables for param (or take their
+                // annotation, whose type vars are shared by the params), update context
                 let mut param_types = vec![];
-                for param in params.iter() {
-                    let typ = self.gen_typevar();
+                let mut annot_vars = HashMap::new();
+                for (i, param) in params.iter().enumerate() {
+                    let typ = match param_typs.get(i) {
+                        Some(Some(annot)) => self.annot_type(annot, &mut annot_vars),
+                        _ => self.gen_typevar(),
+                    };
                     self.var_context.insert(param.clone(), typ.clone());
                     param_types.push(typ);
                 }
 
                 // type infer func body mutates acc_subst
-                let mut ret_typ = self.infer_expr(&body);
+                let ret_typ = self.infer_expr(&body);
+
+                // restore old context
+                self.var_context = old_context;
 
                 // generate function type signature of canonical form
                 for param_typ in param_types.iter_mut() {
                     *param_typ = self.find(param_typ);
                 }
-                ret_typ = self.find(&ret_typ);
-
-                // restore old context
-                self.var_context = old_context;
+                let ret_typ = self.find(&ret_typ?);
 
                 Fun(param_types, Box::new(ret_typ))
             }
 
             Expr::FuncApply { func, args } => {
-                let func_typ = self.infer_expr(func);
+                let func_typ = self.infer_expr(func)?;
                 if let Type::Fun(arg_typs, ret_typ) = func_typ {
                     if arg_typs.len() != args.len() {
-                        panic!("wrong number of argument to apply");
+                        return Err(self.error(format!(
+                            "wrong number of arguments to apply {}, expect {} got {}",
+                            func,
+                            arg_typs.len(),
+                            args.len()
+                        )));
                     } else {
                         for (i, arg) in args.iter().enumerate() {
                             let typ_i: &Type = &arg_typs[i];
-                            let typ_i_actual = self.infer_expr(arg);
+                            let typ_i_actual = self.infer_expr(arg)?;
                             if !self.unify(typ_i, &typ_i_actual) {
-                                panic!(
-                                    "cannot unify {:?}th argument, 
-                                    expect {:?} got {:?}",
-                                    i, typ_i, typ_i_actual
-                                )
+                                return Err(self.error(format!(
+                                    "cannot unify {}th argument of {}, expect {} got {}",
+                                    i, func, typ_i, typ_i_actual
+                                )));
                             }
                         }
                         self.find(&ret_typ)
                     }
                 } else {
                     let ret_typ = self.gen_typevar();
-                    let arg_typs = args.iter().map(|a| self.infer_expr(a)).collect();
+                    let mut arg_typs = vec![];
+                    for arg in args.iter() {
+                        arg_typs.push(self.infer_expr(arg)?);
+                    }
 
                     let func_typ_actual = Type::Fun(arg_typs, Box::new(ret_typ.clone()));
 
                     if !self.unify(&func_typ, &func_typ_actual) {
-                        panic!(
-                            "cannot unify function type, expected {:?} got {:?}",
+                        return Err(self.error(format!(
+                            "cannot unify function type, expected {} got {}",
                             func_typ, func_typ_actual
-                        );
+                        )));
                     }
 
                     self.find(&ret_typ)
                 }
             }
 
+            Expr::Let { name, annot, val, body } => {
+                let val_typ = self.infer_expr(val)?;
+                if let Some(annot) = annot {
+                    let annot_typ = self.annot_type(annot, &mut HashMap::new());
+                    if !self.unify(&annot_typ, &val_typ) {
+                        return Err(self.error(format!(
+                            "type of {} is declared {} but inferred {}",
+                            name,
+                            annot,
+                            self.resolve(&val_typ)
+                        )));
+                    }
+                }
+
+                // name is bound in body as a param is, to a type
+                // that is not generalized
+                let old_context = self.var_context.clone();
+                self.var_context.insert(name.clone(), val_typ);
+                let typ = self.infer_expr(body);
+                self.var_context = old_context;
+                typ?
+            }
+
             // more todo on Action type
-            Expr::Action { assns , inserts} => {
-                assns.iter().for_each(|assn| self.typecheck_assn(assn));
-                inserts.iter().for_each(|insert| self.typecheck_insert(insert));
+            Expr::Action { assns , inserts, edits } => {
+                for assn in assns.iter() {
+                    self.typecheck_assn(assn)?;
+                }
+                for insert in inserts.iter() {
+                    self.typecheck_insert(insert)?;
+                }
+                for edit in edits.iter() {
+                    self.typecheck_edit(edit)?;
+                }
 
                 Action
             }
+            Expr::ActionBlock { stmts } => {
+                for stmt in stmts {
+                    self.typecheck_action_stmt(stmt)?;
+                }
+                Action
+            }
             Expr::Select { table_name, column_names, where_clause } => {
-                let schema = {
-                    let table_type = self.var_context.get(table_name);    // check if table exists and extract schema
-                    match table_type {
-                        Some(Type::Table(fields)) => fields.clone(),
-                        _ => panic!("Table {} for selection not found or not a table type", table_name),
-                    }
-                };
-                let field_names: Vec<_> = schema.iter().map(|field| field.name.clone()).collect();   // get column names and check if columns to be selected exist
+                let schema = self.lookup_table(table_name)?;
+
+                // projected columns, in the order they are selected
+                let mut projected = vec![];
                 for column_name in column_names {
-                    if !field_names.contains(column_name) {
-                        panic!("{} field not found in table {}", column_name, table_name);
+                    let Some(field) = schema.iter().find(|field| &field.name == column_name) else {
+                        return Err(self.error(format!("{} field not found in table {}", column_name, table_name)));
+                    };
+                    if projected.contains(field) {
+                        return Err(self.error(format!("{} field selected twice from table {}", column_name, table_name)));
                     }
+                    projected.push(field.clone());
+                }
+                if projected.is_empty() {
+                    projected = schema.clone(); // no column selects all of them
                 }
 
+                // where clause is typed in the scope of a row, where
+                // columns are bound by their bare name
+                let old_context = self.var_context.clone();
+                for field in schema.iter() {
+                    self.var_context.insert(field.name.clone(), Type::from(&field.type_));
+                }
                 let cond_type = self.infer_expr(where_clause);
-                
-                if cond_type != Type::Bool {
-                    panic!("Select where clause must be boolean, got {}", cond_type);
+                self.var_context = old_context;
+
+                let cond_type = cond_type?;
+                if !self.unify(&cond_type, &Bool) {
+                    return Err(self.error(format!("Select where clause must be boolean, got {}", self.resolve(&cond_type))));
                 }
-                Type::Table(schema)    
+                Type::Table(projected)
             }
             Expr::TableColumn { table_name, column_name } => {
-                // Look up table type in context
-                let found_table = self.var_context.get(table_name);
-                match found_table {
-                    Some(Type::Table(fields)) => {
-                        let found_column = fields.iter().find(|field| &field.name == column_name);
-                        match found_column {
-                            Some(field) => match field.type_ {
-                                DataType::Bool => Type::Bool,
-                                DataType::Number => Type::Int,
-                                DataType::String => Type::String,
-                            },
-                            None => panic!("Column {} not found in table {}", column_name, table_name),
+                let typ = self.lookup(table_name)?;
+                if let Record(fields) = self.find(&typ) {
+                    return self.field_type(&fields, column_name, table_name);
+                }
+
+                let schema = self.lookup_table(table_name)?;
+                match schema.iter().find(|field| &field.name == column_name) {
+                    Some(field) => Type::from(&field.type_),
+                    None => return Err(self.error(format!("Column {} not found in table {}", column_name, table_name))),
+                }
+            }
+            Expr::RecordField { expr: record, name } => {
+                let typ = self.infer_expr(record)?;
+                match self.find(&typ) {
+                    Record(fields) => self.field_type(&fields, name, record)?,
+                    TypVar(_) => return Err(self.not_known(record, "a record")),
+                    typ => {
+                        return Err(self.error(format!(
+                            "{} is not a record, it has type {}",
+                            record,
+                            self.resolve(&typ)
+                        )))
+                    }
+                }
+            }
+            Expr::TupleIndex { expr: tuple, idx } => {
+                let typ = self.infer_expr(tuple)?;
+                match self.find(&typ) {
+                    Vector(typs) => match typs.get(*idx) {
+                        Some(typ) => self.find(typ),
+                        None => {
+                            return Err(self.error(format!(
+                                "tuple {} has {} entries, no entry {}",
+                                tuple,
+                                typs.len(),
+                                idx
+                            )))
                         }
+                    },
+                    TypVar(_) => return Err(self.not_known(tuple, "a tuple")),
+                    typ => {
+                        return Err(self.error(format!(
+                            "{} is not a tuple, it has type {}",
+                            tuple,
+                            self.resolve(&typ)
+                        )))
                     }
-                    _ => panic!("Table {} for TableColumn not found or not a table type", table_name),
                 }
             }
             Expr::Table {schema, records } => Table(schema.to_vec()),
             Expr::Fold { args } => {
                 if args.len()!=3 {
-                    panic!("Fold expects 3 arguments, got {} arguments", args.len());
+                    return Err(self.error(format!("Fold expects 3 arguments, got {} arguments", args.len())));
                 }
-                
-                let column_type = self.infer_expr(&args[0]);
-                let func_type = self.infer_expr(&args[1]);
-                let accum_type = self.infer_expr(&args[2]);
 
-                if let Expr::TableColumn { .. } = &args[0] {     // Maybe later we can have a tablecolumn type for typechecking here
-                    self.infer_expr(&args[0]);
-                } else {         
-                    panic!("First argument should be iterator (column)");
-                }
+                // Maybe later we can have a tablecolumn type for typechecking here
+                let Expr::TableColumn { .. } = args[0].unspanned() else {
+                    return Err(self.error("First argument should be iterator (column)".to_string()));
+                };
+                let column_type = self.infer_expr(&args[0])?;
+                let func_type = self.infer_expr(&args[1])?;
+                let accum_type = self.infer_expr(&args[2])?;
 
-                match func_type {
-                    Type::Fun(params, ret_type) => {
-                        if !self.unify(&accum_type, &*ret_type) {
-                            panic!("Accumulator type should be the same as function return type, expected {}, got {}", &*ret_type, &accum_type);
-                        }
-                        for param in params {
-                            if !self.unify(&param, &column_type) {
-                                panic!("Column type should be the same as function argument type, expected {}, got {}", &param, &column_type);
-                            }
-                        }
-                    },
-                    _ => panic!("Second argument must be function type")
+                // fold(column, f, init) is f(..f(f(init, v1), v2).., vn)
+                let expected_type = Fun(vec![accum_type.clone(), column_type], Box::new(accum_type.clone()));
+                if !self.unify(&func_type, &expected_type) {
+                    return Err(self.error(format!(
+                        "fold function should have type {}, got {}",
+                        self.resolve(&expected_type),
+                        self.resolve(&func_type)
+                    )));
                 }
                 self.find(&accum_type)
-                
+
             },
-            
-        }
+            Expr::ListOp { op, args } => self.infer_list_op(*op, args)?,
+            Expr::Variant { name, args } => {
+                let (typ_name, variant) = self.variant(name)?;
+                let field_typs = self.field_types(&variant);
+                if args.len() != field_typs.len() {
+                    return Err(self.error(format!(
+                        "variant {} has {} fields, got {}",
+                        variant,
+                        field_typs.len(),
+                        args.len()
+                    )));
+                }
+                for (arg, field_typ) in zip(args, field_typs) {
+                    let typ = self.infer_expr(arg)?;
+                    if !self.unify(&typ, &field_typ) {
+                        return Err(self.error(format!(
+                            "field {} of {} has type {}, not {}",
+                            arg,
+                            expr,
+                            self.resolve(&typ),
+                            field_typ
+                        )));
+                    }
+                }
+                Adt(typ_name)
+            }
+            Expr::Match { expr: matched, arms } => self.infer_match(matched, arms)?,
+            // type errors found in the expr point at it
+            Expr::Spanned { span, expr } => {
+                let outer = std::mem::replace(&mut self.span, *span);
+                let typ = self.infer_expr(expr);
+                self.span = outer;
+                typ?
+            }
+
+        };
+        Ok(typ)
 }
+
+    /// type of a match, whose arms all have the same type and match
+    /// every variant of the matched type, each of them once
+    fn infer_match(&mut self, matched: &Expr, arms: &[MatchArm]) -> Result<Type, TypeError> {
+        let matched_typ = self.infer_expr(matched)?;
+        let mut variants = None;
+        for arm in arms {
+            if let Pattern::Variant { name, .. } = &arm.pattern {
+                let (typ_name, _) = self.variant(name)?;
+                if !self.unify(&matched_typ, &Type::Adt(typ_name.clone())) {
+                    return Err(self.error(format!(
+                        "{} has type {}, it cannot be matched with {} of type {}",
+                        matched,
+                        self.resolve(&matched_typ),
+                        arm.pattern,
+                        typ_name
+                    )));
+                }
+                variants = self.types.get(&typ_name).cloned();
+            }
+        }
+
+        let typ = self.gen_typevar();
+        let mut matched_variants = HashSet::new();
+        let mut exhaustive = false;
+        for arm in arms {
+            if exhaustive {
+                return Err(self
+                    .error(format!("arm {} of match is never reached", arm.pattern))
+                    .with_note("arms above it match every value".to_string()));
+            }
+            let bind_typs = match &arm.pattern {
+                Pattern::Var(name) => {
+                    exhaustive = true;
+                    vec![(name.clone(), matched_typ.clone())]
+                }
+                Pattern::Variant { name, binds } => {
+                    let (_, variant) = self.variant(name)?;
+                    if !matched_variants.insert(name.clone()) {
+                        return Err(self.error(format!("variant {} is matched twice", name)));
+                    }
+                    let field_typs = self.field_types(&variant);
+                    if binds.len() != field_typs.len() {
+                        return Err(self.error(format!(
+                            "pattern {} binds {} fields, but variant {} has {}",
+                            arm.pattern,
+                            binds.len(),
+                            variant,
+                            field_typs.len()
+                        )));
+                    }
+                    exhaustive = variants.as_ref().is_some_and(|variants| variants.len() == matched_variants.len());
+                    zip(binds.iter().cloned(), field_typs).collect()
+                }
+            };
+
+            // binds are bound in the body of the arm as a let binds its name
+            let old_context = self.var_context.clone();
+            let mut bound = HashSet::new();
+            for (bind, bind_typ) in bind_typs {
+                if bind != "_" && !bound.insert(bind.clone()) {
+                    self.var_context = old_context;
+                    return Err(self.error(format!("{} is bound twice in pattern {}", bind, arm.pattern)));
+                }
+                self.var_context.insert(bind, bind_typ);
+            }
+            let arm_typ = self.infer_expr(&arm.body);
+            self.var_context = old_context;
+            let arm_typ = arm_typ?;
+            if !self.unify(&typ, &arm_typ) {
+                return Err(self
+                    .error(format!(
+                        "cannot unify arms {} and {}",
+                        self.resolve(&typ),
+                        self.resolve(&arm_typ)
+                    ))
+                    .with_note(format!("arm: {} => {}", arm.pattern, arm.body)));
+            }
+        }
+
+        if !exhaustive {
+            let missing = variants
+                .unwrap_or_default()
+                .iter()
+                .filter(|variant| !matched_variants.contains(&variant.name))
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            return Err(self
+                .error(format!("match on {} is not exhaustive, {} not matched", matched, missing.join(", ")))
+                .with_note("add an arm for each of them, or a `_ => ..` arm".to_string()));
+        }
+        Ok(self.find(&typ))
+    }
+
+    /// declared type of a variant, and the variant
+    fn variant(&self, name: &str) -> Result<(std::string::String, Variant), TypeError> {
+        self.lookup_variant(name).ok_or_else(|| {
+            self.error(format!("cannot find variant {}", name))
+                .with_note("variants are declared as `type status = Pending | Done(int);`".to_string())
+        })
+    }
+
+    /// type of a builtin on lists, e.g. map(xs, f) is list<b>
+    /// for xs of type list<a> and f of type a -> b
+    fn infer_list_op(&mut self, op: ListOp, args: &[Expr]) -> Result<Type, TypeError> {
+        let arity = match op {
+            ListOp::Length | ListOp::Head => 1,
+            ListOp::Map | ListOp::Filter | ListOp::Append => 2,
+            ListOp::Reduce => 3,
+        };
+        if args.len() != arity {
+            return Err(self.error(format!(
+                "{} expects {} arguments, got {} arguments",
+                op,
+                arity,
+                args.len()
+            )));
+        }
+
+        let list_typ = self.infer_expr(&args[0])?;
+        let elem_typ = self.gen_typevar();
+        if !self.unify(&list_typ, &Type::List(Box::new(elem_typ.clone()))) {
+            return Err(self.error(format!(
+                "first argument of {} should be a list, got {}",
+                op,
+                self.resolve(&list_typ)
+            )));
+        }
+
+        // type the other argument of the builtin should have, and its result
+        let (arg_typ, typ) = match op {
+            ListOp::Length => return Ok(Type::Int),
+            ListOp::Head => return Ok(self.find(&elem_typ)),
+            ListOp::Map => {
+                let ret_typ = self.gen_typevar();
+                let func_typ = Type::Fun(vec![elem_typ], Box::new(ret_typ.clone()));
+                (func_typ, Type::List(Box::new(ret_typ)))
+            }
+            ListOp::Filter => (
+                Type::Fun(vec![elem_typ.clone()], Box::new(Type::Bool)),
+                Type::List(Box::new(elem_typ)),
+            ),
+            ListOp::Append => (elem_typ.clone(), Type::List(Box::new(elem_typ))),
+            // reduce(xs, f, init) is f(..f(f(init, x1), x2).., xn)
+            ListOp::Reduce => {
+                let accum_typ = self.infer_expr(&args[2])?;
+                let func_typ = Type::Fun(vec![accum_typ.clone(), elem_typ], Box::new(accum_typ.clone()));
+                (func_typ, accum_typ)
+            }
+        };
+        let typ1 = self.infer_expr(&args[1])?;
+        if !self.unify(&typ1, &arg_typ) {
+            return Err(self.error(format!(
+                "second argument of {} should have type {}, got {}",
+                op,
+                self.resolve(&arg_typ),
+                self.resolve(&typ1)
+            )));
+        }
+        Ok(self.find(&typ))
+    }
+
+    /// type of field `name` of a record of fields
+    fn field_type(
+        &mut self,
+        fields: &[(String, Type)],
+        name: &str,
+        record: &dyn std::fmt::Display,
+    ) -> Result<Type, TypeError> {
+        match fields.iter().find(|(field, _)| field == name) {
+            Some((_, typ)) => Ok(self.find(typ)),
+            None => Err(self.error(format!(
+                "record {} has no field {}, it has type {}",
+                record,
+                name,
+                self.resolve(&Type::Record(fields.to_vec()))
+            ))),
+        }
+    }
+
+    /// a field is only taken from a record (or tuple) whose fields are known
+    fn not_known(&self, expr: &Expr, what: &str) -> TypeError {
+        self.error(format!("type of {} is not known to be {}", expr, what))
+            .with_note("annotate it, e.g. `fn p: {name: string} => p.name`".to_string())
+    }
 }
 
 // TODO List
 // (priority) implement statics for actions
 // 1. more efficient implementation of var context
-// 2. add language feature: let binding
@@ -1,11 +1,53 @@
-use crate::ast::Service;
+use std::collections::HashMap;
 
-use super::TypecheckEnv;
+use crate::ast::{Decl, Service};
+
+use super::{Type, TypeScheme, TypecheckEnv};
 
 impl TypecheckEnv {
+    /// check each decl, collecting type errors in `errors`
     pub fn typecheck_service(&mut self, srvs: &Service) {
         for stmt in srvs.decls.iter() {
-            self.typecheck_decl(stmt);
+            if let Err(e) = self.typecheck_decl(stmt) {
+                self.errors.push(e);
+                self.declare_ill_typed(stmt);
+            }
+        }
+    }
+
+    /// a name whose decl does not typecheck takes any type where it is
+    /// used, so its error is reported once instead of at each use
+    fn declare_ill_typed(&mut self, decl: &Decl) {
+        match decl {
+            Decl::VarDecl { name, .. } | Decl::DefDecl { name, .. } => {
+                if !self.name_context.contains_key(name) {
+                    // forall a. a, each use is typed independently
+                    let typ = self.gen_typevar();
+                    let scheme = self.generalize(&typ);
+                    self.name_context.insert(name.clone(), scheme);
+                }
+                if let Decl::DefDecl { is_pub: true, .. } = decl {
+                    self.pub_names.insert(name.clone());
+                }
+            }
+            Decl::TableDecl { name, fields, .. } => {
+                self.name_context
+                    .entry(name.clone())
+                    .or_insert_with(|| TypeScheme::mono(Type::Table(fields.clone())));
+            }
+            Decl::Import { .. } | Decl::TypeDecl { .. } => {}
         }
     }
+
+    /// fully resolved types of the service's public names,
+    /// for services importing it
+    pub fn exports(&self, srvs: &Service) -> HashMap<String, Type> {
+        srvs.pub_names()
+            .into_iter()
+            .filter_map(|name| {
+                let scheme = self.name_context.get(&name)?;
+                Some((name, self.resolve(&scheme.typ)))
+            })
+            .collect()
+    }
 }
@@ -1,100 +1,338 @@
-use crate::ast::{Assn, DataType, Decl, Insert, Expr, Field};
+use crate::ast::{Assn, Decl, Field, Insert, Expr, RowEdit, Stmt, TypeAnnot, Variant};
 
-use std::collections::HashSet;
-use super::TypecheckEnv;
+use std::collections::{HashMap, HashSet};
+use super::{TypeError, TypeScheme, TypecheckEnv};
 use crate::static_analysis::typecheck::Type;
 
 impl TypecheckEnv {
-    pub fn typecheck_decl(&mut self, decl: &Decl) {
+    pub fn typecheck_decl(&mut self, decl: &Decl) -> Result<(), TypeError> {
+        self.span = decl.span();
+        self.numeric_typevars.clear(); // left by a decl with a type error
         match decl {
-            Decl::Import { srv_name } => todo!(),
-            Decl::VarDecl { name, val } => {
-                let typ = self.infer_expr(&val);
-                self.name_context.insert(name.clone(), typ);
+            Decl::Import { srv_name, .. } => {
+                let Some(exports) = self.srv_to_exports.get(srv_name).cloned() else {
+                    return Err(self.error(format!("cannot find imported service {:?}", srv_name)));
+                };
+
+                // type vars left in an imported type belong to the imported
+                // service's environment, rename them to fresh ones here,
+                // they are all generalized as nothing here refers to them
+                for (name, typ) in exports {
+                    let typ = self.rename_typevars(&typ, &mut HashMap::new());
+                    let mut vars = typ.free_var().into_iter().collect::<Vec<_>>();
+                    vars.sort();
+                    self.name_context.insert(name.clone(), TypeScheme { vars, typ });
+                    self.pub_names.insert(name);
+                }
+                for name in self.srv_to_private_names[srv_name].clone() {
+                    self.private_imports.insert(name, srv_name.clone());
+                }
+                // declared types are all public
+                for (name, variants) in self.srv_to_types[srv_name].clone() {
+                    self.declare_type(&name, &variants)?;
+                }
             }
-            Decl::DefDecl { name, val, is_pub } => {
-                let typ = self.infer_expr(&val);
-                self.name_context.insert(name.clone(), typ);
+            Decl::VarDecl { name, annot, val, .. } => {
+                let typ = self.infer_expr(&val)?;
+                if let Some(annot) = annot {
+                    self.check_annot(name, annot, &typ)?;
+                }
+                self.default_numeric()?;
+                // value restriction: a var is assigned, only defs are generalized,
+                // so `var r = [];` takes the type of what is assigned to it
+                let scheme = TypeScheme::mono(self.resolve(&typ));
+                self.name_context.insert(name.clone(), scheme);
+            }
+            Decl::DefDecl { name, annot, val, is_pub, .. } => {
+                let typ = if val.is_rec_fn(name) {
+                    self.infer_rec_fn(name, val)?
+                } else {
+                    self.infer_expr(&val)?
+                };
+                if let Some(annot) = annot {
+                    self.check_annot(name, annot, &typ)?;
+                }
+                self.default_numeric()?;
+                let scheme = self.generalize(&typ);
+                self.name_context.insert(name.clone(), scheme);
+                if *is_pub {
+                    self.pub_names.insert(name.clone());
+                }
             }
-            Decl::TableDecl { name, fields } =>  {
+            Decl::TableDecl { name, fields, .. } =>  {
                 let mut names = HashSet::new();
                 for field in fields {
                     if !names.insert(field.name.clone()) {
-                        panic!("Duplicate names found in table {}", name)
+                        return Err(self.error(format!("Duplicate names found in table {}", name)));
+                    }
+                }
+                let scheme = TypeScheme::mono(Type::Table(fields.to_vec()));
+                self.name_context.insert(name.clone(), scheme);
+            }
+            Decl::TypeDecl { name, variants, .. } => {
+                if self.types.contains_key(name) {
+                    return Err(self.error(format!("type {} is declared twice", name)));
+                }
+                self.declare_type(name, variants)?;
+
+                // fields are of known types, the declared one included
+                for variant in variants {
+                    let unknown = self
+                        .field_types(variant)
+                        .iter()
+                        .flat_map(Type::free_var)
+                        .collect::<Vec<_>>();
+                    if let Some(unknown) = unknown.first() {
+                        self.types.remove(name);
+                        return Err(self
+                            .error(format!("unknown type {} in variant {}", unknown, variant))
+                            .with_note("declare it with `type` before it is used".to_string()));
                     }
                 }
-                self.var_context.insert(name.clone(), Type::Table(fields.to_vec()));
             }
         }
+        Ok(())
     }
 
-    pub fn typecheck_assn(&mut self, assn: &Assn) {
-        let dest_typ = self
-            .name_context
-            .get(&assn.dest)
-            .cloned()
-            .expect(&format!("cannot find {:?} in var context", assn.dest));
-        let src_typ = self.infer_expr(&assn.src);
-
-        if !self.unify(&dest_typ, &src_typ) {
-            panic!(
-                "cannot unify left {:?} and right {:?} in assign",
-                dest_typ, src_typ
-            );
+    /// add a declared type, its variants cannot be variants of another type,
+    /// the same type imported twice is declared once
+    fn declare_type(&mut self, name: &String, variants: &[Variant]) -> Result<(), TypeError> {
+        if self.types.get(name).is_some_and(|declared| declared == variants) {
+            return Ok(());
         }
+        let mut names = HashSet::new();
+        for variant in variants {
+            if !names.insert(&variant.name) {
+                return Err(self.error(format!("variant {} is declared twice in type {}", variant.name, name)));
+            }
+            if let Some((other, _)) = self.lookup_variant(&variant.name) {
+                return Err(self.error(format!("variant {} is already a variant of type {}", variant.name, other)));
+            }
+        }
+        if self.types.contains_key(name) {
+            return Err(self.error(format!("type {} is declared twice", name)));
+        }
+        self.types.insert(name.clone(), variants.to_vec());
+        Ok(())
     }
 
-    pub fn typecheck_insert(&mut self, insert: &Insert) {
-        let found_type = self.var_context.get(&insert.table_name).cloned();
-        match found_type {
-            None => panic!("Table {} for insertion not found", insert.table_name),
-            Some(table_type) => {
-                match &table_type {
-                    Type::Table(schema) => {
-                        match &insert.row {
-                            Expr::Vector { val: keyvals } => {
-                                if keyvals.len()!= schema.len() {
-                                    panic!("Entries in the row do not match the table {} schema", insert.table_name);
-                                }
-                                for keyval in keyvals {
-                                    match keyval {
-                                        Expr::KeyVal { key, value} => {
-                                            
-                                            let field_type = schema.iter().find(|f| f.name == *key)
-                                                .unwrap_or_else(|| panic!("Field '{}' not found in table '{}' schema", key, insert.table_name));
-                                            let expected_type = match field_type.type_ {
-                                                DataType::Bool => Type::Bool,
-                                                DataType::Number => Type::Int,
-                                                DataType::String => Type::String,
-                                            };
-                                            let inferred_type = self.infer_expr(&*value);
-                                            if !self.unify(&inferred_type, &expected_type) {
-                                                panic!("Data type of entry '{}' does not match the schema, expected {:?}, got {:?}", key, expected_type, inferred_type);
-                                            }
-                                        }
-                                        _ => panic!("Non keyval found")
-                                    }
-                                }
-                            }
-                            expr => {
-                                let inferred_type = self.infer_expr(expr);
-                                let expected_type = Type::Table(schema.clone());
-                                if !self.unify(&inferred_type, &expected_type) {
-                                    panic!("Insert expression type does not match table schema, expected {:?}, got {:?}", expected_type, inferred_type);
-                                }
-                            }
-                        }
-                    }
-                    // If table_name is a parameter (type variable), unify row type with it
-                    other_type => {
-                        let row_type = self.infer_expr(&insert.row);
-                        if !self.unify(&row_type, other_type) {
-                            panic!("Insert row type does not match parameter type for '{}', expected {:?}, got {:?}", insert.table_name, other_type, row_type);
-                        }
-                    }
+    /// a recursive function is typed with its name bound to a monomorphic
+    /// type in its body, it is generalized only after
+    fn infer_rec_fn(&mut self, name: &String, val: &Expr) -> Result<Type, TypeError> {
+        let rec_typ = self.gen_typevar();
+        self.var_context.insert(name.clone(), rec_typ.clone());
+        let typ = self.infer_expr(val);
+        self.var_context.remove(name);
+
+        let typ = typ?;
+        if !self.unify(&rec_typ, &typ) {
+            return Err(self.error(format!(
+                "recursive use of {} has type {}, but {} has type {}",
+                name,
+                self.resolve(&rec_typ),
+                name,
+                self.resolve(&typ)
+            )));
+        }
+        Ok(typ)
+    }
+
+    /// inferred type of a declared name must be an instance of its
+    /// annotation, type vars of the annotation are quantified, i.e.
+    /// `def id: a -> a = ..` must be as general as `a -> a`
+    fn check_annot(&mut self, name: &str, annot: &TypeAnnot, typ: &Type) -> Result<(), TypeError> {
+        let annot_typ = self.declared_types(&Type::from(annot));
+        let mut vars = annot_typ.free_var().into_iter().collect::<Vec<_>>();
+        vars.sort();
+        let scheme = TypeScheme { vars, typ: annot_typ };
+        if self.unify_general(&scheme, typ) {
+            return Ok(());
+        }
+
+        let error = self.error(format!(
+            "type of {} is declared {} but inferred {}",
+            name,
+            annot,
+            self.resolve(typ)
+        ));
+        if scheme.vars.is_empty() {
+            Err(error)
+        } else {
+            Err(error.with_note(format!(
+                "type variables {} of the annotation stand for any type",
+                scheme.vars.join(", ")
+            )))
+        }
+    }
+
+    pub fn typecheck_assn(&mut self, assn: &Assn) -> Result<(), TypeError> {
+        // as in lookup, a name declared here hides a private one of an import
+        let Some(dest_scheme) = self.name_context.get(&assn.dest).cloned() else {
+            if let Some(srv_name) = self.private_imports.get(&assn.dest) {
+                return Err(self.error(format!(
+                    "cannot assign to {:?}, it is not public in imported service {:?}",
+                    assn.dest, srv_name
+                )));
+            }
+            return Err(self.error(format!("cannot find {:?} in var context", assn.dest)));
+        };
+        let src_typ = self.infer_expr(&assn.src)?;
+
+        // a polymorphic name is used at any instance of its type, so only
+        // values as general as its type can be assigned to it
+        if !self.unify_general(&dest_scheme, &src_typ) {
+            return Err(self
+                .error(format!(
+                    "cannot unify left {} and right {} in assign",
+                    dest_scheme,
+                    self.resolve(&src_typ)
+                ))
+                .with_note(format!("in `{}`", assn)));
+        }
+        Ok(())
+    }
+
+    /// statement of an action
+    pub fn typecheck_action_stmt(&mut self, stmt: &Stmt) -> Result<(), TypeError> {
+        match stmt {
+            Stmt::Assn(assn) => self.typecheck_assn(assn),
+            Stmt::Insert(insert) => self.typecheck_insert(insert),
+            Stmt::Edit(edit) => self.typecheck_edit(edit),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                let cond_typ = self.infer_expr(cond)?;
+                if !self.unify(&cond_typ, &Type::Bool) {
+                    return Err(self.error(format!("cannot unify condition {} and bool", self.resolve(&cond_typ))));
+                }
+                for stmt in then_stmts.iter().chain(else_stmts) {
+                    self.typecheck_action_stmt(stmt)?;
+                }
+                Ok(())
+            }
+            Stmt::Do(expr) => {
+                let typ = self.infer_expr(expr)?;
+                if !self.unify(&typ, &Type::Action) {
+                    return Err(self
+                        .error(format!("do requires action expression, got {}", self.resolve(&typ)))
+                        .with_note(format!("in `{}`", stmt)));
                 }
+                Ok(())
+            }
+        }
+    }
+
+    /// where clause and new values of an edit are typed in the scope of a row,
+    /// as in select, its columns shadow reactive names of the same name
+    pub fn typecheck_edit(&mut self, edit: &RowEdit) -> Result<(), TypeError> {
+        let table_name = edit.table_name();
+        let schema = self.lookup_table(table_name)?;
+        let (sets, where_clause) = match edit {
+            RowEdit::Update { sets, where_clause, .. } => (&sets[..], where_clause),
+            RowEdit::Delete { where_clause, .. } => (&[][..], where_clause),
+        };
+
+        let old_context = self.var_context.clone();
+        for field in schema.iter() {
+            self.var_context.insert(field.name.clone(), Type::from(&field.type_));
+        }
+        let typed = self.typecheck_edit_exprs(edit, &schema, sets, where_clause);
+        self.var_context = old_context;
+        typed
+    }
+
+    fn typecheck_edit_exprs(
+        &mut self,
+        edit: &RowEdit,
+        schema: &[Field],
+        sets: &[Assn],
+        where_clause: &Expr,
+    ) -> Result<(), TypeError> {
+        let mut columns = HashSet::new();
+        for set in sets {
+            let Some(field) = schema.iter().find(|field| field.name == set.dest) else {
+                return Err(self.error(format!("column {} not found in table {}", set.dest, edit.table_name())));
+            };
+            if !columns.insert(&set.dest) {
+                return Err(self.error(format!("column {} is set twice in {}", set.dest, edit)));
+            }
+
+            let expected_type = Type::from(&field.type_);
+            let inferred_type = self.infer_expr(&set.src)?;
+            if !self.unify(&inferred_type, &expected_type) {
+                return Err(self
+                    .error(format!(
+                        "cannot set column {} of type {} to {}",
+                        set.dest,
+                        expected_type,
+                        self.resolve(&inferred_type)
+                    ))
+                    .with_note(format!("in `{}`", edit)));
+            }
+        }
+
+        let cond_type = self.infer_expr(where_clause)?;
+        if !self.unify(&cond_type, &Type::Bool) {
+            return Err(self.error(format!(
+                "where clause of {} must be boolean, got {}",
+                edit.table_name(),
+                self.resolve(&cond_type)
+            )));
+        }
+        Ok(())
+    }
+
+    /// a row is inserted as `{column: value, ..}`, giving each column once
+    pub fn typecheck_insert(&mut self, insert: &Insert) -> Result<(), TypeError> {
+        let table_name = &insert.table_name;
+        let schema = self.lookup_table(table_name)?;
+        let Expr::Vector { val: entries } = insert.row.unspanned() else {
+            return Err(self
+                .error(format!("cannot insert {} into {}, it is not a row", insert.row, table_name))
+                .with_note("rows are written as `{column: value, ..}`".to_string()));
+        };
+
+        let mut columns = HashSet::new();
+        for entry in entries {
+            let Expr::KeyVal { key, value } = entry.unspanned() else {
+                return Err(self.error(format!(
+                    "entry {} of the row inserted into {} has no column name",
+                    entry, table_name
+                )));
+            };
+            let Some(field) = schema.iter().find(|field| field.name == *key) else {
+                return Err(self.error(format!("column {} not found in table {}", key, table_name)));
+            };
+            if !columns.insert(key.clone()) {
+                return Err(self.error(format!(
+                    "column {} is given twice in the row inserted into {}",
+                    key, table_name
+                )));
             }
+
+            let expected_type = Type::from(&field.type_);
+            let inferred_type = self.infer_expr(value)?;
+            if !self.unify(&inferred_type, &expected_type) {
+                return Err(self.error(format!(
+                    "Data type of entry '{}' does not match the schema, expected {}, got {}",
+                    key,
+                    expected_type,
+                    self.resolve(&inferred_type)
+                )));
+            }
+        }
+
+        let missing = schema
+            .iter()
+            .filter(|field| !columns.contains(&field.name))
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(self.error(format!(
+                "row inserted into {} has no value for column {}",
+                table_name,
+                missing.join(", ")
+            )));
         }
+        Ok(())
     }
 }
 
@@ -1,22 +1,51 @@
-use super::{Type, TypecheckEnv};
+use super::{Type, TypeError, TypecheckEnv};
 use crate::ast::*;
+use crate::static_analysis::var_analysis::read_write::calc_touched_names;
 impl TypecheckEnv {
+    /// check each command, collecting type errors in `errors`
     pub fn typecheck_test(&mut self, test: &Test) {
-        for command in test.commands.iter() {
-            match command {
-                ReplCmd::Do(expr) => {
-                    let typ = self.infer_expr(expr);
-                    if !self.unify(&typ, &Type::Action) {
-                        panic!("do requires action expression");
+        for (command, span) in test.commands.iter().zip(test.cmd_spans.iter()) {
+            self.span = *span;
+            if let Err(e) = self.typecheck_cmd(command, test) {
+                self.errors.push(e);
+            }
+        }
+    }
+
+    fn typecheck_cmd(&mut self, command: &ReplCmd, test: &Test) -> Result<(), TypeError> {
+        self.numeric_typevars.clear(); // left by a command with a type error
+        match command {
+            ReplCmd::Do(expr) => {
+                // clients can only trigger what the service made public
+                let reactive_names = self.name_context.keys().cloned().collect();
+                for name in calc_touched_names(expr, &reactive_names) {
+                    if self.name_context.contains_key(&name) && !self.pub_names.contains(&name) {
+                        return Err(self
+                            .error(format!(
+                                "do {}: {:?} is not public in service {:?}",
+                                expr, name, test.name
+                            ))
+                            .with_note("declare it as `pub def` to use it here".to_string()));
                     }
                 }
-                ReplCmd::Assert(expr) => {
-                    let typ = self.infer_expr(expr);
-                    if !self.unify(&typ, &Type::Bool) {
-                        panic!("Assert statement requires bool expression");
-                    }
+                let typ = self.infer_expr(expr)?;
+                if !self.unify(&typ, &Type::Action) {
+                    return Err(self.error(format!(
+                        "do requires action expression, got {}",
+                        self.resolve(&typ)
+                    )));
+                }
+            }
+            ReplCmd::Assert(expr) => {
+                let typ = self.infer_expr(expr)?;
+                if !self.unify(&typ, &Type::Bool) {
+                    return Err(self.error(format!(
+                        "Assert statement requires bool expression, got {}",
+                        self.resolve(&typ)
+                    )));
                 }
             }
         }
+        self.default_numeric()
     }
 }
@@ -1,6 +1,7 @@
-use crate::ast::DataType;
+use crate::ast::{DataType, Field, TypeAnnot, Variant};
 use super::Type;
-use super::TypecheckEnv;
+use super::{TypeError, TypeScheme, TypecheckEnv};
+use crate::ast::Span;
 use std::{
     collections::{HashMap, HashSet},
     fmt::format,
@@ -8,36 +9,81 @@ use std::{
 };
 
 impl Type {
-    fn free_var(&self, typ_var_binded: &HashSet<String>) -> HashSet<String> {
+    /// type vars occurring in type, type vars bound by the accumulated
+    /// substitution are not followed, `resolve` the type first for that
+    pub fn free_var(&self) -> HashSet<String> {
         match self {
-            Type::Int | Type::Bool | Type::String | Type::Unit | Type::Action | Type::Vector(..) | Type::Row => HashSet::new(),
-            // Calculate free type var in function type
-            // e.g. (a, int, bool) -> b has free_var
-            // for convenience, we clone the whole type bindings from previous
-            // level, if perfomance matters, we can switch to a stack of type
-            // binds to efficiently maintain type bindings
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit | Type::Action | Type::Table(_) | Type::Adt(_) | Type::Row => HashSet::new(),
+            Type::Vector(typs) => typs.iter().flat_map(|typ| typ.free_var()).collect(),
+            Type::Record(fields) => fields.iter().flat_map(|(_, typ)| typ.free_var()).collect(),
+            Type::List(typ) => typ.free_var(),
             Type::Fun(params, ret) => {
-                let mut typ_var_binded: HashSet<String> = typ_var_binded.clone();
-                let new_binds = params.iter().filter_map(|typ| {
-                    if let Type::TypVar(name) = typ {
-                        Some(name.clone())
-                    } else {
-                        None
-                    }
-                });
-                typ_var_binded.extend(new_binds);
-                ret.free_var(&typ_var_binded)
-            }
-            Type::TypVar(x) => {
-                if typ_var_binded.contains(x) {
-                    HashSet::new()
-                } else {
-                    HashSet::from([x.clone()])
+                let mut free_vars = ret.free_var();
+                for param in params.iter() {
+                    free_vars.extend(param.free_var());
                 }
+                free_vars
             }
-            Type::Table(schema) => {
-                HashSet::new()
-            }
+            Type::TypVar(x) => HashSet::from([x.clone()]),
+        }
+    }
+
+    /// replace type vars by the types they are mapped to
+    pub fn subst(&self, substs: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::TypVar(name) => substs.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Fun(params, ret) => Type::Fun(
+                params.iter().map(|t| t.subst(substs)).collect(),
+                Box::new(ret.subst(substs)),
+            ),
+            Type::Vector(typs) => Type::Vector(typs.iter().map(|t| t.subst(substs)).collect()),
+            Type::Record(fields) => Type::Record(
+                fields.iter().map(|(name, t)| (name.clone(), t.subst(substs))).collect(),
+            ),
+            Type::List(typ) => Type::List(Box::new(typ.subst(substs))),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Type {
+    /// record type of fields, in any order
+    pub fn record(mut fields: Vec<(String, Type)>) -> Type {
+        fields.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+        Type::Record(fields)
+    }
+}
+
+impl From<&DataType> for Type {
+    fn from(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Bool => Type::Bool,
+            DataType::Number => Type::Int,
+            DataType::Float => Type::Float,
+            DataType::String => Type::String,
+        }
+    }
+}
+
+/// type vars of an annotation keep their name, see `annot_type`
+impl From<&TypeAnnot> for Type {
+    fn from(annot: &TypeAnnot) -> Self {
+        match annot {
+            TypeAnnot::Int => Type::Int,
+            TypeAnnot::Float => Type::Float,
+            TypeAnnot::Bool => Type::Bool,
+            TypeAnnot::String => Type::String,
+            TypeAnnot::Action => Type::Action,
+            TypeAnnot::Var(name) => Type::TypVar(name.clone()),
+            TypeAnnot::Fun(params, ret) => Type::Fun(
+                params.iter().map(Type::from).collect(),
+                Box::new(Type::from(ret.as_ref())),
+            ),
+            TypeAnnot::Record(fields) => Type::record(
+                fields.iter().map(|(name, annot)| (name.clone(), Type::from(annot))).collect(),
+            ),
+            TypeAnnot::Tuple(annots) => Type::Vector(annots.iter().map(Type::from).collect()),
+            TypeAnnot::List(annot) => Type::List(Box::new(Type::from(annot.as_ref()))),
         }
     }
 }
@@ -50,6 +96,24 @@ impl TypecheckEnv {
             name_context: HashMap::new(),
             typevar_id: 0,
             acc_subst: HashMap::new(),
+            numeric_typevars: vec![],
+            types: HashMap::new(),
+            srv_to_exports: HashMap::new(),
+            srv_to_types: HashMap::new(),
+            srv_to_private_names: HashMap::new(),
+            pub_names: HashSet::new(),
+            private_imports: HashMap::new(),
+            span: Span::default(),
+            errors: vec![],
+        }
+    }
+
+    /// type error at the decl or test command being checked
+    pub fn error(&self, message: String) -> TypeError {
+        TypeError {
+            span: self.span,
+            message,
+            notes: vec![],
         }
     }
 
@@ -74,10 +138,189 @@ impl TypecheckEnv {
         todo!()
     }
 
+    /// canonical form of a type, with type vars resolved
+    /// also inside function and vector types
+    pub fn resolve(&self, typ: &Type) -> Type {
+        match self.find(typ) {
+            Type::Fun(params, ret) => Type::Fun(
+                params.iter().map(|t| self.resolve(t)).collect(),
+                Box::new(self.resolve(&ret)),
+            ),
+            Type::Vector(typs) => Type::Vector(typs.iter().map(|t| self.resolve(t)).collect()),
+            Type::Record(fields) => Type::Record(
+                fields.iter().map(|(name, t)| (name.clone(), self.resolve(t))).collect(),
+            ),
+            Type::List(typ) => Type::List(Box::new(self.resolve(&typ))),
+            typ => typ,
+        }
+    }
+
+    /// replace each type var in typ by a fresh one (consistently, wrt renames)
+    pub fn rename_typevars(&mut self, typ: &Type, renames: &mut HashMap<String, Type>) -> Type {
+        match typ {
+            Type::TypVar(name) => {
+                if let Some(new_typ) = renames.get(name) {
+                    return new_typ.clone();
+                }
+                let new_typ = self.gen_typevar();
+                renames.insert(name.clone(), new_typ.clone());
+                new_typ
+            }
+            Type::Fun(params, ret) => Type::Fun(
+                params.iter().map(|t| self.rename_typevars(t, renames)).collect(),
+                Box::new(self.rename_typevars(ret, renames)),
+            ),
+            Type::Vector(typs) => {
+                Type::Vector(typs.iter().map(|t| self.rename_typevars(t, renames)).collect())
+            }
+            Type::Record(fields) => Type::Record(
+                fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), self.rename_typevars(t, renames)))
+                    .collect(),
+            ),
+            Type::List(typ) => Type::List(Box::new(self.rename_typevars(typ, renames))),
+            _ => typ.clone(),
+        }
+    }
+
+    /// type of an annotation, with its type vars replaced by fresh ones
+    /// (consistently, wrt renames)
+    pub fn annot_type(&mut self, annot: &TypeAnnot, renames: &mut HashMap<String, Type>) -> Type {
+        let typ = self.declared_types(&Type::from(annot));
+        self.rename_typevars(&typ, renames)
+    }
+
+    /// names of declared types in an annotation are not type vars,
+    /// e.g. `status` in `fn s: status => ..`
+    pub fn declared_types(&self, typ: &Type) -> Type {
+        match typ {
+            Type::TypVar(name) if self.types.contains_key(name) => Type::Adt(name.clone()),
+            Type::Fun(params, ret) => Type::Fun(
+                params.iter().map(|t| self.declared_types(t)).collect(),
+                Box::new(self.declared_types(ret)),
+            ),
+            Type::Vector(typs) => Type::Vector(typs.iter().map(|t| self.declared_types(t)).collect()),
+            Type::Record(fields) => Type::Record(
+                fields.iter().map(|(name, t)| (name.clone(), self.declared_types(t))).collect(),
+            ),
+            Type::List(typ) => Type::List(Box::new(self.declared_types(typ))),
+            typ => typ.clone(),
+        }
+    }
+
+    /// declared type of a variant, and the variant
+    pub fn lookup_variant(&self, name: &str) -> Option<(String, Variant)> {
+        self.types.iter().find_map(|(typ_name, variants)| {
+            let variant = variants.iter().find(|variant| variant.name == name)?;
+            Some((typ_name.clone(), variant.clone()))
+        })
+    }
+
+    /// types of the fields of a variant
+    pub fn field_types(&self, variant: &Variant) -> Vec<Type> {
+        variant.fields.iter().map(|field| self.declared_types(&Type::from(field))).collect()
+    }
+
+    /// type vars free in the type of some variable or reactive name,
+    /// which cannot be generalized
+    fn env_free_var(&self) -> HashSet<String> {
+        let mut free_vars = HashSet::new();
+        for typ in self.var_context.values() {
+            free_vars.extend(self.resolve(typ).free_var());
+        }
+        for scheme in self.name_context.values() {
+            let typ = self.resolve(&scheme.typ);
+            free_vars.extend(TypeScheme { vars: scheme.vars.clone(), typ }.free_var());
+        }
+        free_vars
+    }
+
+    /// quantify type vars of typ not free in the environment
+    pub fn generalize(&self, typ: &Type) -> TypeScheme {
+        let typ = self.resolve(typ);
+        let env_vars = self.env_free_var();
+        let mut vars = typ
+            .free_var()
+            .difference(&env_vars)
+            .cloned()
+            .collect::<Vec<_>>();
+        vars.sort();
+        TypeScheme { vars, typ }
+    }
+
+    /// type of scheme, with quantified type vars replaced by fresh ones
+    pub fn instantiate(&mut self, scheme: &TypeScheme) -> Type {
+        let substs = scheme
+            .vars
+            .iter()
+            .map(|var| (var.clone(), self.gen_typevar()))
+            .collect();
+        scheme.typ.subst(&substs)
+    }
+
+    /// unify a type with a scheme without instantiating it, i.e. typ must
+    /// be at least as general as the scheme: its quantified vars can only
+    /// be unified with distinct type vars not free in the environment
+    pub fn unify_general(&mut self, scheme: &TypeScheme, typ: &Type) -> bool {
+        let fresh_vars = scheme
+            .vars
+            .iter()
+            .map(|_| self.gen_typevar())
+            .collect::<Vec<_>>();
+        let substs = zip(scheme.vars.iter().cloned(), fresh_vars.iter().cloned()).collect();
+        if !self.unify(&scheme.typ.subst(&substs), typ) {
+            return false;
+        }
+
+        let env_vars = self.env_free_var();
+        let mut bound_to = HashSet::new();
+        fresh_vars.iter().all(|var| match self.find(var) {
+            Type::TypVar(name) => !env_vars.contains(&name) && bound_to.insert(name),
+            _ => false,
+        })
+    }
+
+    /// look up type of a variable or reactive name,
+    /// non-public names of imported services are reported as such
+    pub fn lookup(&mut self, ident: &String) -> Result<Type, TypeError> {
+        if let Some(typ) = self.var_context.get(ident) {
+            return Ok(typ.clone());
+        }
+        if let Some(scheme) = self.name_context.get(ident).cloned() {
+            return Ok(self.instantiate(&scheme));
+        }
+        if let Some(srv_name) = self.private_imports.get(ident) {
+            return Err(self
+                .error(format!(
+                    "{:?} is not public in imported service {:?}",
+                    ident, srv_name
+                ))
+                .with_note("declare it as `pub def` to use it here".to_string()));
+        }
+        Err(self.error(format!("cannot find var {:?} in context", ident)))
+    }
+
+    /// schema of a table or table-valued def
+    pub fn lookup_table(&mut self, name: &String) -> Result<Vec<Field>, TypeError> {
+        let typ = self.lookup(name)?;
+        match self.find(&typ) {
+            Type::Table(schema) => Ok(schema),
+            Type::TypVar(_) => Err(self
+                .error(format!("type of {} is not known to be a table", name))
+                .with_note("only tables and table-valued defs can be used as tables".to_string())),
+            typ => Err(self.error(format!(
+                "{} is not a table, it has type {}",
+                name,
+                self.resolve(&typ)
+            ))),
+        }
+    }
+
     // union-find based unification
     pub fn find(&self, typ: &Type) -> Type {
         match typ {
-            Type::Int | Type::Bool | Type::String | Type::Unit | Type::Action | Type::Fun(_, _) | Type::Table(_)  | Type::Vector(..) | Type::Row => typ.clone(),
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit | Type::Action | Type::Fun(_, _) | Type::Table(_)  | Type::Vector(..) | Type::Record(_) | Type::List(_) | Type::Adt(_) | Type::Row => typ.clone(),
 
             Type::TypVar(name) => {
                 let canonical_typ = self
@@ -95,9 +338,20 @@ impl TypecheckEnv {
         }
     }
 
+    /// occurs check: a type var cannot be bound to a type containing it,
+    /// e.g. a = a -> int has no (finite) solution
+    fn bind_typevar(&mut self, name: String, typ: Type) -> bool {
+        if self.resolve(&typ).free_var().contains(&name) {
+            return false;
+        }
+        self.acc_subst.insert(name, typ);
+        true
+    }
+
     pub fn unify(&mut self, typ1: &Type, typ2: &Type) -> bool {
         match (typ1, typ2) {
             (Type::Int, Type::Int)
+            | (Type::Float, Type::Float)
             | (Type::Bool, Type::Bool)
             | (Type::String, Type::String)
             | (Type::Unit, Type::Unit)
@@ -115,35 +369,120 @@ impl TypecheckEnv {
                 let cano_typ1 = self.find(typ1);
                 let cano_typ2 = self.find(typ2);
 
-                if let Type::TypVar(subst_by_name1) = cano_typ1 {
-                    self.acc_subst.insert(subst_by_name1, cano_typ2);
+                if cano_typ1 == cano_typ2 {
                     true
+                } else if let Type::TypVar(subst_by_name1) = cano_typ1 {
+                    self.bind_typevar(subst_by_name1, cano_typ2)
                 } else if let Type::TypVar(subst_by_name2) = cano_typ2 {
-                    self.acc_subst.insert(subst_by_name2, cano_typ1);
-                    true
+                    self.bind_typevar(subst_by_name2, cano_typ1)
                 } else {
                     self.unify(&cano_typ1, &cano_typ2)
                 }
             }
 
-            (Type::Table(schema1), Type::Table(schema2)) => {
-                true
+            // same columns, in the same order
+            (Type::Table(schema1), Type::Table(schema2)) => schema1 == schema2,
+
+            (Type::Vector(typs1), Type::Vector(typs2)) => {
+                typs1.len() == typs2.len()
+                    && zip(typs1, typs2).all(|(typ1, typ2)| self.unify(typ1, typ2))
             }
 
-            (Type::Table(schema), Type::Vector(elements)) => {
-                for (field, item) in schema.iter().zip(elements.iter()) {
-                    match field.type_ {
-                        DataType::Bool => self.unify(&Type::Bool, item),
-                        DataType::Number => self.unify(&Type::Int, item),
-                        DataType::String => self.unify(&Type::String, item)
-                    };
-                }
-                true
+            (Type::List(typ1), Type::List(typ2)) => self.unify(typ1, typ2),
+
+            // declared types are equal by name
+            (Type::Adt(name1), Type::Adt(name2)) => name1 == name2,
+
+            // same field names, fields are sorted by name
+            (Type::Record(fields1), Type::Record(fields2)) => {
+                fields1.len() == fields2.len()
+                    && zip(fields1, fields2).all(|((name1, typ1), (name2, typ2))| {
+                        name1 == name2 && self.unify(typ1, typ2)
+                    })
+            }
+
+            // a table and a vector of records, e.g. {{1, "a"}, {2, "b"}}
+            (Type::Table(schema), Type::Vector(records))
+            | (Type::Vector(records), Type::Table(schema)) => {
+                records.iter().all(|record| match self.find(record) {
+                    Type::Vector(vals) => {
+                        vals.len() == schema.len()
+                            && zip(schema.iter(), vals.iter())
+                                .all(|(field, val)| self.unify(&Type::from(&field.type_), val))
+                    }
+                    _ => false,
+                })
             }
 
             _ => false,
         }
     }
+
+    /// type of arithmetic on operands of typ1 and typ2: int with int is int,
+    /// float with int or float is float, an operand of unknown type takes
+    /// the type of the other one; if both are unknown they are unified,
+    /// and left to be found int or float later in the decl
+    pub fn unify_numeric(&mut self, typ1: &Type, typ2: &Type) -> Result<Type, TypeError> {
+        let (typ1, typ2) = (self.find(typ1), self.find(typ2));
+        let expected = if typ1 == Type::Float || typ2 == Type::Float { "float" } else { "int" };
+        for (side, typ) in [("left hand side", &typ1), ("right hand side", &typ2)] {
+            if !matches!(typ, Type::Int | Type::Float | Type::TypVar(_)) {
+                return Err(self.error(format!("cannot unify {} {} and {}", side, self.resolve(typ), expected)));
+            }
+        }
+
+        match (&typ1, &typ2) {
+            (Type::TypVar(_), Type::TypVar(_)) => {
+                self.unify(&typ1, &typ2);
+                self.numeric_typevars.push(typ1.clone());
+                Ok(typ1)
+            }
+            (Type::TypVar(_), known) | (known, Type::TypVar(_)) => {
+                let known = known.clone();
+                self.unify(&typ1, &typ2);
+                Ok(known)
+            }
+            (Type::Int, Type::Int) => Ok(Type::Int),
+            _ => Ok(Type::Float),
+        }
+    }
+
+    /// type of `+` and of comparisons, on numbers as `unify_numeric`,
+    /// or on strings if either operand is one
+    pub fn unify_ordered(&mut self, typ1: &Type, typ2: &Type) -> Result<Type, TypeError> {
+        if self.find(typ1) != Type::String && self.find(typ2) != Type::String {
+            return self.unify_numeric(typ1, typ2);
+        }
+        for (side, typ) in [("left hand side", typ1), ("right hand side", typ2)] {
+            if !self.unify(typ, &Type::String) {
+                return Err(self.error(format!("cannot unify {} {} and string", side, self.resolve(typ))));
+            }
+        }
+        Ok(Type::String)
+    }
+}
+
+impl TypecheckEnv {
+    /// numeric type vars still unknown once a decl or command is inferred
+    /// are int, e.g. `fn x, y => x + y` is `(int, int) -> int`, while in
+    /// `fold(t.price, fn acc, p => acc + p, 0.0)` they are found float
+    pub fn default_numeric(&mut self) -> Result<(), TypeError> {
+        for typ in std::mem::take(&mut self.numeric_typevars) {
+            match self.find(&typ) {
+                Type::Int | Type::Float => {}
+                Type::TypVar(_) => {
+                    self.unify(&typ, &Type::Int);
+                }
+                other => {
+                    return Err(self.error(format!(
+                        "cannot unify {} and int or float",
+                        self.resolve(&other)
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for TypecheckEnv {
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::ast::Expr;
+use crate::ast::{Expr, RowEdit, Stmt};
 
 impl Expr {
     /// alpha renaming of expression e
@@ -11,7 +11,7 @@ impl Expr {
         renames: &HashMap<String, String>,
     ) {
         match self {
-            Expr::Number { .. } | Expr::Bool { .. } | Expr::String { .. } => {}
+            Expr::Number { .. } | Expr::Float { .. } | Expr::Bool { .. } | Expr::String { .. } => {}
             Expr::Variable { ident } => {
                 if !var_binded.contains(ident) && renames.contains_key(ident) {
                     *ident = renames.get(ident).unwrap().clone();
@@ -20,7 +20,7 @@ impl Expr {
             Expr::KeyVal { value , .. } => {
                 value.alpha_rename(var_binded, renames);
             }
-            Expr::Vector { val } => {
+            Expr::Vector { val } | Expr::List { val } => {
                 for item in val {
                     item.alpha_rename(var_binded, renames);
                 }
@@ -37,11 +37,25 @@ impl Expr {
                 expr1.alpha_rename(var_binded, renames);
                 expr2.alpha_rename(var_binded, renames);
             }
-            Expr::Func { params, body } => {
+            Expr::Func { params, body, .. } => {
                 let mut new_binds = var_binded.clone();
                 new_binds.extend(params.iter().cloned());
                 body.alpha_rename(&new_binds, renames);
             }
+            Expr::Let { name, val, body, .. } => {
+                val.alpha_rename(var_binded, renames);
+                let mut new_binds = var_binded.clone();
+                new_binds.insert(name.clone());
+                body.alpha_rename(&new_binds, renames);
+            }
+            Expr::Match { expr, arms } => {
+                expr.alpha_rename(var_binded, renames);
+                for arm in arms {
+                    let mut new_binds = var_binded.clone();
+                    new_binds.extend(arm.pattern.binds());
+                    arm.body.alpha_rename(&new_binds, renames);
+                }
+            }
             Expr::FuncApply { func, args } => {
                 func.alpha_rename(var_binded, renames);
                 for arg in args {
@@ -49,7 +63,7 @@ impl Expr {
                 }
             }
 
-            Expr::Action { assns, inserts} => {
+            Expr::Action { assns, inserts, edits } => {
                 for assn in assns {
                     // dest should never be renamed, not influenced by capture
                     // let dest = &mut assn.dest;
@@ -60,6 +74,17 @@ impl Expr {
                 }
                 for insert in inserts {
                     insert.row.alpha_rename(var_binded, renames);       // since rows can contain variables
+                    if let Some(cond) = &mut insert.cond {
+                        cond.alpha_rename(var_binded, renames);
+                    }
+                }
+                for edit in edits {
+                    edit.alpha_rename(var_binded, renames);
+                }
+            }
+            Expr::ActionBlock { stmts } => {
+                for stmt in stmts {
+                    stmt.alpha_rename(var_binded, renames);
                 }
             }
             Expr::Select { where_clause, .. } => {
@@ -70,13 +95,65 @@ impl Expr {
                     record.alpha_rename(var_binded, renames);
                 }
             }
-            Expr::TableColumn { .. } => {},
-            Expr::Fold { args } => {
+            Expr::TableColumn { table_name, .. } => {
+                // a table is never renamed, a record named so may be
+                if !var_binded.contains(table_name) && renames.contains_key(table_name) {
+                    *table_name = renames.get(table_name).unwrap().clone();
+                }
+            }
+            Expr::RecordField { expr, .. } | Expr::TupleIndex { expr, .. } => {
+                expr.alpha_rename(var_binded, renames);
+            }
+            Expr::Fold { args } | Expr::ListOp { args, .. } | Expr::Variant { args, .. } => {
                 for arg in args {
                     arg.alpha_rename(var_binded, renames);
                 }
             },
+            Expr::Spanned { expr, .. } => expr.alpha_rename(var_binded, renames),
         
         }
     }
 }
+
+impl Stmt {
+    pub fn alpha_rename(
+        &mut self,
+        var_binded: &HashSet<String>,
+        renames: &HashMap<String, String>,
+    ) {
+        match self {
+            // like in an action, dest is never renamed
+            Stmt::Assn(assn) => assn.src.alpha_rename(var_binded, renames),
+            Stmt::Insert(insert) => insert.row.alpha_rename(var_binded, renames),
+            Stmt::Edit(edit) => edit.alpha_rename(var_binded, renames),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                cond.alpha_rename(var_binded, renames);
+                for stmt in then_stmts.iter_mut().chain(else_stmts) {
+                    stmt.alpha_rename(var_binded, renames);
+                }
+            }
+            Stmt::Do(action) => action.alpha_rename(var_binded, renames),
+        }
+    }
+}
+
+impl RowEdit {
+    pub fn alpha_rename(
+        &mut self,
+        var_binded: &HashSet<String>,
+        renames: &HashMap<String, String>,
+    ) {
+        match self {
+            RowEdit::Update { sets, where_clause, .. } => {
+                for set in sets {
+                    set.src.alpha_rename(var_binded, renames);
+                }
+                where_clause.alpha_rename(var_binded, renames);
+            }
+            RowEdit::Delete { where_clause, .. } => where_clause.alpha_rename(var_binded, renames),
+        }
+        if let Some(cond) = self.cond_mut() {
+            cond.alpha_rename(var_binded, renames);
+        }
+    }
+}
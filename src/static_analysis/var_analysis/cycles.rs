@@ -0,0 +1,172 @@
+//! static check for dependency cycles, run before any service is allocated:
+//! between defs of a service, e.g. `def a = b + 1; def b = a;`, and between
+//! services importing each other
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Decl, Prog, Service, Span};
+use crate::error::Diagnostic;
+
+use super::DependAnalysis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleKind {
+    Defs,    // defs of a service depending on each other
+    Imports, // services importing each other
+}
+
+/// a cycle, e.g. `a -> b -> c -> a`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependCycle {
+    pub kind: CycleKind,
+    pub path: Vec<String>, // starts and ends with the same name
+    pub spans: Vec<Span>,  // of the decl of each name on the path, or of its import
+}
+
+impl DependCycle {
+    /// cycle of names declared at spans, rotated to start at the first
+    /// declared one, so the same cycle is always reported the same way
+    fn new(kind: CycleKind, mut path: Vec<String>, mut spans: Vec<Span>) -> Self {
+        path.pop();
+        let first = (0..spans.len()).min_by_key(|i| spans[*i].start).unwrap_or(0);
+        path.rotate_left(first);
+        spans.rotate_left(first);
+        path.push(path[0].clone());
+        DependCycle { kind, path, spans }
+    }
+
+    /// render against the source the program is parsed from,
+    /// pointing at the first decl, and at the others in notes
+    pub fn to_diagnostic(&self, file: &str, source: &str) -> Diagnostic {
+        let message = match self.kind {
+            CycleKind::Defs => format!("dependency cycle: {}", self.path.join(" -> ")),
+            CycleKind::Imports => format!("import cycle: {}", self.path.join(" -> ")),
+        };
+        let mut diag = Diagnostic::new(file, source, self.spans[0], message);
+
+        for (i, span) in self.spans.iter().enumerate().skip(1) {
+            let at = Diagnostic::new(file, source, *span, String::new());
+            let note = match self.kind {
+                CycleKind::Defs => format!("{} is declared at", self.path[i]),
+                CycleKind::Imports => format!("{} imports {} at", self.path[i], self.path[i + 1]),
+            };
+            diag = diag.with_note(format!("{} {}:{}:{}", note, file, at.line, at.column));
+        }
+        if self.kind == CycleKind::Defs && self.path.len() == 2 {
+            diag = diag.with_note(
+                "only a def whose value is a function can refer to itself, in its body".to_string(),
+            );
+        }
+        diag
+    }
+}
+
+/// all cycles between services, and between defs of each service
+pub fn check_cycles(prog: &Prog) -> Result<(), Vec<DependCycle>> {
+    let mut cycles = import_cycles(prog);
+    for srv in prog.services.iter() {
+        cycles.extend(def_cycles(srv));
+    }
+
+    if cycles.is_empty() {
+        Ok(())
+    } else {
+        cycles.sort_by_key(|cycle| cycle.spans[0].start);
+        Err(cycles)
+    }
+}
+
+/// cycles found by the dependency analysis of srv, each found cycle has
+/// an edge removed from the graph before looking for the next one
+fn def_cycles(srv: &Service) -> Vec<DependCycle> {
+    let decl_spans = srv
+        .decls
+        .iter()
+        .filter_map(|decl| match decl {
+            Decl::VarDecl { name, span, .. }
+            | Decl::DefDecl { name, span, .. }
+            | Decl::TableDecl { name, span, .. } => Some((name, *span)),
+            Decl::Import { .. } | Decl::TypeDecl { .. } => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    // imported names are not in the graph, they cannot be on a cycle of srv
+    let mut dep_graph = DependAnalysis::new(srv, &HashMap::new()).dep_graph;
+    let mut cycles = vec![];
+    loop {
+        let mut da = DependAnalysis::new(srv, &HashMap::new());
+        da.dep_graph = dep_graph.clone();
+        let Err(path) = da.calc_dep_vars() else {
+            return cycles;
+        };
+
+        let [.., from, to] = path.as_slice() else {
+            unreachable!("a cycle has at least one edge")
+        };
+        dep_graph.get_mut(from).expect("def on a cycle should be in the graph").remove(to);
+
+        let spans = path[..path.len() - 1]
+            .iter()
+            .map(|name| decl_spans[name])
+            .collect();
+        cycles.push(DependCycle::new(CycleKind::Defs, path, spans));
+    }
+}
+
+/// each cycle of services importing each other, found by dfs from
+/// services in declaration order
+pub fn import_cycles(prog: &Prog) -> Vec<DependCycle> {
+    let srvs = prog
+        .services
+        .iter()
+        .map(|srv| (&srv.name, srv))
+        .collect::<HashMap<_, _>>();
+
+    let mut cycles = vec![];
+    let mut visited = HashSet::new();
+    for srv in prog.services.iter() {
+        import_dfs(&srvs, srv, &mut visited, &mut vec![], &mut cycles);
+    }
+    cycles
+}
+
+fn import_dfs<'a>(
+    srvs: &HashMap<&String, &'a Service>,
+    srv: &'a Service,
+    visited: &mut HashSet<String>,
+    path: &mut Vec<&'a Service>,
+    cycles: &mut Vec<DependCycle>,
+) {
+    if let Some(start) = path.iter().position(|s| s.name == srv.name) {
+        let mut names = path[start..].iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        names.push(srv.name.clone());
+        // span of the import of the next service on the cycle
+        let spans = names
+            .windows(2)
+            .map(|pair| import_span(srvs[&pair[0]], &pair[1]))
+            .collect();
+        cycles.push(DependCycle::new(CycleKind::Imports, names, spans));
+        return;
+    }
+    if !visited.insert(srv.name.clone()) {
+        return;
+    }
+
+    path.push(srv);
+    for import in srv.imports() {
+        // non-existing services are reported by the typechecker
+        if let Some(imported) = srvs.get(&import) {
+            import_dfs(srvs, imported, visited, path, cycles);
+        }
+    }
+    path.pop();
+}
+
+fn import_span(srv: &Service, imported: &String) -> Span {
+    srv.decls
+        .iter()
+        .find_map(|decl| match decl {
+            Decl::Import { srv_name, span } if srv_name == imported => Some(*span),
+            _ => None,
+        })
+        .expect("imported service should be imported by an import decl")
+}
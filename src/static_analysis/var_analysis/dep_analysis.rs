@@ -5,10 +5,14 @@ use crate::ast;
 use super::DependAnalysis;
 
 impl DependAnalysis {
-    pub fn new(ast: &ast::Service) -> DependAnalysis {
+    pub fn new(
+        ast: &ast::Service,
+        imported: &HashMap<String, HashSet<String>>,
+    ) -> DependAnalysis {
         let mut vars: HashSet<String> = HashSet::new();
         let mut defs: HashSet<String> = HashSet::new();
-        let mut reactive_names = HashSet::new();
+        // imported names are in scope from the start of the service
+        let mut reactive_names: HashSet<String> = imported.keys().cloned().collect();
         let mut tables: HashSet<String> = HashSet::new();
 
         let mut dep_graph: HashMap<String, HashSet<String>> = HashMap::new();
@@ -25,7 +29,11 @@ impl DependAnalysis {
                     reactive_names.insert(name.clone());
                     // we calculated all reactive names so far
 
-                    let deps = val.free_var(&reactive_names, &HashSet::new());
+                    let mut deps = val.free_var(&reactive_names, &HashSet::new());
+                    // a recursive function is not subscribed to itself
+                    if val.is_rec_fn(name) {
+                        deps.remove(name);
+                    }
                     dep_graph.insert(name.clone(), deps);
                 }
                 ast::Decl::TableDecl { name, .. } => {
@@ -40,6 +48,7 @@ impl DependAnalysis {
             vars,
             defs,
             tables,
+            imported: imported.clone(),
             dep_graph,
             topo_order: Vec::new(),
             dep_transtive: HashMap::new(),
@@ -52,41 +61,63 @@ impl DependAnalysis {
     /// # Arguments
     /// * `vars` - set of variable names (no dependencies).
     /// * `tables` - set of table names (no dependencies).
+    /// * `imported` - names from other services, with their transitive vars
+    ///    already calculated by the service declaring them.
     /// * `visited` - set of visited nodes in dfs.
+    /// * `path` - names being visited, from the root of the dfs to `name`.
     /// * `calced` - map of def to their computed dependencies, only appeared
     ///    when finished computing for a def.
-    /// # Panics
-    /// * panic if a cycle is detected in the graph.
+    /// # Errors
+    /// * the cycle, e.g. `[a, b, a]`, if a cycle is detected in the graph.
     fn dfs_helper(
         graph: &HashMap<String, HashSet<String>>,
         vars: &HashSet<String>,
         tables: &HashSet<String>,
+        imported: &HashMap<String, HashSet<String>>,
         visited: &mut HashSet<String>,
+        path: &mut Vec<String>,
         finished: &mut Vec<String>,
         calced: &mut HashMap<String, HashSet<String>>,
         name: &String
-    ) {
+    ) -> Result<(), Vec<String>> {
         if calced.contains_key(name) {
-            return;
+            return Ok(());
         }
 
+        // visited but not calced, name is on the path
         if visited.contains(name) {
-            panic!("Cycle detected in dependency graph of var and defs");
+            let start = path.iter().position(|n| n == name).expect("visited name should be on path");
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.clone());
+            return Err(cycle);
         }
 
         visited.insert(name.clone());
+        // if visit imported name, its (remote) vars are already known, and
+        // it has no actor to be allocated locally
+        if let Some(remote_vars) = imported.get(name) {
+            calced.insert(name.clone(), remote_vars.clone());
+            return Ok(());
+        }
+
         // if visit var, notice var is transitively depend on itself
         if vars.contains(name) || tables.contains(name) {
             calced.insert(name.clone(), HashSet::from([name.clone()]));
             finished.push(name.clone());
-            return;
+            return Ok(());
         }
 
+        // names not declared (reported by typecheck) depend on nothing
+        let Some(deps) = graph.get(name) else {
+            calced.insert(name.clone(), HashSet::new());
+            return Ok(());
+        };
+
         // else visit def
+        path.push(name.clone());
         let mut dep = HashSet::new();
-
-        for dep_name in graph.get(name).expect(&format!("No such name in dep graph: {}", name)) {
-            Self::dfs_helper(graph, vars, tables, visited, finished, calced, dep_name);
+        for dep_name in deps {
+            Self::dfs_helper(graph, vars, tables, imported, visited, path, finished, calced, dep_name)?;
             dep.extend(
                 calced
                     .get(dep_name)
@@ -98,12 +129,16 @@ impl DependAnalysis {
             );
             dep.insert(dep_name.clone());
         }
+        path.pop();
 
         calced.insert(name.clone(), dep);
         finished.push(name.clone());
+        Ok(())
     }
 
-    pub fn calc_dep_vars(&mut self) {
+    /// calculate transitive dependencies and topological order,
+    /// or return a dependency cycle, e.g. `[a, b, a]`
+    pub fn calc_dep_vars(&mut self) -> Result<(), Vec<String>> {
         let mut visited = HashSet::new();
 
         for name in self.vars.iter().chain(self.defs.iter().chain(self.tables.iter())) {
@@ -111,14 +146,17 @@ impl DependAnalysis {
                 &self.dep_graph,
                 &self.vars,
                 &self.tables,
+                &self.imported,
                 &mut visited,
+                &mut vec![],
                 &mut self.topo_order,
                 &mut self.dep_transtive,
                 name
-            );
+            )?;
         }
 
-        let vars_and_tables: HashSet<_> = self.vars.union(&self.tables).cloned().collect();
+        let mut vars_and_tables: HashSet<_> = self.vars.union(&self.tables).cloned().collect();
+        vars_and_tables.extend(self.imported.values().flatten().cloned());
         for name in self.vars.iter().chain(self.defs.iter().chain(self.tables.iter())) {
             self.dep_vars.insert(
                 name.clone(),
@@ -130,5 +168,9 @@ impl DependAnalysis {
                     .collect()
             );
         }
+        for (name, remote_vars) in self.imported.iter() {
+            self.dep_vars.insert(name.clone(), remote_vars.clone());
+        }
+        Ok(())
     }
 }
@@ -9,13 +9,18 @@ use std::{
 use crate::ast;
 
 pub mod alpha_rename;
+pub mod cycles;
 pub mod dep_analysis;
+pub mod qualify;
 pub mod read_write;
 
 pub struct DependAnalysis {
     pub vars: HashSet<String>,
     pub defs: HashSet<String>,
     pub tables: HashSet<String>,
+    // names imported from other services, to their transitively dependent vars
+    // (remote vars are recorded as depending on themselves)
+    pub imported: HashMap<String, HashSet<String>>,
     pub dep_graph: HashMap<String, HashSet<String>>,
     pub topo_order: Vec<String>, // topological order of vars/defs
     // transitively dependent vars/defs of a name
@@ -53,16 +58,67 @@ impl Display for DependAnalysis {
     }
 }
 
-pub fn calc_dep_srv(ast: &ast::Service) -> DependAnalysis {
-    let mut da = DependAnalysis::new(ast);
-    da.calc_dep_vars();
+/// dependency analysis of a service, cycles are reported by `cycles`
+/// before any service is allocated
+pub fn calc_dep_srv(
+    ast: &ast::Service,
+    imported: &HashMap<String, HashSet<String>>,
+) -> Result<DependAnalysis, String> {
+    let mut da = DependAnalysis::new(ast, imported);
+    da.calc_dep_vars().map_err(|cycle| {
+        format!("dependency cycle in service {}: {}", ast.name, cycle.join(" -> "))
+    })?;
     //println!("{}", da);
-    da
+    Ok(da)
 }
 
-pub fn calc_dep_prog(ast: &ast::Prog) {
+pub fn calc_dep_prog(ast: &ast::Prog) -> Result<(), String> {
     for srv in ast.services.iter() {
-        let da = calc_dep_srv(srv);
+        let da = calc_dep_srv(srv, &HashMap::new())?;
         println!("{}", da);
     }
+    Ok(())
+}
+
+/// order services such that every service comes after the services it imports,
+/// so that importers can be typechecked and allocated against their imports
+pub fn calc_srv_order(ast: &ast::Prog) -> Result<Vec<&ast::Service>, String> {
+    let srv_names: HashSet<&String> = ast.services.iter().map(|srv| &srv.name).collect();
+    for srv in ast.services.iter() {
+        for import in srv.imports() {
+            if !srv_names.contains(&import) {
+                return Err(format!(
+                    "service {} imports non-existing service {}",
+                    srv.name, import
+                ));
+            }
+        }
+    }
+
+    let mut ordered: Vec<&ast::Service> = Vec::new();
+    let mut done: HashSet<String> = HashSet::new();
+    while ordered.len() < ast.services.len() {
+        let ready = ast.services.iter().find(|srv| {
+            !done.contains(&srv.name) && srv.imports().iter().all(|i| done.contains(i))
+        });
+        match ready {
+            Some(srv) => {
+                done.insert(srv.name.clone());
+                ordered.push(srv);
+            }
+            None => {
+                let pending = ast
+                    .services
+                    .iter()
+                    .filter(|srv| !done.contains(&srv.name))
+                    .map(|srv| srv.name.clone())
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "cyclic imports between services: {}",
+                    pending.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(ordered)
 }
@@ -0,0 +1,213 @@
+//! runtime names of a service
+//!
+//! private names of a service, i.e. its vars, tables and non-pub defs, are
+//! qualified by the service at runtime, e.g. var `x` of service `a` is
+//! `a::x`: a service importing `a` reaches the actors behind `x` (say to
+//! lock it when reading a pub def of `a`) without `x` colliding with its own
+//! names. pub defs keep their names, they are what importers bind.
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Decl, Expr, RowEdit, Service, Stmt};
+
+/// name as declared in its service, of a runtime name of any service
+pub fn source_name(name: &str) -> String {
+    name.rsplit_once("::").map_or(name, |(_, name)| name).to_string()
+}
+
+/// text shown to developers and clients, e.g. an error evaluating an expr,
+/// with runtime names of any service back to their source names: `::` is
+/// never in source names
+pub fn source_text(text: &str) -> String {
+    let mut parts = text.split("::").collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    let mut source = String::new();
+    for part in parts {
+        source += part.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    }
+    source + last
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Namespace {
+    pub renames: HashMap<String, String>, // private name to its runtime name
+    pub columns: HashMap<String, HashSet<String>>, // table to its columns
+}
+
+impl Namespace {
+    pub fn new(srv: &Service) -> Self {
+        let renames = srv
+            .private_names()
+            .into_iter()
+            .map(|name| (name.clone(), format!("{}::{}", srv.name, name)))
+            .collect();
+        let columns = srv
+            .decls
+            .iter()
+            .filter_map(|decl| match decl {
+                Decl::TableDecl { name, fields, .. } => {
+                    Some((name.clone(), fields.iter().map(|field| field.name.clone()).collect()))
+                }
+                _ => None,
+            })
+            .collect();
+        Namespace { renames, columns }
+    }
+
+    pub fn qualify_srv(&self, srv: &Service) -> Service {
+        let mut srv = srv.clone();
+        for decl in srv.decls.iter_mut() {
+            match decl {
+                Decl::VarDecl { name, val, .. } | Decl::DefDecl { name, val, .. } => {
+                    self.rename(name);
+                    self.qualify(val, &HashSet::new());
+                }
+                Decl::TableDecl { name, .. } => self.rename(name),
+                Decl::Import { .. } | Decl::TypeDecl { .. } => {}
+            }
+        }
+        srv
+    }
+
+    /// qualify names of an expr of the service, e.g. an assert on it
+    pub fn qualify_expr(&self, expr: &mut Expr) {
+        self.qualify(expr, &HashSet::new());
+    }
+
+    fn rename(&self, name: &mut String) {
+        if let Some(runtime_name) = self.renames.get(name) {
+            *name = runtime_name.clone();
+        }
+    }
+
+    fn rename_free(&self, name: &mut String, var_binded: &HashSet<String>) {
+        if !var_binded.contains(name) {
+            self.rename(name);
+        }
+    }
+
+    /// names bound in a where clause or new values of `table`, its columns
+    fn row_binds(&self, table_name: &String, var_binded: &HashSet<String>) -> HashSet<String> {
+        let mut new_binds = var_binded.clone();
+        new_binds.extend(self.columns.get(table_name).into_iter().flatten().cloned());
+        new_binds
+    }
+
+    fn qualify(&self, expr: &mut Expr, var_binded: &HashSet<String>) {
+        match expr {
+            Expr::Number { .. } | Expr::Float { .. } | Expr::Bool { .. } | Expr::String { .. } => {}
+            Expr::Variable { ident } => self.rename_free(ident, var_binded),
+            Expr::KeyVal { value, .. } => self.qualify(value, var_binded),
+            Expr::Vector { val } | Expr::List { val } => {
+                val.iter_mut().for_each(|item| self.qualify(item, var_binded))
+            }
+            Expr::Unop { expr, .. } => self.qualify(expr, var_binded),
+            Expr::Binop { expr1, expr2, .. } => {
+                self.qualify(expr1, var_binded);
+                self.qualify(expr2, var_binded);
+            }
+            Expr::If { cond, expr1, expr2 } => {
+                self.qualify(cond, var_binded);
+                self.qualify(expr1, var_binded);
+                self.qualify(expr2, var_binded);
+            }
+            Expr::Func { params, body, .. } => {
+                let mut new_binds = var_binded.clone();
+                new_binds.extend(params.iter().cloned());
+                self.qualify(body, &new_binds);
+            }
+            Expr::Let { name, val, body, .. } => {
+                self.qualify(val, var_binded);
+                let mut new_binds = var_binded.clone();
+                new_binds.insert(name.clone());
+                self.qualify(body, &new_binds);
+            }
+            Expr::Match { expr, arms } => {
+                self.qualify(expr, var_binded);
+                for arm in arms {
+                    let mut new_binds = var_binded.clone();
+                    new_binds.extend(arm.pattern.binds());
+                    self.qualify(&mut arm.body, &new_binds);
+                }
+            }
+            Expr::FuncApply { func, args } => {
+                self.qualify(func, var_binded);
+                args.iter_mut().for_each(|arg| self.qualify(arg, var_binded));
+            }
+            Expr::Action { assns, inserts, edits } => {
+                for assn in assns {
+                    self.rename(&mut assn.dest);
+                    self.qualify(&mut assn.src, var_binded);
+                }
+                for insert in inserts {
+                    self.rename(&mut insert.table_name);
+                    self.qualify(&mut insert.row, var_binded);
+                    if let Some(cond) = &mut insert.cond {
+                        self.qualify(cond, var_binded);
+                    }
+                }
+                edits.iter_mut().for_each(|edit| self.qualify_edit(edit, var_binded));
+            }
+            Expr::ActionBlock { stmts } => {
+                stmts.iter_mut().for_each(|stmt| self.qualify_stmt(stmt, var_binded))
+            }
+            Expr::Select { table_name, where_clause, .. } => {
+                let new_binds = self.row_binds(table_name, var_binded);
+                self.qualify(where_clause, &new_binds);
+                self.rename_free(table_name, var_binded);
+            }
+            Expr::Table { records, .. } => {
+                records.iter_mut().for_each(|record| self.qualify(record, var_binded))
+            }
+            Expr::TableColumn { table_name, .. } => self.rename_free(table_name, var_binded),
+            Expr::RecordField { expr, .. } | Expr::TupleIndex { expr, .. } => {
+                self.qualify(expr, var_binded)
+            }
+            Expr::Fold { args } | Expr::ListOp { args, .. } | Expr::Variant { args, .. } => {
+                args.iter_mut().for_each(|arg| self.qualify(arg, var_binded))
+            }
+            Expr::Spanned { expr, .. } => self.qualify(expr, var_binded),
+        }
+    }
+
+    fn qualify_stmt(&self, stmt: &mut Stmt, var_binded: &HashSet<String>) {
+        match stmt {
+            Stmt::Assn(assn) => {
+                self.rename(&mut assn.dest);
+                self.qualify(&mut assn.src, var_binded);
+            }
+            Stmt::Insert(insert) => {
+                self.rename(&mut insert.table_name);
+                self.qualify(&mut insert.row, var_binded);
+            }
+            Stmt::Edit(edit) => self.qualify_edit(edit, var_binded),
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                self.qualify(cond, var_binded);
+                for stmt in then_stmts.iter_mut().chain(else_stmts) {
+                    self.qualify_stmt(stmt, var_binded);
+                }
+            }
+            Stmt::Do(action) => self.qualify(action, var_binded),
+        }
+    }
+
+    /// columns of the table edited are bound in its where clause and new values
+    fn qualify_edit(&self, edit: &mut RowEdit, var_binded: &HashSet<String>) {
+        let new_binds = self.row_binds(edit.table_name(), var_binded);
+        if let Some(cond) = edit.cond_mut() {
+            self.qualify(cond, var_binded);
+        }
+        match edit {
+            RowEdit::Update { table_name, sets, where_clause, .. } => {
+                for set in sets {
+                    self.qualify(&mut set.src, &new_binds);
+                }
+                self.qualify(where_clause, &new_binds);
+                self.rename(table_name);
+            }
+            RowEdit::Delete { table_name, where_clause, .. } => {
+                self.qualify(where_clause, &new_binds);
+                self.rename(table_name);
+            }
+        }
+    }
+}
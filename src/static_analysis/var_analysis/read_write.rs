@@ -3,7 +3,7 @@ use std::{
     hash::Hash,
 };
 
-use crate::ast::{Assn, Expr};
+use crate::ast::{Assn, Expr, Insert, RowEdit, Stmt};
 
 impl Expr {
     /// return free variables in expr wrt var_binded, used for
@@ -15,7 +15,7 @@ impl Expr {
         var_binded: &HashSet<String>, // should be initialized by all reactive name declared in the service
     ) -> HashSet<String> {
         match self {
-            Expr::Number { .. } | Expr::Bool { .. } | Expr::String { .. } | Expr::Table { .. }=> HashSet::new(),
+            Expr::Number { .. } | Expr::Float { .. } | Expr::Bool { .. } | Expr::String { .. } | Expr::Table { .. }=> HashSet::new(),
             Expr::Variable { ident } => {
                 if var_binded.contains(ident) {
                     HashSet::new()
@@ -26,7 +26,7 @@ impl Expr {
             Expr::KeyVal { value, .. } => {
                 value.free_var(reactive_names, var_binded)
             }
-            Expr::Vector { val } => {
+            Expr::Vector { val } | Expr::List { val } => {
                 let mut free_vars = HashSet::new();
                 for item in val {
                     free_vars.extend(item.free_var(reactive_names, var_binded));
@@ -45,11 +45,27 @@ impl Expr {
                 free_vars.extend(expr2.free_var(reactive_names, var_binded));
                 free_vars
             }
-            Expr::Func { params, body } => {
+            Expr::Func { params, body, .. } => {
                 let mut new_binds = var_binded.clone();
                 new_binds.extend(params.iter().cloned());
                 body.free_var(reactive_names, &new_binds)
             }
+            Expr::Let { name, val, body, .. } => {
+                let mut free_vars = val.free_var(reactive_names, var_binded);
+                let mut new_binds = var_binded.clone();
+                new_binds.insert(name.clone());
+                free_vars.extend(body.free_var(reactive_names, &new_binds));
+                free_vars
+            }
+            Expr::Match { expr, arms } => {
+                let mut free_vars = expr.free_var(reactive_names, var_binded);
+                for arm in arms {
+                    let mut new_binds = var_binded.clone();
+                    new_binds.extend(arm.pattern.binds());
+                    free_vars.extend(arm.body.free_var(reactive_names, &new_binds));
+                }
+                free_vars
+            }
             Expr::FuncApply { func, args } => {
                 let mut free_vars = func.free_var(reactive_names, var_binded);
                 for arg in args {
@@ -78,13 +94,37 @@ impl Expr {
                 // we exclude reactive names from free_vars in action
                 free_vars.difference(reactive_names).cloned().collect()
             }
+            // as in an action, names read by assigns, inserts and conditions
+            // are read at the time of the transaction, while a nested action
+            // is evaluated along with the action, e.g. `do withdraw(n)`
+            Expr::ActionBlock { stmts } => {
+                let mut free_vars = HashSet::new();
+                let mut nested_vars = HashSet::new();
+                for stmt in stmts {
+                    stmt.free_var(reactive_names, var_binded, &mut free_vars, &mut nested_vars);
+                }
+
+                free_vars.retain(|name| !reactive_names.contains(name));
+                free_vars.extend(nested_vars);
+                free_vars
+            }
             Expr::Select { table_name, where_clause, .. } => {
+                // where clause is evaluated with the columns of each row in
+                // scope, other names in it are columns
                 let mut free_vars = where_clause.free_var(reactive_names, var_binded);
+                free_vars.retain(|name| reactive_names.contains(name));
                 free_vars.insert(table_name.clone());
                 free_vars
             }
             Expr::TableColumn { table_name, .. } => {
-                HashSet::from([table_name.to_string()])
+                if var_binded.contains(table_name) {
+                    HashSet::new() // field of a bound record
+                } else {
+                    HashSet::from([table_name.to_string()])
+                }
+            }
+            Expr::RecordField { expr, .. } | Expr::TupleIndex { expr, .. } => {
+                expr.free_var(reactive_names, var_binded)
             }
             Expr::Fold { args } => { 
                 let mut free_vars = HashSet::new();
@@ -94,17 +134,113 @@ impl Expr {
                 
                 free_vars
             }
+            Expr::ListOp { args, .. } | Expr::Variant { args, .. } => {
+                let mut free_vars = HashSet::new();
+                for arg in args {
+                    free_vars.extend(arg.free_var(reactive_names, var_binded));
+                }
+                free_vars
+            }
+            Expr::Spanned { expr, .. } => expr.free_var(reactive_names, var_binded),
+        }
+    }
+}
+
+impl Stmt {
+    /// collect free variables of an action statement, those of nested actions
+    /// in `nested_vars` and the others in `free_vars`
+    fn free_var(
+        &self,
+        reactive_names: &HashSet<String>,
+        var_binded: &HashSet<String>,
+        free_vars: &mut HashSet<String>,
+        nested_vars: &mut HashSet<String>,
+    ) {
+        match self {
+            Stmt::Assn(assn) => free_vars.extend(assn.src.free_var(reactive_names, var_binded)),
+            Stmt::Insert(insert) => free_vars.extend(insert.row.free_var(reactive_names, var_binded)),
+            // names in an edit are columns of its rows, args of the action, or
+            // reactive names read at the time of the transaction, which are
+            // excluded from free vars of an action anyway
+            Stmt::Edit(_) => {}
+            Stmt::If { cond, then_stmts, else_stmts } => {
+                free_vars.extend(cond.free_var(reactive_names, var_binded));
+                for stmt in then_stmts.iter().chain(else_stmts) {
+                    stmt.free_var(reactive_names, var_binded, free_vars, nested_vars);
+                }
+            }
+            // the action done is evaluated along, its args are read at the
+            // time of the transaction, e.g. `f` but not `x` of `do f(x)`
+            Stmt::Do(action) => {
+                let mut action = action;
+                while let Expr::FuncApply { func, args } = action.unspanned() {
+                    for arg in args {
+                        free_vars.extend(arg.free_var(reactive_names, var_binded));
+                    }
+                    action = func;
+                }
+                nested_vars.extend(action.free_var(reactive_names, var_binded));
+            }
+        }
+    }
+}
+
+impl RowEdit {
+    /// names read by the where clause and new values of an edit, as in
+    /// select, its columns are among them
+    pub fn free_var(&self, reactive_names: &HashSet<String>, var_binded: &HashSet<String>) -> HashSet<String> {
+        let mut free_vars = HashSet::new();
+        match self {
+            RowEdit::Update { sets, where_clause, .. } => {
+                for set in sets {
+                    free_vars.extend(set.src.free_var(reactive_names, var_binded));
+                }
+                free_vars.extend(where_clause.free_var(reactive_names, var_binded));
+            }
+            RowEdit::Delete { where_clause, .. } => {
+                free_vars.extend(where_clause.free_var(reactive_names, var_binded));
+            }
+        }
+        if let RowEdit::Update { cond: Some(cond), .. } | RowEdit::Delete { cond: Some(cond), .. } = self {
+            free_vars.extend(cond.free_var(reactive_names, var_binded));
         }
+        free_vars
+    }
+}
+
+impl Expr {
+    /// whether a def `name` of this expr is a recursive function, i.e. a
+    /// function referring to `name` in its body: the reference is only
+    /// evaluated once the function is applied, so it is not a dependency
+    pub fn is_rec_fn(&self, name: &String) -> bool {
+        let names = HashSet::from([name.clone()]);
+        matches!(self.unspanned(), Expr::Func { .. }) && self.free_var(&names, &HashSet::new()).contains(name)
     }
 }
 
-/// Calculate direct read set
+/// Calculate direct read set, of assigns, inserted rows and edits
 /// used for lock acquisition
-pub fn calc_read_sets(assns: &Vec<Assn>, reactive_names: &HashSet<String>) -> HashSet<String> {
+pub fn calc_read_sets(
+    assns: &Vec<Assn>,
+    inserts: &Vec<Insert>,
+    edits: &Vec<RowEdit>,
+    reactive_names: &HashSet<String>,
+) -> HashSet<String> {
     let mut direct_reads = HashSet::new();
     for assn in assns {
         direct_reads.extend(assn.src.free_var(reactive_names, &HashSet::new()));
     }
+    for insert in inserts {
+        direct_reads.extend(insert.row.free_var(reactive_names, &HashSet::new()));
+        if let Some(cond) = &insert.cond {
+            direct_reads.extend(cond.free_var(reactive_names, &HashSet::new()));
+        }
+    }
+    // other names in an edit are columns of its rows
+    for edit in edits {
+        let free_vars = edit.free_var(reactive_names, &HashSet::new());
+        direct_reads.extend(free_vars.into_iter().filter(|name| reactive_names.contains(name)));
+    }
 
     direct_reads
 }
@@ -118,3 +254,133 @@ pub fn calc_write_set(assns: &Vec<Assn>) -> HashSet<String> {
     }
     writes
 }
+
+/// calculate all names an expression may read or write once it is evaluated,
+/// including names touched by actions nested in it, e.g.
+/// `fn z => action { x = y + z; }` touches both x and y
+/// used for exporting the actors behind a service's public names
+pub fn calc_touched_names(expr: &Expr, reactive_names: &HashSet<String>) -> HashSet<String> {
+    let mut names = expr.free_var(reactive_names, &HashSet::new());
+    let mut writes = HashSet::new();
+    collect_action_names(expr, reactive_names, &mut names, &mut writes);
+    names.extend(writes);
+    names
+}
+
+/// reactive names read by an expr of an action, which are read at the time
+/// of the transaction so are not among the free vars of the action
+fn action_reads(expr: &Expr, reactive_names: &HashSet<String>) -> HashSet<String> {
+    let mut reads = expr.free_var(&HashSet::new(), &HashSet::new());
+    reads.retain(|name| reactive_names.contains(name));
+    reads
+}
+
+/// collect the names actions in `expr` write, vars assigned and tables
+/// inserted into or edited, and the reactive names they read
+fn collect_action_names(
+    expr: &Expr,
+    reactive_names: &HashSet<String>,
+    reads: &mut HashSet<String>,
+    writes: &mut HashSet<String>,
+) {
+    let mut collect = |expr: &Expr| collect_action_names(expr, reactive_names, reads, writes);
+    match expr {
+        Expr::Number { .. }
+        | Expr::Float { .. }
+        | Expr::Bool { .. }
+        | Expr::String { .. }
+        | Expr::Variable { .. }
+        | Expr::TableColumn { .. } => {}
+        Expr::KeyVal { value: expr, .. }
+        | Expr::RecordField { expr, .. }
+        | Expr::TupleIndex { expr, .. }
+        | Expr::Unop { expr, .. }
+        | Expr::Func { body: expr, .. }
+        | Expr::Select { where_clause: expr, .. }
+        | Expr::Spanned { expr, .. } => collect(expr),
+        Expr::Vector { val: exprs }
+        | Expr::List { val: exprs }
+        | Expr::Table { records: exprs, .. }
+        | Expr::Fold { args: exprs }
+        | Expr::ListOp { args: exprs, .. }
+        | Expr::Variant { args: exprs, .. } => exprs.iter().for_each(collect),
+        Expr::Binop { expr1, expr2, .. } | Expr::Let { val: expr1, body: expr2, .. } => {
+            collect(expr1);
+            collect(expr2);
+        }
+        Expr::If { cond, expr1, expr2 } => {
+            collect(cond);
+            collect(expr1);
+            collect(expr2);
+        }
+        Expr::Match { expr, arms } => {
+            collect(expr);
+            arms.iter().for_each(|arm| collect(&arm.body));
+        }
+        Expr::FuncApply { func, args } => {
+            collect(func);
+            args.iter().for_each(collect);
+        }
+        Expr::Action { assns, inserts, edits } => {
+            for assn in assns {
+                writes.insert(assn.dest.clone());
+                reads.extend(action_reads(&assn.src, reactive_names));
+            }
+            for insert in inserts {
+                writes.insert(insert.table_name.clone());
+                reads.extend(action_reads(&insert.row, reactive_names));
+                if let Some(cond) = &insert.cond {
+                    reads.extend(action_reads(cond, reactive_names));
+                }
+            }
+            for edit in edits {
+                writes.insert(edit.table_name().clone());
+                reads.extend(edit_reads(edit, reactive_names));
+            }
+        }
+        Expr::ActionBlock { stmts } => {
+            stmts.iter().for_each(|stmt| collect_stmt_names(stmt, reactive_names, reads, writes))
+        }
+    }
+}
+
+/// other names in an edit are columns of the rows it edits
+fn edit_reads(edit: &RowEdit, reactive_names: &HashSet<String>) -> HashSet<String> {
+    let mut reads = edit.free_var(&HashSet::new(), &HashSet::new());
+    reads.retain(|name| reactive_names.contains(name));
+    reads
+}
+
+fn collect_stmt_names(
+    stmt: &Stmt,
+    reactive_names: &HashSet<String>,
+    reads: &mut HashSet<String>,
+    writes: &mut HashSet<String>,
+) {
+    match stmt {
+        Stmt::Assn(assn) => {
+            writes.insert(assn.dest.clone());
+            reads.extend(action_reads(&assn.src, reactive_names));
+        }
+        Stmt::Insert(insert) => {
+            writes.insert(insert.table_name.clone());
+            reads.extend(action_reads(&insert.row, reactive_names));
+        }
+        Stmt::Edit(edit) => {
+            writes.insert(edit.table_name().clone());
+            reads.extend(edit_reads(edit, reactive_names));
+        }
+        Stmt::If { cond, then_stmts, else_stmts } => {
+            reads.extend(action_reads(cond, reactive_names));
+            for stmt in then_stmts.iter().chain(else_stmts) {
+                collect_stmt_names(stmt, reactive_names, reads, writes);
+            }
+        }
+        // the action done is read like any other name, e.g. `withdraw` of
+        // `do withdraw(n)`, along with its args
+        Stmt::Do(action) => {
+            reads.extend(action_reads(action, reactive_names));
+            collect_action_names(action, reactive_names, reads, writes);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! services on separate nodes, talking over a unix socket
+
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_meerkat_distributed");
+
+/// node serving `srv` of `file` until dropped
+struct Node(Child);
+
+impl Node {
+    fn start(file: &Path, srv: &str, addr: &str) -> Node {
+        let mut node = Node(
+            Command::new(BIN)
+                .arg("-f")
+                .arg(file)
+                .args(["-l", addr, "-s", srv])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("node starts"),
+        );
+
+        // other nodes can look its services up once it serves them
+        let mut lines = BufReader::new(node.0.stdout.take().unwrap()).lines();
+        for line in lines.by_ref() {
+            if line.expect("node prints").starts_with("Serving") {
+                // keep draining, a closed pipe would fail its later prints
+                std::thread::spawn(move || lines.for_each(drop));
+                return node;
+            }
+        }
+        panic!("node exited before serving {}", srv);
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// run the tests of `source`, counter served by one node, dashboard
+/// testing on another
+fn run_on_two_nodes(name: &str, source: &str) -> Output {
+    let dir = std::env::temp_dir();
+    let file = dir.join(format!("{}_{}.meerkat", name, std::process::id()));
+    let sock = dir.join(format!("{}_{}.sock", name, std::process::id()));
+    std::fs::write(&file, source).unwrap();
+    let _ = std::fs::remove_file(&sock);
+    let addr = format!("unix://{}", sock.display());
+
+    let _counter = Node::start(&file, "counter", &addr);
+    let output = Command::new(BIN)
+        .arg("-f")
+        .arg(&file)
+        .args(["-s", "dashboard", "-p", &addr])
+        .stderr(Stdio::null())
+        .output()
+        .expect("tests run");
+    let _ = std::fs::remove_file(&file);
+    output
+}
+
+#[test]
+fn imported_service_on_another_node() {
+    let source = std::fs::read_to_string("tests/test_import.meerkat").unwrap();
+    let output = run_on_two_nodes("import_pass", &source);
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", out);
+    assert!(out.contains("total: 7 passed, 0 failed, 0 timed out"), "{}", out);
+}
+
+#[test]
+fn failed_assert_across_nodes_fails_the_run() {
+    let source = std::fs::read_to_string("tests/test_import.meerkat")
+        .unwrap()
+        .replace("assert(total == 3);", "assert(total == 4);");
+    let output = run_on_two_nodes("import_fail", &source);
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", out);
+    assert!(out.contains("fail test total == 4"), "{}", out);
+    assert!(out.contains("total: 6 passed, 1 failed, 0 timed out"), "{}", out);
+}
@@ -0,0 +1,59 @@
+//! commands typed at the repl prompt, driven by piped commands
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_repl(args: &[&str], commands: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_meerkat_distributed"))
+        .args(args)
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("repl starts");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("repl exits");
+    assert!(output.status.success(), "repl exit: {}", output.status);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn columns_of_an_edit_are_not_names_of_the_service() {
+    let out = run_repl(
+        &["-f", "tests/test_table_edit.meerkat"],
+        "do action { update items set price = price * 2 where id == 1; };\n:quit\n",
+    );
+    // rejected for the table it writes, not for its columns
+    assert!(out.contains("\"items\" is not public in service \"shop\""), "{}", out);
+    assert!(!out.contains("\"price\" is not public"), "{}", out);
+}
+
+#[test]
+fn input_is_typechecked_before_the_manager_sees_it() {
+    let out = run_repl(
+        &["-f", "tests/test_table_edit.meerkat"],
+        "assert(total + 1);\n:print total + \"a\"\n:print total\n:quit\n",
+    );
+    assert!(out.contains("Assert statement requires bool expression, got int"), "{}", out);
+    assert!(out.contains("<input>:1:1"), "{}", out);
+    assert!(out.contains("<input>:1:8"), "{}", out);
+    assert!(!out.contains("test assert"), "{}", out);
+    assert!(out.contains("total = 0"), "{}", out);
+}
+
+#[test]
+fn writes_are_printed_with_source_names() {
+    let out = run_repl(
+        &["-f", "tests/test_import.meerkat"],
+        ":use dashboard\ndo inc;\ndo bump;\n:quit\n",
+    );
+    // count is a private var of the imported counter
+    assert!(out.contains("committed, wrote [\"count\"]"), "{}", out);
+    assert!(out.contains("committed, wrote [\"offset\"]"), "{}", out);
+}
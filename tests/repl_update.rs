@@ -0,0 +1,53 @@
+//! code update through the repl, driven by piped commands
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_repl(file: &str, commands: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_meerkat_distributed"))
+        .args(["-f", file, "-i"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("repl starts");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("repl exits");
+    assert!(output.status.success(), "repl exit: {}", output.status);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn importer_calls_updated_action() {
+    let out = run_repl(
+        "tests/test_import.meerkat",
+        ":update tests/test_import_v2.meerkat\n:quit\n",
+    );
+    assert!(out.contains("Service dashboard updated"), "{}", out);
+    assert!(out.contains("total: 4 passed, 0 failed, 0 timed out"), "{}", out);
+}
+
+#[test]
+fn kept_importer_refreshes_imports() {
+    // only counter is updated, dashboard keeps running its code
+    let v2 = std::fs::read_to_string("tests/test_import_v2.meerkat").unwrap();
+    let counter = &v2[..v2.find("service dashboard").unwrap()];
+    let file = std::env::temp_dir().join(format!("counter_v2_{}.meerkat", std::process::id()));
+    std::fs::write(&file, counter).unwrap();
+
+    let out = run_repl(
+        "tests/test_import.meerkat",
+        &format!(
+            ":update {}\n:use dashboard\ndo inc;\n:print total\n:quit\n",
+            file.display()
+        ),
+    );
+    let _ = std::fs::remove_file(&file);
+    assert!(out.contains("Service dashboard imports refreshed"), "{}", out);
+    assert!(out.contains("total = 201"), "{}", out);
+}
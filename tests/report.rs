@@ -0,0 +1,41 @@
+//! test results written as JUnit XML or JSON
+
+use std::process::Command;
+
+const REJECTED: &str = "service s {
+  var n = 1;
+  pub def zero = action { n = 0; };
+  pub def inv = action { n = 10 / n; };
+}
+@test(s) {
+  do zero;
+  do inv;
+  assert(n == 0);
+  assert(n == 1);
+}
+";
+
+fn report(source: &str, format: &str) -> String {
+    let file = std::env::temp_dir().join(format!("report_{}_{}.meerkat", format, std::process::id()));
+    std::fs::write(&file, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_meerkat_distributed"))
+        .args(["-f", file.to_str().unwrap(), "--format", format])
+        .output()
+        .expect("meerkat runs");
+    let _ = std::fs::remove_file(&file);
+    assert_eq!(output.status.code(), Some(1), "failing tests passed");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn rejected_do_carries_its_reason() {
+    let xml = report(REJECTED, "junit");
+    assert!(xml.contains("<testsuites tests=\"3\" failures=\"2\" errors=\"0\""), "{}", xml);
+    assert!(xml.contains("<failure message=\"rejected: evaluating n = 10 / 0 failed: "), "{}", xml);
+    assert!(xml.contains("division by zero in 10 / 0\"/>"), "{}", xml);
+    assert!(xml.contains("<failure message=\"assert evaluated to false\"/>"), "{}", xml);
+
+    let json = report(REJECTED, "json");
+    assert!(json.contains("\"reason\": \"rejected: evaluating "), "{}", json);
+    assert!(json.contains("\"reason\": null"), "{}", json);
+}
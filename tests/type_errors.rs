@@ -0,0 +1,26 @@
+//! type errors are reported at the sub-expression that fails to typecheck
+
+use std::process::Command;
+
+fn check(source: &str) -> String {
+    let file = std::env::temp_dir().join(format!("type_errors_{}.meerkat", std::process::id()));
+    std::fs::write(&file, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_meerkat_distributed"))
+        .args(["-f", file.to_str().unwrap()])
+        .output()
+        .expect("meerkat runs");
+    let _ = std::fs::remove_file(&file);
+    assert!(!output.status.success(), "ill-typed program ran");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn error_points_at_failing_subexpr() {
+    let err = check(
+        "service s {\n  var n = 1;\n  def q = n + 2 * (true || 3);\n}\n@test(s) {\n  assert(q == 1 && missing);\n}\n",
+    );
+    assert!(err.contains(":3:20\n"), "{}", err);
+    assert!(err.contains("  |                    ^^^^^^^^^\n"), "{}", err);
+    assert!(err.contains(":6:20\n"), "{}", err);
+    assert!(err.contains("  |                    ^^^^^^^\n"), "{}", err);
+}